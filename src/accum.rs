@@ -1,6 +1,10 @@
 use crate::*;
 pub trait Accumulator {
     fn accum(&mut self, color: &(RGB, usize));
+    //'events' describe the path from the eye to the light (see lpe)
+    fn accum_path(&mut self, color: &RGB, events: &[lpe::Event]) {
+        self.accum(&(*color, events.len().max(2) - 2))
+    }
     fn merge(&mut self, another: &Self);
     fn is_finite(&self) -> bool;
     fn reset(&mut self);
@@ -70,6 +74,11 @@ where
         self.1.accum(color);
     }
 
+    fn accum_path(&mut self, color: &RGB, events: &[lpe::Event]) {
+        self.0.accum_path(color, events);
+        self.1.accum_path(color, events);
+    }
+
     fn merge(&mut self, another: &Self) {
         self.0.merge(&another.0);
         self.1.merge(&another.1);
@@ -107,6 +116,10 @@ impl<T: Accumulator> Accumulator for Pixel<T> {
         self.accum.accum(color);
         self.samples += 1;
     }
+    fn accum_path(&mut self, color: &RGB, events: &[lpe::Event]) {
+        self.accum.accum_path(color, events);
+        self.samples += 1;
+    }
     fn merge(&mut self, another: &Self) {
         self.accum.merge(&another.accum);
         self.samples += another.samples;
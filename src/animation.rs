@@ -0,0 +1,305 @@
+//keyframed animation of the camera, object transforms and light intensities
+use crate::*;
+use camera::{Camera, PinHole, ReverseSampleResult, Shutter};
+use nalgebra::{Isometry3, Translation3};
+use rand::prelude::*;
+use scene::Scene;
+
+pub trait Interpolate: Clone {
+    //t = 0 gives self, t = 1 gives another
+    fn interpolate(&self, another: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, another: &Self, t: f32) -> Self {
+        self + (another - self) * t
+    }
+}
+
+impl Interpolate for P3 {
+    fn interpolate(&self, another: &Self, t: f32) -> Self {
+        self + (another - self) * t
+    }
+}
+
+impl Interpolate for V3 {
+    fn interpolate(&self, another: &Self, t: f32) -> Self {
+        self.lerp(another, t)
+    }
+}
+
+impl Interpolate for RGB {
+    fn interpolate(&self, another: &Self, t: f32) -> Self {
+        *self * (1.0 - t) + *another * t
+    }
+}
+
+//the translation is interpolated linearly and the rotation spherically
+impl Interpolate for Isometry3<f32> {
+    fn interpolate(&self, another: &Self, t: f32) -> Self {
+        let tr = self.translation.vector.lerp(&another.translation.vector, t);
+        let rot = self
+            .rotation
+            .try_slerp(&another.rotation, t, 1e-6)
+            .unwrap_or(self.rotation);
+        Isometry3::from_parts(Translation3::from(tr), rot)
+    }
+}
+
+//values at given times. the first and last values are held outside the keys.
+#[derive(Clone, Debug)]
+pub struct Keyframes<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Interpolate> Keyframes<T> {
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        assert!(!keys.is_empty());
+        keys.sort_by(|(t0, _), (t1, _)| t0.partial_cmp(t1).unwrap());
+        Keyframes { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Keyframes {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys[..]
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    pub fn at(&self, time: f32) -> T {
+        let i = self.keys.iter().position(|(t, _)| time < *t);
+        match i {
+            Some(0) => self.keys[0].1.clone(),
+            None => self.keys[self.keys.len() - 1].1.clone(),
+            Some(i) => {
+                let (t0, ref a) = self.keys[i - 1];
+                let (t1, ref b) = self.keys[i];
+                a.interpolate(b, (time - t0) / (t1 - t0))
+            }
+        }
+    }
+
+    //the same values restricted to [begin, end]
+    pub fn clip(&self, begin: f32, end: f32) -> Self {
+        if self.is_constant() {
+            return self.clone();
+        }
+        let mut keys = vec![(begin, self.at(begin))];
+        keys.extend(
+            self.keys
+                .iter()
+                .filter(|(t, _)| begin < *t && *t < end)
+                .cloned(),
+        );
+        if begin < end {
+            keys.push((end, self.at(end)));
+        }
+        Keyframes { keys }
+    }
+}
+
+//inputs of PinHole::new
+#[derive(Clone, Debug)]
+pub struct PinHoleKeys {
+    pub origin: Keyframes<P3>,
+    pub view_at: Keyframes<P3>,
+    pub view_up: Keyframes<V3>,
+    pub fov_degree: Keyframes<f32>,
+    pub hole_radius: Option<f32>,
+}
+
+impl PinHoleKeys {
+    pub fn at(&self, time: f32) -> PinHole {
+        PinHole::new(
+            self.origin.at(time),
+            self.view_at.at(time),
+            self.view_up.at(time),
+            self.fov_degree.at(time),
+            self.hole_radius,
+        )
+    }
+}
+
+//the pinhole of the keys at the time of each ray. the film uvs are those of the pinhole at
+//the reference time, whose film width is kept while the fov changes.
+#[derive(Clone)]
+pub struct MovingPinHole {
+    keys: PinHoleKeys,
+    reference: PinHole,
+}
+
+impl MovingPinHole {
+    pub fn new(keys: PinHoleKeys, reference_time: f32) -> Self {
+        MovingPinHole {
+            reference: keys.at(reference_time),
+            keys,
+        }
+    }
+}
+
+impl Camera for MovingPinHole {
+    fn film_width(&self) -> f32 {
+        self.reference.film_width()
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray> {
+        self.reference.sample_ray(u, v, rng)
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
+        self.reference.sample_lens_point(rng)
+    }
+
+    fn importance(&self, lens_point: &P3, dir: &V3) -> Option<camera::FilmImportance> {
+        self.reference.importance(lens_point, dir)
+    }
+
+    fn sample_ray_at<R: Rng + ?Sized>(
+        &self,
+        u: f32,
+        v: f32,
+        time: f32,
+        rng: &mut R,
+    ) -> Option<Ray> {
+        self.keys.at(time).sample_ray(u, v, rng)
+    }
+
+    fn sample_film_uv_at<R: Rng + ?Sized>(
+        &self,
+        p: &P3,
+        time: f32,
+        rng: &mut R,
+    ) -> Option<ReverseSampleResult> {
+        let camera = self.keys.at(time);
+        //the film area per pixel scales with the square of the film width
+        let scale = (self.film_width() / camera.film_width()).powi(2);
+        camera
+            .sample_film_uv(p, rng)
+            .map(|sample| ReverseSampleResult {
+                measure_conv: sample.measure_conv * scale,
+                ..sample
+            })
+    }
+}
+
+//an object whose transform is given by its motion and whose emission may change
+#[derive(Clone)]
+pub struct AnimatedObject {
+    pub object: object::SimpleObject,
+    pub emission: Option<Keyframes<RGB>>,
+}
+
+impl From<object::SimpleObject> for AnimatedObject {
+    fn from(object: object::SimpleObject) -> Self {
+        AnimatedObject {
+            object,
+            emission: None,
+        }
+    }
+}
+
+pub struct Animation {
+    pub camera: PinHoleKeys,
+    pub objects: Vec<AnimatedObject>,
+    pub fps: f32,
+    //fraction of a frame during which the shutter is open
+    pub shutter: f32,
+}
+
+impl Animation {
+    pub fn frame_time(&self, frame: usize) -> f32 {
+        frame as f32 / self.fps
+    }
+
+    fn shutter_interval(&self, frame: usize) -> (f32, f32) {
+        let open = self.frame_time(frame);
+        (open, open + self.shutter / self.fps)
+    }
+
+    pub fn camera_at(&self, frame: usize) -> Shutter<MovingPinHole> {
+        let (open, close) = self.shutter_interval(frame);
+        Shutter::new(MovingPinHole::new(self.camera.clone(), open), open, close)
+    }
+
+    //if true, the scene of any frame can be used for all frames
+    pub fn is_scene_static(&self) -> bool {
+        self.objects.iter().all(|o| {
+            o.object.motion.iter().all(object::Motion::is_static)
+                && o.emission.iter().all(Keyframes::is_constant)
+        })
+    }
+
+    pub fn scene_at(&self, frame: usize) -> Scene {
+        let (open, close) = self.shutter_interval(frame);
+        let objects = self
+            .objects
+            .iter()
+            .map(|o| {
+                let mut object = o.object.clone();
+                //keeps the aabbs in the bvh tight around the frame
+                object.motion = object.motion.map(|m| m.clip(open, close));
+                if let Some(emission) = &o.emission {
+                    let color = emission::EmissionColor::Texture(emission.at(open).into());
+                    //the profile and the sides of the emission are kept
+                    object.emission = Some(match object.emission {
+                        Some(e) => emission::Emission { color, ..e },
+                        None => emission::Emission::from(emission.at(open)),
+                    });
+                }
+                object
+            })
+            .collect();
+        Scene::new(objects)
+    }
+}
+
+#[test]
+fn test_keyframes() {
+    let keys = Keyframes::new(vec![(1.0, 10.0), (0.0, 0.0), (2.0, 0.0)]);
+    assert_eq!(keys.at(-1.0), 0.0);
+    assert_eq!(keys.at(0.5), 5.0);
+    assert_eq!(keys.at(1.5), 5.0);
+    assert_eq!(keys.at(3.0), 0.0);
+
+    let clipped = keys.clip(0.5, 1.5);
+    assert_eq!(clipped.keys().len(), 3);
+    assert_eq!(clipped.at(0.0), 5.0);
+    assert_eq!(clipped.at(1.0), 10.0);
+    assert_eq!(clipped.at(1.25), 7.5);
+}
+
+#[test]
+fn test_camera_motion() {
+    let keys = PinHoleKeys {
+        origin: Keyframes::new(vec![
+            (0.0, P3::new(0.0, 0.0, 5.0)),
+            (1.0, P3::new(1.0, 0.0, 5.0)),
+        ]),
+        view_at: Keyframes::constant(P3::origin()),
+        view_up: Keyframes::constant(V3::y()),
+        fov_degree: Keyframes::new(vec![(0.0, 45.0), (1.0, 30.0)]),
+        hole_radius: None,
+    };
+    let camera = Shutter::new(MovingPinHole::new(keys.clone(), 0.0), 0.0, 1.0);
+    let mut rng = SmallRng::seed_from_u64(0);
+    for _ in 0..10 {
+        let ray = camera.sample_ray(0.2, -0.1, &mut rng).unwrap();
+        assert!((ray.origin - keys.origin.at(ray.time)).norm() < 1e-5);
+        //the uv of the film at the reference time are kept
+        let film = camera
+            .sample_film_uv_at(&ray.at(3.0), ray.time, &mut rng)
+            .unwrap();
+        assert!((film.u - 0.2).abs() < 1e-4 && (film.v + 0.1).abs() < 1e-4);
+        let at_time = keys.at(ray.time);
+        let expected = at_time.sample_film_uv(&ray.at(3.0), &mut rng).unwrap();
+        let scale = (camera.film_width() / at_time.film_width()).powi(2);
+        assert!((film.measure_conv - expected.measure_conv * scale).abs() < 1e-4);
+    }
+}
@@ -9,10 +9,65 @@ pub struct ReverseSampleResult {
     pub measure_conv: f32,
 }
 
+pub struct FilmImportance {
+    pub u: f32,
+    pub v: f32,
+    //film area per unit solid angle of the ray leaving the lens (dA(x_film) / dω)
+    pub importance: f32,
+}
+
 pub trait Camera {
     fn film_width(&self) -> f32;
-    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Ray;
-    fn sample_film_uv<R: Rng + ?Sized>(&self, p: &P3, rng: &mut R) -> Option<ReverseSampleResult>;
+    //returns None if no ray reaches the film position (e.g. vignetting)
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray>;
+    //uniformly samples a point on the lens (or the pinhole) in world coordinates
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3;
+    //finds the film position whose ray leaves lens_point in direction dir
+    fn importance(&self, lens_point: &P3, dir: &V3) -> Option<FilmImportance>;
+    //time at which the shutter is open; static cameras see only time 0
+    fn sample_time<R: Rng + ?Sized>(&self, _rng: &mut R) -> f32 {
+        0.0
+    }
+    //the ray of the camera at the given time, for cameras moving while the shutter is open
+    fn sample_ray_at<R: Rng + ?Sized>(
+        &self,
+        u: f32,
+        v: f32,
+        _time: f32,
+        rng: &mut R,
+    ) -> Option<Ray> {
+        self.sample_ray(u, v, rng)
+    }
+    fn sample_film_uv_at<R: Rng + ?Sized>(
+        &self,
+        p: &P3,
+        _time: f32,
+        rng: &mut R,
+    ) -> Option<ReverseSampleResult> {
+        self.sample_film_uv(p, rng)
+    }
+
+    fn sample_film_uv<R: Rng + ?Sized>(&self, p: &P3, rng: &mut R) -> Option<ReverseSampleResult> {
+        let lens_point = self.sample_lens_point(rng);
+        let to_p = p - lens_point;
+        let sq_dist = to_p.norm_squared();
+        self.importance(&lens_point, &(to_p / sq_dist.sqrt())).map(
+            |FilmImportance { u, v, importance }| ReverseSampleResult {
+                u,
+                v,
+                lens_point,
+                //dA(x_film) = importance * dω = importance * cos * dA(x) / r^2
+                measure_conv: importance / sq_dist,
+            },
+        )
+    }
+}
+
+fn sample_disk<R: Rng + ?Sized>(radius: f32, rng: &mut R) -> P3 {
+    use rand::distributions::Uniform;
+    let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+    let r = radius * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
+    P3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 
 #[derive(Clone)]
@@ -46,48 +101,42 @@ impl Camera for PinHole {
         self.film_width
     }
 
-    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Ray {
-        use rand::distributions::Uniform;
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray> {
         let origin = if let Some(radius) = self.hole_radius {
-            let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
-            let r = radius * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
-            P3::new(r * theta.cos(), r * theta.sin(), 0.0)
+            sample_disk(radius, rng)
         } else {
             P3::origin()
         };
 
-        self.lc.l2w()
-            * Ray::new(
-                origin,
-                V3::new(self.film_width * u, self.film_width * v, -1.0).normalize(),
-            )
+        Some(
+            self.lc.l2w()
+                * Ray::new(
+                    origin,
+                    V3::new(self.film_width * u, self.film_width * v, -1.0).normalize(),
+                ),
+        )
     }
-    fn sample_film_uv<R: Rng + ?Sized>(&self, p: &P3, rng: &mut R) -> Option<ReverseSampleResult> {
-        use rand::distributions::Uniform;
-        let p_local = self.lc.w2l() * p;
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
         let hole_point_local = if let Some(radius) = self.hole_radius {
-            let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
-            let r = radius * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
-            P3::new(r * theta.cos(), r * theta.sin(), 0.0)
+            sample_disk(radius, rng)
         } else {
             P3::origin()
         };
+        self.lc.l2w() * hole_point_local
+    }
 
-        if p_local[2] >= 0.0 {
+    fn importance(&self, _lens_point: &P3, dir: &V3) -> Option<FilmImportance> {
+        let dir_local = (self.lc.w2l() * dir).normalize();
+        let cos_theta = -dir_local[2];
+        if cos_theta <= 0.0 {
             None
         } else {
-            let film_point_local =
-                hole_point_local + (p_local - hole_point_local) / p_local[2].abs();
-            let film_to_hole = hole_point_local - film_point_local;
-            let sq_dist_fh = film_to_hole.norm_squared();
-            let sq_dist_hp = (p_local - hole_point_local).norm_squared();
-            let cos_theta = film_to_hole[2].abs() / sq_dist_fh.sqrt();
-
-            Some(ReverseSampleResult {
-                u: film_point_local[0] / self.film_width,
-                v: film_point_local[1] / self.film_width,
-                lens_point: self.lc.l2w() * hole_point_local,
-                measure_conv: sq_dist_fh / sq_dist_hp / cos_theta,
+            //the film is placed at distance 1 from the hole
+            Some(FilmImportance {
+                u: dir_local[0] / cos_theta / self.film_width,
+                v: dir_local[1] / cos_theta / self.film_width,
+                importance: 1.0 / (cos_theta * cos_theta * cos_theta),
             })
         }
     }
@@ -154,28 +203,373 @@ impl ThinLens {
     }
 }
 
+impl ThinLens {
+    //distance from the lens to the plane in focus
+    fn focus_distance(&self) -> f32 {
+        let a = self.film_distance;
+        let f = self.focal_length;
+        f * a / (a - f)
+    }
+}
+
 impl Camera for ThinLens {
     fn film_width(&self) -> f32 {
         self.film_width
     }
-    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Ray {
-        use rand::distributions::Uniform;
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray> {
         let a = self.film_distance;
         let f = self.focal_length;
-        let lens_point = {
-            let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
-            let r = self.radius * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
-            P3::new(r * theta.cos(), r * theta.sin(), 0.0)
-        };
+        let lens_point = sample_disk(self.radius, rng);
 
         let ray_to = f / (a - f) * P3::new(u * self.film_width, v * self.film_width, -a);
-        self.lc.l2w() * Ray::from_to(&lens_point, &ray_to)
+        Some(self.lc.l2w() * Ray::from_to(&lens_point, &ray_to))
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
+        self.lc.l2w() * sample_disk(self.radius, rng)
+    }
+
+    fn importance(&self, lens_point: &P3, dir: &V3) -> Option<FilmImportance> {
+        let a = self.film_distance;
+        let b = self.focus_distance();
+        let lens_point_local = self.lc.w2l() * lens_point;
+        let dir_local = (self.lc.w2l() * dir).normalize();
+        let cos_theta = -dir_local[2];
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        //the ray passes through the point in focus, which is imaged on the film
+        //through the center of the lens
+        let focus_point = lens_point_local + dir_local * (b / cos_theta);
+        let x = focus_point[0] * a / b;
+        let y = focus_point[1] * a / b;
+        //for a fixed lens point, the film behaves like that of a pinhole camera
+        //placed at distance a
+        Some(FilmImportance {
+            u: x / self.film_width,
+            v: y / self.film_width,
+            importance: a * a / (cos_theta * cos_theta * cos_theta),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Orthographic {
+    lc: LocalCoord,
+    film_width: f32,
+}
+
+impl Orthographic {
+    pub fn new(origin: P3, view_at: P3, view_up: V3, film_width: f32) -> Self {
+        let lc = LocalCoord::new_zy(&origin, &(origin - view_at), &view_up);
+        Orthographic { lc, film_width }
+    }
+}
+
+impl Camera for Orthographic {
+    fn film_width(&self) -> f32 {
+        self.film_width
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, _rng: &mut R) -> Option<Ray> {
+        let origin = P3::new(self.film_width * u, self.film_width * v, 0.0);
+        Some(self.lc.l2w() * Ray::new(origin, -V3::z()))
+    }
+
+    //the film itself is the lens
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
+        use rand::distributions::Uniform;
+        let u = Uniform::new(-0.5f32, 0.5);
+        let x = self.film_width * u.sample(rng);
+        let y = self.film_width * u.sample(rng);
+        self.lc.l2w() * P3::new(x, y, 0.0)
+    }
+
+    //the direction of rays is a delta distribution
+    fn importance(&self, _lens_point: &P3, _dir: &V3) -> Option<FilmImportance> {
+        None
+    }
+
+    fn sample_film_uv<R: Rng + ?Sized>(&self, p: &P3, _rng: &mut R) -> Option<ReverseSampleResult> {
+        let p_local = self.lc.w2l() * p;
+        if p_local[2] >= 0.0 {
+            None
+        } else {
+            //dA(x_film) = cos * dA(x)
+            Some(ReverseSampleResult {
+                u: p_local[0] / self.film_width,
+                v: p_local[1] / self.film_width,
+                lens_point: self.lc.l2w() * P3::new(p_local[0], p_local[1], 0.0),
+                measure_conv: 1.0,
+            })
+        }
+    }
+}
+
+//equidistant fisheye: the distance from the film center is proportional to the angle from the
+//view direction
+#[derive(Clone)]
+pub struct Fisheye {
+    lc: LocalCoord,
+    fov_rad: f32,
+}
+
+impl Fisheye {
+    //fov_degree is the angle covered by the width of the film, up to 360 degrees
+    pub fn new(origin: P3, view_at: P3, view_up: V3, fov_degree: f32) -> Self {
+        assert!(0.0 < fov_degree && fov_degree <= 360.0);
+        let lc = LocalCoord::new_zy(&origin, &(origin - view_at), &view_up);
+        Fisheye {
+            lc,
+            fov_rad: fov_degree * std::f32::consts::PI / 180.0,
+        }
+    }
+}
+
+impl Camera for Fisheye {
+    //the film is measured in radians
+    fn film_width(&self) -> f32 {
+        self.fov_rad
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, _rng: &mut R) -> Option<Ray> {
+        let x = u * self.fov_rad;
+        let y = v * self.fov_rad;
+        let theta = (x * x + y * y).sqrt();
+        if theta > std::f32::consts::PI {
+            return None;
+        }
+        let dir = if theta > 0.0 {
+            let s = theta.sin() / theta;
+            V3::new(x * s, y * s, -theta.cos())
+        } else {
+            -V3::z()
+        };
+        Some(self.lc.l2w() * Ray::new(P3::origin(), dir))
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, _rng: &mut R) -> P3 {
+        self.lc.origin()
+    }
+
+    fn importance(&self, _lens_point: &P3, dir: &V3) -> Option<FilmImportance> {
+        let dir_local = (self.lc.w2l() * dir).normalize();
+        let theta = (-dir_local[2]).clamp(-1.0, 1.0).acos();
+        let sin_theta = theta.sin();
+        //dA(x_film) = theta dtheta dphi, dω = sin_theta dtheta dphi
+        let (x, y, importance) = if sin_theta > 1e-6 {
+            let s = theta / sin_theta;
+            (dir_local[0] * s, dir_local[1] * s, s)
+        } else if dir_local[2] < 0.0 {
+            (0.0, 0.0, 1.0)
+        } else {
+            return None;
+        };
+        Some(FilmImportance {
+            u: x / self.fov_rad,
+            v: y / self.fov_rad,
+            importance,
+        })
     }
-    fn sample_film_uv<R: Rng + ?Sized>(
+}
+
+//360 degree latitude-longitude panorama. the film should have the aspect ratio of 2:1
+#[derive(Clone)]
+pub struct Equirectangular {
+    lc: LocalCoord,
+}
+
+impl Equirectangular {
+    pub fn new(origin: P3, view_at: P3, view_up: V3) -> Self {
+        let lc = LocalCoord::new_zy(&origin, &(origin - view_at), &view_up);
+        Equirectangular { lc }
+    }
+}
+
+impl Camera for Equirectangular {
+    //the film is measured in radians
+    fn film_width(&self) -> f32 {
+        2.0 * std::f32::consts::PI
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, _rng: &mut R) -> Option<Ray> {
+        let longitude = u * self.film_width();
+        let latitude = v * self.film_width();
+        if latitude.abs() > std::f32::consts::FRAC_PI_2 {
+            return None;
+        }
+        let dir = V3::new(
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+            -latitude.cos() * longitude.cos(),
+        );
+        Some(self.lc.l2w() * Ray::new(P3::origin(), dir))
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, _rng: &mut R) -> P3 {
+        self.lc.origin()
+    }
+
+    fn importance(&self, _lens_point: &P3, dir: &V3) -> Option<FilmImportance> {
+        let dir_local = (self.lc.w2l() * dir).normalize();
+        let latitude = dir_local[1].clamp(-1.0, 1.0).asin();
+        let longitude = dir_local[0].atan2(-dir_local[2]);
+        let cos_latitude = latitude.cos();
+        if cos_latitude <= 1e-6 {
+            return None;
+        }
+        //dA(x_film) = dlongitude dlatitude, dω = cos_latitude dlongitude dlatitude
+        Some(FilmImportance {
+            u: longitude / self.film_width(),
+            v: latitude / self.film_width(),
+            importance: 1.0 / cos_latitude,
+        })
+    }
+}
+
+//opens the shutter of the wrapped camera during [open, close)
+#[derive(Clone)]
+pub struct Shutter<C> {
+    camera: C,
+    open: f32,
+    close: f32,
+}
+
+impl<C: Camera> Shutter<C> {
+    pub fn new(camera: C, open: f32, close: f32) -> Self {
+        assert!(open <= close);
+        Shutter {
+            camera,
+            open,
+            close,
+        }
+    }
+}
+
+impl<C: Camera> Camera for Shutter<C> {
+    fn film_width(&self) -> f32 {
+        self.camera.film_width()
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray> {
+        let time = self.sample_time(rng);
+        let ray = self.camera.sample_ray_at(u, v, time, rng)?;
+        Some(ray.with_time(time))
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
+        self.camera.sample_lens_point(rng)
+    }
+
+    fn importance(&self, lens_point: &P3, dir: &V3) -> Option<FilmImportance> {
+        self.camera.importance(lens_point, dir)
+    }
+
+    fn sample_film_uv<R: Rng + ?Sized>(&self, p: &P3, rng: &mut R) -> Option<ReverseSampleResult> {
+        self.camera.sample_film_uv(p, rng)
+    }
+
+    fn sample_film_uv_at<R: Rng + ?Sized>(
         &self,
-        _p: &P3,
-        _rng: &mut R,
+        p: &P3,
+        time: f32,
+        rng: &mut R,
     ) -> Option<ReverseSampleResult> {
-        unimplemented!();
+        self.camera.sample_film_uv_at(p, time, rng)
+    }
+
+    fn sample_time<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        use rand::distributions::Uniform;
+        if self.open < self.close {
+            Uniform::new(self.open, self.close).sample(rng)
+        } else {
+            self.open
+        }
+    }
+}
+
+mod realistic;
+pub use realistic::*;
+
+#[test]
+fn test_thin_lens_round_trip() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let camera = ThinLens::new_with_focus_distance(
+        P3::new(0.0, 0.0, 300.0),
+        P3::new(0.0, 0.0, 0.0),
+        V3::new(0.1, 1.0, 0.0),
+        1.0,
+        10.0,
+        250.0,
+        45.0,
+    );
+    for &(u, v) in [(0.0, 0.0), (0.3, -0.1), (-0.45, 0.2)].iter() {
+        let ray = camera.sample_ray(u, v, &mut rng).unwrap();
+        let imp = camera.importance(&ray.origin, &ray.dir).unwrap();
+        assert!((imp.u - u).abs() < 1e-3);
+        assert!((imp.v - v).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_orthographic_round_trip() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let camera = Orthographic::new(
+        P3::new(1.0, 2.0, 3.0),
+        P3::new(0.0, 0.0, 0.0),
+        V3::new(0.0, 1.0, 0.0),
+        4.0,
+    );
+    for &(u, v) in [(0.0, 0.0), (0.3, -0.1), (-0.45, 0.2)].iter() {
+        let ray = camera.sample_ray(u, v, &mut rng).unwrap();
+        let film = camera.sample_film_uv(&ray.at(5.0), &mut rng).unwrap();
+        assert!((film.u - u).abs() < 1e-4);
+        assert!((film.v - v).abs() < 1e-4);
+        assert!((film.lens_point - ray.origin).norm() < 1e-4);
+    }
+    //behind the film
+    assert!(camera
+        .sample_film_uv(&P3::new(2.0, 4.0, 6.0), &mut rng)
+        .is_none());
+}
+
+#[test]
+fn test_fisheye_round_trip() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let camera = Fisheye::new(
+        P3::new(0.0, 0.0, 1.0),
+        P3::new(1.0, 0.0, 1.0),
+        V3::new(0.0, 0.0, 1.0),
+        360.0,
+    );
+    for &(u, v) in [(0.0, 0.0), (0.3, -0.1), (-0.45, 0.2), (0.0, 0.49)].iter() {
+        let ray = camera.sample_ray(u, v, &mut rng).unwrap();
+        let imp = camera.importance(&ray.origin, &ray.dir).unwrap();
+        assert!((imp.u - u).abs() < 1e-3);
+        assert!((imp.v - v).abs() < 1e-3);
+    }
+    //the center looks at view_at and the corners are outside of the image circle
+    let ray = camera.sample_ray(0.0, 0.0, &mut rng).unwrap();
+    assert!((ray.dir - V3::x()).norm() < 1e-5);
+    assert!(camera.sample_ray(0.45, 0.45, &mut rng).is_none());
+}
+
+#[test]
+fn test_equirectangular_round_trip() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let camera = Equirectangular::new(
+        P3::origin(),
+        P3::new(0.0, 0.0, -1.0),
+        V3::new(0.0, 1.0, 0.0),
+    );
+    for &(u, v) in [(0.0, 0.0), (0.3, -0.1), (-0.45, 0.2), (0.1, 0.24)].iter() {
+        let ray = camera.sample_ray(u, v, &mut rng).unwrap();
+        let imp = camera.importance(&ray.origin, &ray.dir).unwrap();
+        assert!((imp.u - u).abs() < 1e-3);
+        assert!((imp.v - v).abs() < 1e-3);
     }
+    //the edges of the film meet behind the camera
+    let ray = camera.sample_ray(0.5, 0.0, &mut rng).unwrap();
+    assert!((ray.dir - V3::z()).norm() < 1e-5);
+    assert!(camera.sample_ray(0.0, 0.3, &mut rng).is_none());
 }
@@ -0,0 +1,420 @@
+use super::*;
+
+//an interface of the lens system. a zero curvature radius denotes the aperture stop
+#[derive(Clone, Debug)]
+pub struct LensElement {
+    pub curvature_radius: f32,
+    //distance to the next interface toward the film
+    pub thickness: f32,
+    //index of refraction of the medium behind the interface. zero means air
+    pub eta: f32,
+    pub aperture_radius: f32,
+}
+
+//multi-element lens camera traced through spherical interfaces.
+//in the local coordinate, the film is on z = 0 and the lens elements are placed toward -z.
+#[derive(Clone)]
+pub struct RealisticLens {
+    lc: LocalCoord,
+    elements: Vec<LensElement>,
+    film_width: f32,
+    //(offset from the vertex, radius) of the disks where rays are sampled
+    rear_sample: (f32, f32),
+    front_sample: (f32, f32),
+}
+
+fn refract(wi: &V3, n: &V3, eta: f32) -> Option<V3> {
+    let cos_i = n.dot(wi);
+    let sin2_i = (1.0 - cos_i * cos_i).max(0.0);
+    let sin2_t = eta * eta * sin2_i;
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(eta * -wi + (eta * cos_i - cos_t) * n)
+}
+
+impl RealisticLens {
+    //reads a lens prescription. each line has four numbers:
+    //curvature radius, thickness, index of refraction and aperture diameter (in millimeters),
+    //listed from the element nearest to the scene. '#' starts a comment.
+    pub fn load(file: &str) -> Result<Vec<LensElement>, String> {
+        let text = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", file, e))
+    }
+
+    //a prescription in the format of the files of 'load'
+    pub fn parse(text: &str) -> Result<Vec<LensElement>, String> {
+        let mut elements = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(|x| x.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            if values.len() != 4 {
+                return Err(format!("line {}: expected 4 values", i + 1));
+            }
+            elements.push(LensElement {
+                curvature_radius: values[0],
+                thickness: values[1],
+                eta: values[2],
+                aperture_radius: values[3] / 2.0,
+            });
+        }
+        if elements.is_empty() {
+            Err("no lens elements".into())
+        } else {
+            Ok(elements)
+        }
+    }
+
+    //'scale' converts the units of the prescription and film_width into the scene units.
+    //the last element is moved so that the plane at focus_distance from the film is in focus.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        origin: P3,
+        view_at: P3,
+        view_up: V3,
+        mut elements: Vec<LensElement>,
+        scale: f32,
+        film_width: f32,
+        aperture_diameter: Option<f32>,
+        focus_distance: f32,
+    ) -> Result<Self, String> {
+        if elements.is_empty() {
+            return Err("no lens elements".into());
+        }
+        for e in elements.iter_mut() {
+            e.curvature_radius *= scale;
+            e.thickness *= scale;
+            e.aperture_radius *= scale;
+            if e.curvature_radius == 0.0 {
+                if let Some(d) = aperture_diameter {
+                    e.aperture_radius = (d * scale / 2.0).min(e.aperture_radius);
+                }
+            }
+        }
+        let lc = LocalCoord::new_zy(&origin, &(origin - view_at), &view_up);
+
+        //the sampling disks are placed on the planes touching the outermost elements.
+        //they are widened by the sag so that oblique rays are covered.
+        let sag = |e: &LensElement| {
+            let r = e.curvature_radius.abs();
+            let a = e.aperture_radius;
+            if r > a {
+                r - (r * r - a * a).sqrt()
+            } else {
+                a
+            }
+        };
+        let rear = elements.last().unwrap();
+        let rear_sample = (
+            if rear.curvature_radius > 0.0 {
+                sag(rear)
+            } else {
+                0.0
+            },
+            rear.aperture_radius + sag(rear),
+        );
+        let front = &elements[0];
+        let front_sample = (
+            if front.curvature_radius < 0.0 {
+                -sag(front)
+            } else {
+                0.0
+            },
+            front.aperture_radius + sag(front),
+        );
+        let mut lens = RealisticLens {
+            lc,
+            elements,
+            film_width: film_width * scale,
+            rear_sample,
+            front_sample,
+        };
+        lens.focus(focus_distance);
+        Ok(lens)
+    }
+
+    fn rear_z(&self) -> f32 {
+        -self.elements.last().unwrap().thickness
+    }
+
+    fn front_z(&self) -> f32 {
+        -self.elements.iter().map(|e| e.thickness).sum::<f32>()
+    }
+
+    fn intersect_element(element_z: f32, radius: f32, ray: &Ray) -> Option<(f32, V3)> {
+        let center = P3::new(0.0, 0.0, element_z + radius);
+        let o = ray.origin - center;
+        let a = ray.dir.norm_squared();
+        let b = 2.0 * ray.dir.dot(&o);
+        let c = o.norm_squared() - radius * radius;
+        let disc = b * b - 4.0 * a * c;
+        if disc < 0.0 {
+            return None;
+        }
+        let sq = disc.sqrt();
+        let t0 = (-b - sq) / (2.0 * a);
+        let t1 = (-b + sq) / (2.0 * a);
+        let use_closer = (ray.dir[2] > 0.0) != (radius < 0.0);
+        let t = if use_closer { t0.min(t1) } else { t0.max(t1) };
+        if t < 0.0 {
+            return None;
+        }
+        let n = (o + t * ray.dir).normalize();
+        let n = if n.dot(&-ray.dir) < 0.0 { -n } else { n };
+        Some((t, n))
+    }
+
+    //traces a ray (in the local coordinate) at the film side into the scene
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = ray.clone();
+        let mut element_z = 0.0;
+        for i in (0..self.elements.len()).rev() {
+            let element = &self.elements[i];
+            element_z -= element.thickness;
+            let (t, n) = if element.curvature_radius == 0.0 {
+                ((element_z - ray.origin[2]) / ray.dir[2], None)
+            } else {
+                let (t, n) = Self::intersect_element(element_z, element.curvature_radius, &ray)?;
+                (t, Some(n))
+            };
+            //rays may start on a stop, as the lens points are sampled on the plane of the front one
+            if t.is_nan() || t < 0.0 {
+                return None;
+            }
+            let p = ray.at(t);
+            if p[0] * p[0] + p[1] * p[1] > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            ray.origin = p;
+            if let Some(n) = n {
+                let eta_i = if element.eta == 0.0 { 1.0 } else { element.eta };
+                let eta_t = if i > 0 && self.elements[i - 1].eta != 0.0 {
+                    self.elements[i - 1].eta
+                } else {
+                    1.0
+                };
+                ray.dir = refract(&(-ray.dir).normalize(), &n, eta_i / eta_t)?;
+            }
+        }
+        Some(ray)
+    }
+
+    //traces a ray (in the local coordinate) at the scene side toward the film
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = ray.clone();
+        let mut element_z = self.front_z();
+        for i in 0..self.elements.len() {
+            let element = &self.elements[i];
+            let (t, n) = if element.curvature_radius == 0.0 {
+                ((element_z - ray.origin[2]) / ray.dir[2], None)
+            } else {
+                let (t, n) = Self::intersect_element(element_z, element.curvature_radius, &ray)?;
+                (t, Some(n))
+            };
+            //rays may start on a stop, as the lens points are sampled on the plane of the front one
+            if t.is_nan() || t < 0.0 {
+                return None;
+            }
+            let p = ray.at(t);
+            if p[0] * p[0] + p[1] * p[1] > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            ray.origin = p;
+            if let Some(n) = n {
+                let eta_i = if i == 0 || self.elements[i - 1].eta == 0.0 {
+                    1.0
+                } else {
+                    self.elements[i - 1].eta
+                };
+                let eta_t = if element.eta == 0.0 { 1.0 } else { element.eta };
+                ray.dir = refract(&(-ray.dir).normalize(), &n, eta_i / eta_t)?;
+            }
+            element_z += element.thickness;
+        }
+        Some(ray)
+    }
+
+    //returns (principal plane, focal point) along z of a paraxial ray
+    fn cardinal_points(r_in: &Ray, r_out: &Ray) -> (f32, f32) {
+        let tf = -r_out.origin[0] / r_out.dir[0];
+        let tp = (r_in.origin[0] - r_out.origin[0]) / r_out.dir[0];
+        (r_out.at(tp)[2], r_out.at(tf)[2])
+    }
+
+    //moves the lens system along the axis, approximating it by a thick lens
+    fn focus(&mut self, focus_distance: f32) {
+        let x = 1e-3 * self.film_width;
+        let r_scene = Ray::new(P3::new(x, 0.0, self.front_z() - 1.0), V3::z());
+        let r_film = Ray::new(P3::new(x, 0.0, self.rear_z() + 1.0), -V3::z());
+        let (img, obj) = match (
+            self.trace_from_scene(&r_scene),
+            self.trace_from_film(&r_film),
+        ) {
+            (Some(img), Some(obj)) => (img, obj),
+            _ => {
+                warn!("failed to focus the lens system");
+                return;
+            }
+        };
+        let (zp_img, zf_img) = Self::cardinal_points(&r_scene, &img);
+        let (zp_obj, _) = Self::cardinal_points(&r_film, &obj);
+        let f = zf_img - zp_img;
+
+        //moving the lens by -delta, solve 1 / (zp_obj - delta + d) + 1 / (delta - zp_img) = 1 / f
+        let a = zp_obj + focus_distance;
+        let b = -zp_img;
+        let disc = (a + b) * (a + b - 4.0 * f);
+        if disc < 0.0 {
+            warn!("focus distance {} is too short", focus_distance);
+            return;
+        }
+        let delta = 0.5 * (a - b - disc.sqrt());
+        self.elements.last_mut().unwrap().thickness += delta;
+    }
+
+    fn film_point(&self, u: f32, v: f32) -> P3 {
+        //the image formed by the lens is upside down
+        P3::new(-u * self.film_width, -v * self.film_width, 0.0)
+    }
+}
+
+impl Camera for RealisticLens {
+    fn film_width(&self) -> f32 {
+        self.film_width
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray> {
+        let film_point = self.film_point(u, v);
+        let (offset, radius) = self.rear_sample;
+        let rear_point = sample_disk(radius, rng) + V3::z() * (self.rear_z() + offset);
+        let ray = Ray::from_to(&film_point, &rear_point);
+        let ray = self.trace_from_film(&ray)?;
+        Some(self.lc.l2w() * Ray::new(ray.origin, ray.dir.normalize()))
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
+        let (offset, radius) = self.front_sample;
+        self.lc.l2w() * (sample_disk(radius, rng) + V3::z() * (self.front_z() + offset))
+    }
+
+    //the ray space measure is preserved through the lens system,
+    //  G(x_film, x_rear) dA(x_film) dA(x_rear) = G(x_front, x) dA(x_front) dA(x),
+    //while the film is sampled with dA(x_film) dA(x_rear) / A_rear and the lens with
+    //dA(x_front) / A_front.
+    fn importance(&self, lens_point: &P3, dir: &V3) -> Option<FilmImportance> {
+        let lens_point_local = self.lc.w2l() * lens_point;
+        let dir_local = (self.lc.w2l() * dir).normalize();
+        let cos_front = -dir_local[2];
+        if cos_front <= 0.0 {
+            return None;
+        }
+        let ray = self.trace_from_scene(&Ray::new(lens_point_local, -dir_local))?;
+        if ray.dir[2] <= 0.0 {
+            return None;
+        }
+        let t_film = -ray.origin[2] / ray.dir[2];
+        let film_point = ray.at(t_film);
+        let (rear_offset, rear_radius) = self.rear_sample;
+        let t_rear = (self.rear_z() + rear_offset - film_point[2]) / ray.dir[2];
+        let rear_point = film_point + ray.dir * t_rear;
+        let rear_r2 = rear_point[0] * rear_point[0] + rear_point[1] * rear_point[1];
+        if rear_r2 > rear_radius * rear_radius {
+            return None;
+        }
+        let cos_film = ray.dir[2] / ray.dir.norm();
+        let g_film = cos_film * cos_film / (rear_point - film_point).norm_squared();
+        let area_ratio = (self.front_sample.1 / rear_radius).powi(2);
+        Some(FilmImportance {
+            u: -film_point[0] / self.film_width,
+            v: -film_point[1] / self.film_width,
+            importance: cos_front * area_ratio / g_film,
+        })
+    }
+}
+
+//a biconvex lens of the focal length of 50 behind the aperture stop, in millimeters
+#[cfg(test)]
+const SINGLET: &str = "
+# radius thickness eta aperture
+0 5 0 16
+50 5 1.5 20
+-50 50 0 20
+";
+
+#[test]
+fn test_lens_prescription() {
+    let elements = RealisticLens::parse(SINGLET).unwrap();
+    assert_eq!(elements.len(), 3);
+    assert_eq!(elements[1].curvature_radius, 50.0);
+    assert_eq!(elements[1].eta, 1.5);
+    assert_eq!(elements[2].aperture_radius, 10.0);
+    assert!(RealisticLens::parse("# only a comment\n").is_err());
+    assert!(RealisticLens::parse("50 5 1.5\n").is_err());
+    assert!(RealisticLens::parse("50 5 x 20\n").is_err());
+    let empty = RealisticLens::new(
+        P3::origin(),
+        P3::new(0.0, 0.0, -1.0),
+        V3::y(),
+        vec![],
+        1.0,
+        36.0,
+        None,
+        1000.0,
+    );
+    assert!(empty.is_err());
+}
+
+#[test]
+fn test_realistic_lens() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let focus_distance = 1000.0;
+    let lens = RealisticLens::new(
+        P3::origin(),
+        P3::new(0.0, 0.0, -1.0),
+        V3::y(),
+        RealisticLens::parse(SINGLET).unwrap(),
+        1.0,
+        10.0,
+        Some(2.0),
+        focus_distance,
+    )
+    .unwrap();
+
+    //rays from the film center converge on the axis at the focus distance from the film
+    let spread = |z: f32, rng: &mut SmallRng| {
+        let mut max_r: f32 = 0.0;
+        let mut count = 0;
+        for _ in 0..4000 {
+            if let Some(ray) = lens.sample_ray(0.0, 0.0, rng) {
+                let p = ray.at((z - ray.origin[2]) / ray.dir[2]);
+                max_r = max_r.max((p[0] * p[0] + p[1] * p[1]).sqrt());
+                count += 1;
+            }
+        }
+        assert!(count > 20);
+        max_r
+    };
+    let in_focus = spread(-focus_distance, &mut rng);
+    assert!(in_focus < 0.05);
+    assert!(spread(-focus_distance / 2.0, &mut rng) > 5.0 * in_focus);
+
+    //ray -> film -> ray through the plane touching the front element
+    let front_z = lens.front_z() + lens.front_sample.0;
+    for &(u, v) in [(0.0, 0.0), (0.2, -0.1), (-0.3, 0.25)].iter() {
+        let ray = (0..100000)
+            .find_map(|_| lens.sample_ray(u, v, &mut rng))
+            .unwrap();
+        let lens_point = ray.at((front_z - ray.origin[2]) / ray.dir[2]);
+        let imp = lens.importance(&lens_point, &ray.dir).unwrap();
+        assert!((imp.u - u).abs() < 1e-3);
+        assert!((imp.v - v).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,138 @@
+//emission from the surfaces of objects
+use crate::*;
+use rand::prelude::*;
+
+#[derive(Clone, Debug)]
+pub enum EmissionColor {
+    Texture(texture::Texture),
+    //blackbody spectrum of the luminance given by the scale
+    Blackbody(spectrum::Blackbody, f32),
+}
+
+//distribution of the radiance over directions
+#[derive(Clone, Copy, Debug)]
+pub enum Profile {
+    Diffuse,
+    //radiance proportional to cos^n around the normal, scaled to keep the radiant exitance
+    //of the diffuse emission of the same color
+    CosinePower(f32),
+}
+
+#[derive(Clone, Debug)]
+pub struct Emission {
+    pub color: EmissionColor,
+    pub profile: Profile,
+    //one-sided emission is toward the side of the geometric normal
+    pub two_sided: bool,
+}
+
+impl From<RGB> for Emission {
+    fn from(color: RGB) -> Self {
+        Emission::textured(color.into())
+    }
+}
+
+impl Emission {
+    pub fn textured(texture: texture::Texture) -> Self {
+        Emission {
+            color: EmissionColor::Texture(texture),
+            profile: Profile::Diffuse,
+            two_sided: true,
+        }
+    }
+
+    pub fn blackbody(temperature: f32, luminance: f32) -> Self {
+        Emission {
+            color: EmissionColor::Blackbody(spectrum::Blackbody::new(temperature), luminance),
+            profile: Profile::Diffuse,
+            two_sided: true,
+        }
+    }
+
+    pub fn set_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn set_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    //radiance at the point before the profile is applied
+    pub fn color_at(&self, uv: &P2, wavelengths: Option<&spectrum::Wavelengths>) -> RGB {
+        match &self.color {
+            EmissionColor::Texture(texture) => {
+                let color = texture.at(uv);
+                wavelengths.map_or(color, |w| w.uplift(&color))
+            }
+            EmissionColor::Blackbody(blackbody, luminance) => {
+                wavelengths.map_or(blackbody.rgb(), |w| w.blackbody(blackbody)) * *luminance
+            }
+        }
+    }
+
+    //factor of the radiance toward a direction whose cosine with the geometric normal is 'cos'
+    pub fn profile_at(&self, cos: f32) -> f32 {
+        if cos < 0.0 && !self.two_sided {
+            return 0.0;
+        }
+        match self.profile {
+            Profile::Diffuse => 1.0,
+            Profile::CosinePower(n) => (n + 2.0) / 2.0 * cos.abs().powf(n),
+        }
+    }
+
+    //samples an emitted direction in proportion to the profile times the cosine.
+    //the value is the direction and the profile times the cosine.
+    pub fn sample_dir<R: Rng + ?Sized>(
+        &self,
+        normal: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, f32)> {
+        let exponent = match self.profile {
+            Profile::Diffuse => 0.0,
+            Profile::CosinePower(n) => n,
+        };
+        let cos = (1.0 - rng.gen::<f32>()).powf(1.0 / (exponent + 2.0));
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+        let lc = LocalCoord::new_zx(&P3::origin(), normal, &pick_orthogonal(normal));
+        let mut dir = lc.l2w() * V3::new(sin * phi.cos(), sin * phi.sin(), cos);
+        let mut pdf =
+            (exponent + 2.0) / 2.0 * std::f32::consts::FRAC_1_PI * cos.powf(exponent + 1.0);
+        if self.two_sided {
+            pdf *= 0.5;
+            if rng.gen::<bool>() {
+                dir = -dir;
+            }
+        }
+        pdf::PdfSample {
+            value: (dir, self.profile_at(dir.dot(normal)) * cos),
+            pdf,
+        }
+    }
+}
+
+#[test]
+fn test_sample_dir() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let normal = V3::new(1.0, 2.0, 3.0).normalize();
+    //the radiant exitance of the profiles is pi per side
+    for profile in [Profile::Diffuse, Profile::CosinePower(20.0)].iter() {
+        for two_sided in [false, true].iter() {
+            let emission = Emission::from(RGB::all(1.0))
+                .set_profile(*profile)
+                .set_two_sided(*two_sided);
+            let n = 10000;
+            let sum: f32 = (0..n)
+                .map(|_| {
+                    let s = emission.sample_dir(&normal, &mut rng);
+                    s.value.1 / s.pdf
+                })
+                .sum();
+            let sides = if *two_sided { 2.0 } else { 1.0 };
+            assert!((sum / n as f32 - std::f32::consts::PI * sides).abs() < 1e-3);
+        }
+    }
+}
@@ -16,7 +16,11 @@ pub fn make_scene() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::new(0.0, 10.0, 0.0)),
+        emission: Some(RGB::new(0.0, 10.0, 0.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -26,6 +30,10 @@ pub fn make_scene() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -34,7 +42,11 @@ pub fn make_scene() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::new(0.0, 0.0, 10.0)),
+        emission: Some(RGB::new(0.0, 0.0, 10.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -50,7 +62,7 @@ pub fn make_scene() -> (PinHole, Scene) {
     (camera, scene)
 }
 
-pub fn make_box() -> (impl Camera + Clone, Scene) {
+fn make_box_objects() -> Vec<object::SimpleObject> {
     use material::materials::*;
     use material::Material;
     use shape::shapes::*;
@@ -66,6 +78,10 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.5, 0.5, 0.5)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -77,6 +93,10 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.5, 0.5, 0.5)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -88,6 +108,10 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.0, 0.8, 0.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -99,6 +123,10 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.2, 0.2, 0.8)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -110,6 +138,10 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.8, 0.8, 0.2)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -121,6 +153,10 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.8, 0.2, 0.2)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -130,7 +166,11 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(50.0)),
+        emission: Some(RGB::all(50.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -139,8 +179,12 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
             radius: 10.0,
         }
         .into(),
-        material: Mirror(RGB::all(1.0)).into(),
+        material: Mirror::new(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -149,13 +193,20 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
             radius: 15.0,
         }
         .into(),
-        //material: Mirror(RGB::all(1.0)).into(),
+        //material: Mirror::new(RGB::all(1.0)).into(),
         material: Transparent {
             color: RGB::new(1.0, 1.0, 1.0),
             index: 1.4,
+            dispersion: None,
+            film: None,
+            absorption: RGB::all(0.0),
         }
         .into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -167,10 +218,14 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         //material: Lambert(RGB::new(0.0, 1.0, 0.5)).into(),
         material: Material::mix(
             0.1,
-            Mirror(RGB::new(0.8, 0.8, 0.2)).into(),
+            Mirror::new(RGB::new(0.8, 0.8, 0.2)).into(),
             Lambert(RGB::new(0.8, 0.8, 0.2)).into(),
         ),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -181,9 +236,17 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
         .into(),
         material: Lambert(RGB::new(0.0, 1.0, 1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
-    let scene = Scene::new(objects);
+    objects
+}
+
+pub fn make_box() -> (impl Camera + Clone, Scene) {
+    let scene = Scene::new(make_box_objects());
 
     let camera = {
         let origin = P3::new(0.0, 0.0, 300.0);
@@ -202,6 +265,45 @@ pub fn make_box() -> (impl Camera + Clone, Scene) {
     (camera, scene)
 }
 
+//two seconds of the box: the camera swings, the light pulses and the mirror ball bounces
+pub fn make_box_animation() -> animation::Animation {
+    use animation::*;
+    let mut objects: Vec<AnimatedObject> = make_box_objects().into_iter().map(Into::into).collect();
+
+    objects[6].emission = Some(Keyframes::new(vec![
+        (0.0, RGB::all(50.0)),
+        (1.0, RGB::all(10.0)),
+        (2.0, RGB::all(50.0)),
+    ]));
+
+    let bounce = |height: f32| nalgebra::Isometry3::translation(0.0, height, 0.0);
+    objects[7].object.motion = Some(object::Motion::new(vec![
+        (0.0, bounce(0.0)),
+        (0.5, bounce(60.0)),
+        (1.0, bounce(0.0)),
+        (1.5, bounce(60.0)),
+        (2.0, bounce(0.0)),
+    ]));
+
+    let camera = PinHoleKeys {
+        origin: Keyframes::new(vec![
+            (0.0, P3::new(-60.0, 0.0, 300.0)),
+            (2.0, P3::new(60.0, 0.0, 300.0)),
+        ]),
+        view_at: Keyframes::constant(P3::new(0.0, 0.0, 0.0)),
+        view_up: Keyframes::constant(V3::new(0.1, 1.0, 0.0)),
+        fov_degree: Keyframes::new(vec![(0.0, 45.0), (1.0, 35.0), (2.0, 45.0)]),
+        hole_radius: None,
+    };
+
+    Animation {
+        camera,
+        objects,
+        fps: 24.0,
+        shutter: 0.5,
+    }
+}
+
 pub fn make_plane_scene() -> (PinHole, Scene) {
     use material::materials::*;
     use shape::shapes::*;
@@ -217,6 +319,10 @@ pub fn make_plane_scene() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::new(1.0, 0.6, 0.6)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     use rand::distributions::Uniform;
@@ -258,10 +364,17 @@ pub fn make_plane_scene() -> (PinHole, Scene) {
             };
             let emission = None;
             let m = if material_type < 0.3 {
-                material::materials::Mirror(color).into()
+                material::materials::Mirror::new(color).into()
             } else if material_type < 0.6 {
                 let index = Uniform::new(1.0, 2.0).sample(&mut rng);
-                material::materials::Transparent { color, index }.into()
+                material::materials::Transparent {
+                    color,
+                    index,
+                    dispersion: None,
+                    film: None,
+                    absorption: RGB::all(0.0),
+                }
+                .into()
             } else {
                 material::materials::Lambert(color).into()
             };
@@ -272,6 +385,10 @@ pub fn make_plane_scene() -> (PinHole, Scene) {
             shape: sphere.into(),
             material,
             emission,
+            motion: None,
+            normal_map: None,
+            tangent_map: None,
+            alpha: None,
         });
     }
     let envmap = image::Image::read_exr16("envmap_rect.exr").unwrap();
@@ -302,6 +419,10 @@ pub fn make_parallel() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -312,6 +433,10 @@ pub fn make_parallel() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -321,7 +446,11 @@ pub fn make_parallel() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(10.0)),
+        emission: Some(RGB::all(10.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -351,6 +480,10 @@ pub fn make_parallel_and_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -361,6 +494,10 @@ pub fn make_parallel_and_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -370,8 +507,12 @@ pub fn make_parallel_and_mirror() -> (PinHole, Scene) {
             P3::new(0.0, -50.0, -200.0),
         ])
         .into(),
-        material: Mirror(RGB::all(1.0)).into(),
+        material: Mirror::new(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -381,7 +522,11 @@ pub fn make_parallel_and_mirror() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(10.0)),
+        emission: Some(RGB::all(10.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -409,7 +554,11 @@ pub fn make_fiveballs_mirror() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(10.0)),
+        emission: Some(RGB::all(10.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -418,8 +567,12 @@ pub fn make_fiveballs_mirror() -> (PinHole, Scene) {
             radius: 20.0,
         }
         .into(),
-        material: Mirror(RGB::all(1.0)).into(),
+        material: Mirror::new(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -429,6 +582,10 @@ pub fn make_fiveballs_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -436,8 +593,12 @@ pub fn make_fiveballs_mirror() -> (PinHole, Scene) {
             radius: 20.0,
         }
         .into(),
-        material: Mirror(RGB::all(1.0)).into(),
+        material: Mirror::new(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -447,6 +608,10 @@ pub fn make_fiveballs_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -474,7 +639,11 @@ pub fn make_fiveballs_lambert() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(10.0)),
+        emission: Some(RGB::all(10.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -485,6 +654,10 @@ pub fn make_fiveballs_lambert() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -494,6 +667,10 @@ pub fn make_fiveballs_lambert() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -503,6 +680,10 @@ pub fn make_fiveballs_lambert() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -512,6 +693,10 @@ pub fn make_fiveballs_lambert() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -550,6 +735,10 @@ pub fn make_black_shell() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -559,7 +748,11 @@ pub fn make_black_shell() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(1.0)),
+        emission: Some(RGB::all(1.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     //for i in 0..100 {
@@ -570,7 +763,7 @@ pub fn make_black_shell() -> (PinHole, Scene) {
     //        }
     //        .into(),
     //        material: Lambert(RGB::all(0.0)).into(),
-    //        emission: Some(RGB::all(1000.0)),
+    //        emission: Some(RGB::all(1000.0).into()),
     //    });
     //}
 
@@ -599,7 +792,11 @@ pub fn make_ball_triangle() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(100.0)),
+        emission: Some(RGB::all(100.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -611,6 +808,10 @@ pub fn make_ball_triangle() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -638,7 +839,11 @@ pub fn make_ball_triangle_2() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(100.0)),
+        emission: Some(RGB::all(100.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -650,6 +855,10 @@ pub fn make_ball_triangle_2() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -660,6 +869,10 @@ pub fn make_ball_triangle_2() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -687,7 +900,11 @@ pub fn make_ball_triangle_mirror() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(100.0)),
+        emission: Some(RGB::all(100.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -699,6 +916,10 @@ pub fn make_ball_triangle_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -709,6 +930,10 @@ pub fn make_ball_triangle_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -719,6 +944,10 @@ pub fn make_ball_triangle_mirror() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -727,8 +956,12 @@ pub fn make_ball_triangle_mirror() -> (PinHole, Scene) {
             P3::new(-10.0, -50.0, 50.0),
         ])
         .into(),
-        material: Mirror(RGB::all(1.0)).into(),
+        material: Mirror::new(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -757,7 +990,11 @@ pub fn make_emissive_triangle() -> (PinHole, Scene) {
         ])
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(0.5)),
+        emission: Some(RGB::all(0.5).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -785,7 +1022,11 @@ pub fn make_debug() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(1.0)),
+        emission: Some(RGB::all(1.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -796,6 +1037,10 @@ pub fn make_debug() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(1.0)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -813,7 +1058,6 @@ pub fn make_debug() -> (PinHole, Scene) {
 
 pub fn make_mix_balls() -> (PinHole, Scene) {
     use material::materials::*;
-    use material::Material::Mix;
     use shape::shapes::*;
     let mut objects = vec![];
 
@@ -825,12 +1069,16 @@ pub fn make_mix_balls() -> (PinHole, Scene) {
                 radius: 20.0,
             }
             .into(),
-            material: Mix(
+            material: material::Material::mix(
                 i as f32 / 9.0,
-                Box::new(Lambert(RGB::all(1.0)).into()),
-                Box::new(Mirror(RGB::all(1.0)).into()),
+                Lambert(RGB::all(1.0)).into(),
+                Mirror::new(RGB::all(1.0)).into(),
             ),
             emission: None,
+            motion: None,
+            normal_map: None,
+            tangent_map: None,
+            alpha: None,
         });
 
         objects.push(object::SimpleObject {
@@ -839,12 +1087,16 @@ pub fn make_mix_balls() -> (PinHole, Scene) {
                 radius: 20.0,
             }
             .into(),
-            material: Mix(
+            material: material::Material::mix(
                 (i + 5) as f32 / 9.0,
-                Box::new(Lambert(RGB::all(1.0)).into()),
-                Box::new(Mirror(RGB::all(1.0)).into()),
+                Lambert(RGB::all(1.0)).into(),
+                Mirror::new(RGB::all(1.0)).into(),
             ),
             emission: None,
+            motion: None,
+            normal_map: None,
+            tangent_map: None,
+            alpha: None,
         });
     }
 
@@ -855,7 +1107,11 @@ pub fn make_mix_balls() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(1e3)),
+        emission: Some(RGB::all(1e3).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Sphere {
@@ -864,7 +1120,11 @@ pub fn make_mix_balls() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(1e3)),
+        emission: Some(RGB::all(1e3).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -882,7 +1142,6 @@ pub fn make_mix_balls() -> (PinHole, Scene) {
 
 pub fn make_mix_balls_2() -> (PinHole, Scene) {
     use material::materials::*;
-    use material::Material::Mix;
     use shape::shapes::*;
     let mut objects = vec![];
 
@@ -892,12 +1151,16 @@ pub fn make_mix_balls_2() -> (PinHole, Scene) {
             radius: 20.0,
         }
         .into(),
-        material: Mix(
+        material: material::Material::mix(
             0.5,
-            Box::new(Lambert(RGB::all(1.0)).into()),
-            Box::new(Mirror(RGB::all(1.0)).into()),
+            Lambert(RGB::all(1.0)).into(),
+            Mirror::new(RGB::all(1.0)).into(),
         ),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -906,12 +1169,16 @@ pub fn make_mix_balls_2() -> (PinHole, Scene) {
             radius: 20.0,
         }
         .into(),
-        material: Mix(
+        material: material::Material::mix(
             0.25,
-            Box::new(Lambert(RGB::all(1.0)).into()),
-            Box::new(Mirror(RGB::all(1.0)).into()),
+            Lambert(RGB::all(1.0)).into(),
+            Mirror::new(RGB::all(1.0)).into(),
         ),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -920,12 +1187,16 @@ pub fn make_mix_balls_2() -> (PinHole, Scene) {
             radius: 20.0,
         }
         .into(),
-        material: Mix(
+        material: material::Material::mix(
             0.75,
-            Box::new(Lambert(RGB::all(1.0)).into()),
-            Box::new(Mirror(RGB::all(1.0)).into()),
+            Lambert(RGB::all(1.0)).into(),
+            Mirror::new(RGB::all(1.0)).into(),
         ),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -936,6 +1207,10 @@ pub fn make_mix_balls_2() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(0.5)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -945,7 +1220,11 @@ pub fn make_mix_balls_2() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(1.0)),
+        emission: Some(RGB::all(1.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -975,6 +1254,10 @@ pub fn make_test_mix() -> (PinHole, Scene) {
         .into(),
         material: Lambert(RGB::all(0.5)).into(),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
     objects.push(object::SimpleObject {
         shape: Triangle::new([
@@ -987,9 +1270,13 @@ pub fn make_test_mix() -> (PinHole, Scene) {
             0.5,
             Lambert(RGB::all(1.0)).into(),
             //Lambert(RGB::all(0.0)).into(),
-            Mirror(RGB::all(1.0)).into(),
+            Mirror::new(RGB::all(1.0)).into(),
         ),
         emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     objects.push(object::SimpleObject {
@@ -1005,7 +1292,11 @@ pub fn make_test_mix() -> (PinHole, Scene) {
         }
         .into(),
         material: Lambert(RGB::all(0.0)).into(),
-        emission: Some(RGB::all(1.0)),
+        emission: Some(RGB::all(1.0).into()),
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
     });
 
     let scene = Scene::new(objects);
@@ -123,7 +123,7 @@ impl<B> Film<B> {
     pub fn uv_to_ix(&self, u: f32, v: f32) -> (i32, i32) {
         let x = (u + 0.5) * self.w() as f32;
         let y = self.h() as f32 / 2.0 - v * self.w() as f32;
-        (x as i32, y as i32)
+        (x.floor() as i32, y.floor() as i32)
     }
     pub fn uv_to_ix_in_range(&self, u: f32, v: f32) -> Option<(usize, usize)> {
         let (xi, yi) = self.uv_to_ix(u, v);
@@ -0,0 +1,5 @@
+//scene and mesh files from other tools
+mod json;
+
+pub mod gltf;
+pub mod ply;
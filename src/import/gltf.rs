@@ -0,0 +1,812 @@
+//glTF 2.0 scenes, as .gltf with external or embedded buffers or as .glb.
+//the meshes are placed in world space by the node transforms; materials take the factors of
+//the metallic-roughness model, as images other than exr are not read.
+//lights come from KHR_lights_punctual with their photometric intensities taken as they are.
+//point lights whose extras give a 'profile', an .ies or .ldt file relative to the glTF file,
+//emit by the profile scaled by their color and intensity, with the nadir toward -z of the node.
+use super::json::Json;
+use crate::*;
+use camera::Camera;
+use nalgebra::{Matrix4, Quaternion, Translation3, UnitQuaternion};
+use rand::prelude::*;
+use std::path::{Path, PathBuf};
+
+//perspective cameras of the nodes. lenses are given by 'apertureRadius' and 'focusDistance'
+//in the extras, which glTF does not define.
+#[derive(Clone)]
+pub enum GltfCamera {
+    PinHole(camera::PinHole),
+    ThinLens(camera::ThinLens),
+}
+
+impl Camera for GltfCamera {
+    fn film_width(&self) -> f32 {
+        match self {
+            GltfCamera::PinHole(c) => c.film_width(),
+            GltfCamera::ThinLens(c) => c.film_width(),
+        }
+    }
+
+    fn sample_ray<R: Rng + ?Sized>(&self, u: f32, v: f32, rng: &mut R) -> Option<Ray> {
+        match self {
+            GltfCamera::PinHole(c) => c.sample_ray(u, v, rng),
+            GltfCamera::ThinLens(c) => c.sample_ray(u, v, rng),
+        }
+    }
+
+    fn sample_lens_point<R: Rng + ?Sized>(&self, rng: &mut R) -> P3 {
+        match self {
+            GltfCamera::PinHole(c) => c.sample_lens_point(rng),
+            GltfCamera::ThinLens(c) => c.sample_lens_point(rng),
+        }
+    }
+
+    fn importance(&self, lens_point: &P3, dir: &V3) -> Option<camera::FilmImportance> {
+        match self {
+            GltfCamera::PinHole(c) => c.importance(lens_point, dir),
+            GltfCamera::ThinLens(c) => c.importance(lens_point, dir),
+        }
+    }
+}
+
+//a mesh primitive in world space, which can be subdivided before it is put in a scene
+#[derive(Clone, Debug)]
+pub struct Primitive {
+    pub mesh: mesh::Mesh,
+    pub material: material::Material,
+    pub emission: Option<emission::Emission>,
+}
+
+pub struct GltfScene {
+    pub primitives: Vec<Primitive>,
+    pub lights: Vec<light::Light>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl GltfScene {
+    pub fn objects(&self) -> Vec<object::SimpleObject> {
+        let mut objects = vec![];
+        for primitive in &self.primitives {
+            let triangles = primitive.mesh.triangles();
+            let materials = primitive.mesh.tinted(&primitive.material);
+            for (triangle, material) in triangles.into_iter().zip(materials) {
+                objects.push(object::SimpleObject {
+                    shape: triangle.into(),
+                    material,
+                    emission: primitive.emission.clone(),
+                    motion: None,
+                    normal_map: None,
+                    tangent_map: None,
+                    alpha: None,
+                });
+            }
+        }
+        objects
+    }
+
+    pub fn scene(&self) -> Result<scene::Scene, String> {
+        let objects = self.objects();
+        if objects.is_empty() {
+            return Err("glTF contains no mesh primitives".into());
+        }
+        Ok(self
+            .lights
+            .iter()
+            .fold(scene::Scene::new(objects), |scene, light| {
+                scene.add_light(light.clone())
+            }))
+    }
+}
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_JSON: u32 = 0x4e4f_534a;
+const GLB_BIN: u32 = 0x004e_4942;
+
+//reads .gltf or .glb by the contents. external buffers are relative to the file.
+pub fn load(file: &str) -> Result<GltfScene, String> {
+    let bytes = std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+    let dir = Path::new(file).parent().map(Path::to_path_buf);
+    let result = if bytes.starts_with(&GLB_MAGIC.to_le_bytes()) {
+        parse_glb(&bytes, dir)
+    } else {
+        std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_gltf(text, dir))
+    };
+    result.map_err(|e| format!("{}: {}", file, e))
+}
+
+pub fn parse_gltf(text: &str, dir: Option<PathBuf>) -> Result<GltfScene, String> {
+    Document::new(Json::parse(text)?, dir, None)?.scene()
+}
+
+pub fn parse_glb(bytes: &[u8], dir: Option<PathBuf>) -> Result<GltfScene, String> {
+    let word = |pos: usize| {
+        bytes
+            .get(pos..pos + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "truncated glb".to_string())
+    };
+    if word(0)? != GLB_MAGIC || word(4)? != 2 {
+        return Err("not a glb of version 2".into());
+    }
+    let mut json = None;
+    let mut bin = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let (length, kind) = (word(pos)? as usize, word(pos + 4)?);
+        let chunk = bytes
+            .get(pos + 8..pos + 8 + length)
+            .ok_or("truncated glb")?;
+        match kind {
+            GLB_JSON => json = Some(std::str::from_utf8(chunk).map_err(|e| e.to_string())?),
+            GLB_BIN if bin.is_none() => bin = Some(chunk.to_vec()),
+            _ => {}
+        }
+        pos += 8 + length;
+    }
+    let json = Json::parse(json.ok_or("glb without json")?)?;
+    Document::new(json, dir, bin)?.scene()
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(format!("invalid base64 character '{}'", c as char)),
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+fn color(values: Option<Vec<f32>>, default: RGB) -> RGB {
+    match values.as_deref() {
+        Some([r, g, b, ..]) => RGB::new(*r, *g, *b),
+        _ => default,
+    }
+}
+
+fn relative_to(dir: &Option<PathBuf>, path: &str) -> PathBuf {
+    match dir {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+struct Document {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+    dir: Option<PathBuf>,
+}
+
+impl Document {
+    fn new(json: Json, dir: Option<PathBuf>, bin: Option<Vec<u8>>) -> Result<Self, String> {
+        let mut bin = bin;
+        let mut buffers = vec![];
+        for buffer in json.get("buffers").items() {
+            let data = match buffer.get("uri").as_str() {
+                Some(uri) if uri.starts_with("data:") => {
+                    let (_, data) = uri
+                        .split_once(";base64,")
+                        .ok_or("data uri without base64")?;
+                    decode_base64(data)?
+                }
+                Some(uri) => {
+                    let path = relative_to(&dir, uri);
+                    std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?
+                }
+                //the binary chunk of glb
+                None => bin.take().ok_or("buffer without data")?,
+            };
+            buffers.push(data);
+        }
+        Ok(Document { json, buffers, dir })
+    }
+
+    //the components of the elements in a flat array, and the number of components.
+    //the elements have to lie in their buffer view, which bounds the count.
+    fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize), String> {
+        let accessor = self.json.get("accessors").at(index);
+        let count = accessor
+            .get("count")
+            .as_usize()
+            .ok_or(format!("accessor {}: no count", index))?;
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(format!("accessor {}: unknown type", index)),
+        };
+        if !accessor.get("sparse").is_null() {
+            return Err(format!(
+                "accessor {}: sparse accessors are not supported",
+                index
+            ));
+        }
+        //without sparse values, accessors without a buffer view would only be zeros
+        let view_index = accessor
+            .get("bufferView")
+            .as_usize()
+            .ok_or(format!("accessor {}: no buffer view", index))?;
+        let component_type = accessor.get("componentType").as_usize().unwrap_or(0);
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(format!("accessor {}: unknown component type", index)),
+        };
+        let normalized = accessor.get("normalized") == &Json::Bool(true);
+
+        let view = self.json.get("bufferViews").at(view_index);
+        let buffer = view
+            .get("buffer")
+            .as_usize()
+            .and_then(|b| self.buffers.get(b))
+            .ok_or(format!("buffer view {}: no buffer", view_index))?;
+        let view_start = view.get("byteOffset").as_usize().unwrap_or(0);
+        let view_bytes = view
+            .get("byteLength")
+            .as_usize()
+            .and_then(|length| buffer.get(view_start..view_start.checked_add(length)?))
+            .ok_or(format!("buffer view {}: out of the buffer", view_index))?;
+        let element = size * components;
+        let stride = view.get("byteStride").as_usize().unwrap_or(element);
+        if stride < element {
+            return Err(format!("buffer view {}: stride too small", view_index));
+        }
+        let start = accessor.get("byteOffset").as_usize().unwrap_or(0);
+        let end = match count {
+            0 => Some(start),
+            _ => (count - 1)
+                .checked_mul(stride)
+                .and_then(|e| e.checked_add(start))
+                .and_then(|e| e.checked_add(element)),
+        };
+        let bytes = end
+            .and_then(|end| view_bytes.get(start..end))
+            .ok_or(format!("accessor {}: out of the buffer view", index))?;
+
+        let mut values = Vec::with_capacity(count * components);
+        for e in 0..count {
+            for c in 0..components {
+                let pos = e * stride + c * size;
+                let b = &bytes[pos..pos + size];
+                let value = match component_type {
+                    5120 => (b[0] as i8 as f64, 127.0),
+                    5121 => (b[0] as f64, 255.0),
+                    5122 => (i16::from_le_bytes([b[0], b[1]]) as f64, 32767.0),
+                    5123 => (u16::from_le_bytes([b[0], b[1]]) as f64, 65535.0),
+                    5125 => (u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64, 1.0),
+                    _ => (f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64, 1.0),
+                };
+                values.push(if normalized {
+                    (value.0 / value.1).max(-1.0)
+                } else {
+                    value.0
+                });
+            }
+        }
+        Ok((values, components))
+    }
+
+    //an accessor whose elements have one of the given numbers of components
+    fn attribute(
+        &self,
+        index: usize,
+        name: &str,
+        allowed: &[usize],
+    ) -> Result<(Vec<f64>, usize), String> {
+        let (values, components) = self.accessor(index)?;
+        if allowed.contains(&components) {
+            Ok((values, components))
+        } else {
+            Err(format!(
+                "{} with {} components per element",
+                name, components
+            ))
+        }
+    }
+
+    fn material(
+        &self,
+        index: Option<usize>,
+    ) -> Result<(material::Material, Option<emission::Emission>), String> {
+        //the default material is a rough white metal
+        let json = match index {
+            Some(i) => self.json.get("materials").at(i),
+            None => &Json::Null,
+        };
+        let pbr = json.get("pbrMetallicRoughness");
+        let extensions = json.get("extensions");
+        let mut principled = material::materials::Principled::new(color(
+            pbr.get("baseColorFactor").as_f32s(),
+            RGB::all(1.0),
+        ));
+        let scalars = [
+            ("metallicFactor", pbr.get("metallicFactor"), 1.0),
+            ("roughnessFactor", pbr.get("roughnessFactor"), 1.0),
+            ("ior", extensions.get("KHR_materials_ior").get("ior"), 1.5),
+            (
+                "transmissionFactor",
+                extensions
+                    .get("KHR_materials_transmission")
+                    .get("transmissionFactor"),
+                0.0,
+            ),
+            (
+                "clearcoatFactor",
+                extensions
+                    .get("KHR_materials_clearcoat")
+                    .get("clearcoatFactor"),
+                0.0,
+            ),
+            (
+                "clearcoatRoughnessFactor",
+                extensions
+                    .get("KHR_materials_clearcoat")
+                    .get("clearcoatRoughnessFactor"),
+                0.0,
+            ),
+        ];
+        for (name, value, default) in scalars.iter() {
+            principled = principled.set_param(name, &[value.as_f32().unwrap_or(*default)])?;
+        }
+
+        let strength = extensions
+            .get("KHR_materials_emissive_strength")
+            .get("emissiveStrength")
+            .as_f32()
+            .unwrap_or(1.0);
+        let emissive = color(json.get("emissiveFactor").as_f32s(), RGB::all(0.0)) * strength;
+        let emission = if emissive.max() > 0.0 {
+            let two_sided = json.get("doubleSided") == &Json::Bool(true);
+            Some(emission::Emission::from(emissive).set_two_sided(two_sided))
+        } else {
+            None
+        };
+        Ok((principled.into(), emission))
+    }
+
+    fn primitive(
+        &self,
+        json: &Json,
+        transform: &Matrix4<f32>,
+    ) -> Result<Option<Primitive>, String> {
+        let attributes = json.get("attributes");
+        let position = attributes
+            .get("POSITION")
+            .as_usize()
+            .ok_or("primitive without positions")?;
+        let (values, _) = self.attribute(position, "positions", &[3])?;
+        let positions: Vec<P3> = values
+            .chunks(3)
+            .map(|p| P3::new(p[0] as f32, p[1] as f32, p[2] as f32))
+            .collect();
+        let indices: Vec<usize> = match json.get("indices").as_usize() {
+            Some(i) => self
+                .attribute(i, "indices", &[1])?
+                .0
+                .into_iter()
+                .map(|i| i as usize)
+                .collect(),
+            None => (0..positions.len()).collect(),
+        };
+        if indices.iter().any(|&i| i >= positions.len()) {
+            return Err("vertex index out of range".into());
+        }
+        let faces: Vec<Vec<usize>> = match json.get("mode").as_usize().unwrap_or(4) {
+            4 if indices.len() % 3 != 0 => {
+                return Err("triangle indices not in threes".into());
+            }
+            4 => indices.chunks(3).map(|t| t.to_vec()).collect(),
+            //strips alternate the winding
+            5 => (2..indices.len())
+                .map(|i| {
+                    if i % 2 == 0 {
+                        vec![indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        vec![indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+            6 => (2..indices.len())
+                .map(|i| vec![indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            //points and lines
+            _ => return Ok(None),
+        };
+
+        let mut mesh = mesh::Mesh::new(positions, faces)?;
+        if let Some(i) = attributes.get("NORMAL").as_usize() {
+            let (values, _) = self.attribute(i, "normals", &[3])?;
+            mesh = mesh.set_normals(
+                values
+                    .chunks(3)
+                    .map(|n| V3::new(n[0] as f32, n[1] as f32, n[2] as f32))
+                    .collect(),
+            )?;
+        }
+        if let Some(i) = attributes.get("TEXCOORD_0").as_usize() {
+            let (values, _) = self.attribute(i, "texture coordinates", &[2])?;
+            //the origin of glTF is at the top left of the images
+            mesh = mesh.set_uvs(
+                values
+                    .chunks(2)
+                    .map(|uv| P2::new(uv[0] as f32, 1.0 - uv[1] as f32))
+                    .collect(),
+            )?;
+        }
+        if let Some(i) = attributes.get("COLOR_0").as_usize() {
+            let (values, components) = self.attribute(i, "colors", &[3, 4])?;
+            mesh = mesh.set_colors(
+                values
+                    .chunks(components)
+                    .map(|c| RGB::new(c[0] as f32, c[1] as f32, c[2] as f32))
+                    .collect(),
+            )?;
+        }
+
+        let (material, emission) = self.material(json.get("material").as_usize())?;
+        Ok(Some(Primitive {
+            mesh: mesh.transformed(transform),
+            material,
+            emission,
+        }))
+    }
+
+    fn camera(&self, json: &Json, transform: &Matrix4<f32>) -> Option<GltfCamera> {
+        let perspective = json.get("perspective");
+        let yfov = perspective.get("yfov").as_f32()?;
+        let aspect = perspective.get("aspectRatio").as_f32().unwrap_or(1.0);
+        //the film spans the width
+        let fov_degree = (2.0 * ((yfov / 2.0).tan() * aspect).atan()).to_degrees();
+        let origin = transform.transform_point(&P3::origin());
+        let view_at = origin + transform.transform_vector(&-V3::z()).normalize();
+        let view_up = transform.transform_vector(&V3::y());
+
+        let extras = json.get("extras");
+        let radius = extras.get("apertureRadius").as_f32().unwrap_or(0.0);
+        let focus_distance = extras.get("focusDistance").as_f32().unwrap_or(0.0);
+        Some(if radius > 0.0 && focus_distance > 0.0 {
+            //the focal length of half the focus distance keeps the film in front of the focus
+            GltfCamera::ThinLens(camera::ThinLens::new_with_focus_distance(
+                origin,
+                view_at,
+                view_up,
+                radius,
+                focus_distance / (4.0 * radius),
+                focus_distance,
+                fov_degree,
+            ))
+        } else {
+            GltfCamera::PinHole(camera::PinHole::new(
+                origin, view_at, view_up, fov_degree, None,
+            ))
+        })
+    }
+
+    fn light(&self, json: &Json, transform: &Matrix4<f32>) -> Result<Option<light::Light>, String> {
+        let intensity = color(json.get("color").as_f32s(), RGB::all(1.0))
+            * json.get("intensity").as_f32().unwrap_or(1.0);
+        let pos = transform.transform_point(&P3::origin());
+        let dir = transform.transform_vector(&-V3::z()).normalize();
+        let kind = match json.get("type").as_str() {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+        let profile = json.get("extras").get("profile").as_str();
+        Ok(Some(match (kind, profile) {
+            ("point", Some(file)) => {
+                let file = relative_to(&self.dir, file);
+                let profile = light::photometry::PhotometricProfile::load(&file.to_string_lossy())?;
+                light::lights::GoniometricLight::new(
+                    pos,
+                    -dir,
+                    transform.transform_vector(&V3::x()),
+                    intensity,
+                    profile,
+                )
+                .into()
+            }
+            ("point", None) => light::lights::PointLight { pos, intensity }.into(),
+            ("spot", _) => {
+                let spot = json.get("spot");
+                let inner = spot.get("innerConeAngle").as_f32().unwrap_or(0.0);
+                let outer = spot
+                    .get("outerConeAngle")
+                    .as_f32()
+                    .unwrap_or(std::f32::consts::FRAC_PI_4);
+                light::lights::SpotLight::new(
+                    pos,
+                    pos + dir,
+                    intensity,
+                    outer.to_degrees(),
+                    inner.min(outer).to_degrees(),
+                )
+                .into()
+            }
+            ("directional", _) => light::lights::DistantLight::new(&-dir, intensity).into(),
+            _ => return Ok(None),
+        }))
+    }
+
+    fn node(
+        &self,
+        index: usize,
+        parent: &Matrix4<f32>,
+        scene: &mut GltfScene,
+        depth: usize,
+    ) -> Result<(), String> {
+        if depth > self.json.get("nodes").items().len() {
+            return Err("cyclic nodes".into());
+        }
+        let node = self.json.get("nodes").at(index);
+        let local = if let Some(m) = node.get("matrix").as_f32s().filter(|m| m.len() == 16) {
+            Matrix4::from_column_slice(&m)
+        } else {
+            let t = color(node.get("translation").as_f32s(), RGB::all(0.0));
+            let s = color(node.get("scale").as_f32s(), RGB::all(1.0));
+            let rotation = match node.get("rotation").as_f32s().as_deref() {
+                Some([x, y, z, w]) => {
+                    UnitQuaternion::from_quaternion(Quaternion::new(*w, *x, *y, *z))
+                }
+                _ => UnitQuaternion::identity(),
+            };
+            Translation3::new(t.r, t.g, t.b).to_homogeneous()
+                * rotation.to_homogeneous()
+                * Matrix4::new_nonuniform_scaling(&V3::new(s.r, s.g, s.b))
+        };
+        let transform = parent * local;
+
+        if let Some(mesh) = node.get("mesh").as_usize() {
+            for json in self.json.get("meshes").at(mesh).get("primitives").items() {
+                if let Some(primitive) = self.primitive(json, &transform)? {
+                    scene.primitives.push(primitive);
+                }
+            }
+        }
+        if let Some(camera) = node.get("camera").as_usize() {
+            let json = self.json.get("cameras").at(camera);
+            scene.cameras.extend(self.camera(json, &transform));
+        }
+        if let Some(light) = node
+            .get("extensions")
+            .get("KHR_lights_punctual")
+            .get("light")
+            .as_usize()
+        {
+            let json = self
+                .json
+                .get("extensions")
+                .get("KHR_lights_punctual")
+                .get("lights")
+                .at(light);
+            scene.lights.extend(self.light(json, &transform)?);
+        }
+        for child in node.get("children").items() {
+            let child = child.as_usize().ok_or("invalid child")?;
+            self.node(child, &transform, scene, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn scene(&self) -> Result<GltfScene, String> {
+        let nodes = self.json.get("nodes").items();
+        //without scenes, the nodes which are not children are the roots
+        let roots: Vec<usize> = match self.json.get("scenes").items() {
+            [] => (0..nodes.len())
+                .filter(|i| {
+                    !nodes.iter().any(|n| {
+                        n.get("children")
+                            .items()
+                            .iter()
+                            .any(|c| c.as_usize() == Some(*i))
+                    })
+                })
+                .collect(),
+            scenes => {
+                let scene = self.json.get("scene").as_usize().unwrap_or(0);
+                scenes
+                    .get(scene)
+                    .ok_or("no such scene")?
+                    .get("nodes")
+                    .items()
+                    .iter()
+                    .filter_map(Json::as_usize)
+                    .collect()
+            }
+        };
+        let mut scene = GltfScene {
+            primitives: vec![],
+            lights: vec![],
+            cameras: vec![],
+        };
+        for root in roots {
+            self.node(root, &Matrix4::identity(), &mut scene, 0)?;
+        }
+        Ok(scene)
+    }
+}
+
+#[test]
+fn test_gltf() {
+    assert_eq!(decode_base64("AAECAw==").unwrap(), vec![0, 1, 2, 3]);
+
+    //a triangle with indices, shifted by its node under a rotated parent
+    let mut bin = vec![];
+    for x in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+        bin.extend(x.to_le_bytes());
+    }
+    for i in [0u16, 1, 2, 0] {
+        bin.extend(i.to_le_bytes());
+    }
+    let json = r#"{
+        "asset": {"version": "2.0"},
+        "scene": 0,
+        "scenes": [{"nodes": [0, 2]}],
+        "nodes": [
+            {"rotation": [0, 0, 0.7071068, 0.7071068], "children": [1]},
+            {"mesh": 0, "translation": [0, 0, 2],
+             "extensions": {"KHR_lights_punctual": {"light": 0}}},
+            {"camera": 0, "translation": [0, 0, 5]},
+            {"camera": 1}
+        ],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]}],
+        "materials": [{
+            "pbrMetallicRoughness": {"baseColorFactor": [0.5, 0.25, 1, 1], "metallicFactor": 0},
+            "emissiveFactor": [1, 1, 1],
+            "extensions": {"KHR_materials_emissive_strength": {"emissiveStrength": 4}}
+        }],
+        "cameras": [
+            {"type": "perspective", "perspective": {"yfov": 1.5707964, "znear": 0.1}},
+            {"type": "perspective", "perspective": {"yfov": 1.0, "aspectRatio": 1.5},
+             "extras": {"apertureRadius": 0.1, "focusDistance": 3}}
+        ],
+        "extensions": {"KHR_lights_punctual": {"lights": [
+            {"type": "spot", "intensity": 2, "spot": {"outerConeAngle": 0.5}}
+        ]}},
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 0, "byteOffset": 36, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "bufferViews": [{"buffer": 0, "byteLength": 44}],
+        "buffers": [{"byteLength": 44}]
+    }"#;
+    let mut glb = vec![];
+    let chunk = |kind: u32, data: &[u8]| {
+        let mut data = data.to_vec();
+        data.resize((data.len() + 3) / 4 * 4, b' ');
+        let mut bytes = (data.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(kind.to_le_bytes());
+        bytes.extend(data);
+        bytes
+    };
+    let (json_chunk, bin_chunk) = (chunk(GLB_JSON, json.as_bytes()), chunk(GLB_BIN, &bin));
+    glb.extend(GLB_MAGIC.to_le_bytes());
+    glb.extend(2u32.to_le_bytes());
+    glb.extend(((12 + json_chunk.len() + bin_chunk.len()) as u32).to_le_bytes());
+    glb.extend(json_chunk);
+    glb.extend(bin_chunk);
+
+    let scene = parse_glb(&glb, None).unwrap();
+    assert_eq!(scene.primitives.len(), 1);
+    let mesh = &scene.primitives[0].mesh;
+    //the x axis turns to y
+    assert!((mesh.positions[1] - P3::new(0.0, 1.0, 2.0)).norm() < 1e-5);
+    assert!((mesh.positions[2] - P3::new(-1.0, 0.0, 2.0)).norm() < 1e-5);
+    assert!(scene.primitives[0].emission.is_some());
+    match &scene.primitives[0].material {
+        material::Material::Principled(m) => {
+            assert_eq!(
+                (m.base_color.r, m.base_color.g, m.base_color.b),
+                (0.5, 0.25, 1.0)
+            );
+            assert_eq!((m.metallic, m.roughness), (0.0, 1.0));
+        }
+        _ => panic!("expected a principled material"),
+    }
+    assert_eq!(scene.objects().len(), 1);
+    assert_eq!(scene.lights.len(), 1);
+    assert!(matches!(scene.lights[0], light::Light::SpotLight(_)));
+
+    //only the nodes of the scene are loaded
+    assert_eq!(scene.cameras.len(), 1);
+    let camera = &scene.cameras[0];
+    assert!(matches!(camera, GltfCamera::PinHole(_)));
+    assert!((camera.film_width() - 2.0).abs() < 1e-4);
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+    let ray = camera.sample_ray(0.0, 0.0, &mut rng).unwrap();
+    assert!((ray.origin - P3::new(0.0, 0.0, 5.0)).norm() < 1e-5);
+    assert!((ray.dir + V3::z()).norm() < 1e-5);
+
+    let lens_json = json.replace(r#""nodes": [0, 2]"#, r#""nodes": [3]"#);
+    let scene = Document::new(Json::parse(&lens_json).unwrap(), None, Some(bin.clone()))
+        .unwrap()
+        .scene()
+        .unwrap();
+    assert!(scene.primitives.is_empty());
+    assert!(matches!(scene.cameras[0], GltfCamera::ThinLens(_)));
+    assert!(scene.scene().is_err());
+
+    //invalid accessors and primitives are errors
+    let parse = |from: &str, to: &str| {
+        let json = json.replace(from, to);
+        let glb = [
+            &glb[..12],
+            &chunk(GLB_JSON, json.as_bytes()),
+            &chunk(GLB_BIN, &bin),
+        ]
+        .concat();
+        parse_glb(&glb, None)
+    };
+    assert!(parse("", "").unwrap().scene().is_ok());
+    for (from, to) in [
+        (
+            r#""count": 3, "type": "VEC3""#,
+            r#""count": 4, "type": "VEC3""#,
+        ),
+        (
+            r#""count": 3, "type": "VEC3""#,
+            r#""count": 4611686018427387904, "type": "VEC3""#,
+        ),
+        (
+            r#""count": 3, "type": "VEC3""#,
+            r#""count": 3, "type": "VEC2""#,
+        ),
+        (
+            r#""count": 3, "type": "SCALAR""#,
+            r#""count": 2, "type": "SCALAR""#,
+        ),
+        (r#""byteOffset": 36, "#, r#""byteOffset": 40, "#),
+        (r#""byteLength": 44}]"#, r#""byteLength": 48}]"#),
+        (
+            r#""bufferView": 0, "componentType": 5126"#,
+            r#""componentType": 5126"#,
+        ),
+        (r#""POSITION": 0}"#, r#""POSITION": 0, "NORMAL": 1}"#),
+        (r#""POSITION": 0}"#, r#""POSITION": 0, "COLOR_0": 1}"#),
+    ]
+    .iter()
+    {
+        assert!(parse(from, to).is_err(), "{}", to);
+    }
+
+    //a point light with a profile next to the file
+    let dir = std::env::temp_dir().join("sabiptrace_test_gltf");
+    std::fs::create_dir_all(&dir).unwrap();
+    let ies = "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 2 1 1 2 0 0 0\n1 1 100\n0 90\n0\n300 0\n";
+    std::fs::write(dir.join("lamp.ies"), ies).unwrap();
+    let json = r#"{
+        "nodes": [{"translation": [0, 0, 3],
+                   "extensions": {"KHR_lights_punctual": {"light": 0}}}],
+        "extensions": {"KHR_lights_punctual": {"lights": [
+            {"type": "point", "intensity": 2, "extras": {"profile": "lamp.ies"}}
+        ]}}
+    }"#;
+    let scene = parse_gltf(json, Some(dir.clone())).unwrap();
+    let light = &scene.lights[0];
+    assert!(matches!(light, light::Light::GoniometricLight(_)));
+    //the nadir of the profile is toward -z of the node
+    assert!((light.emission_cos(&-V3::z()).r - 600.0).abs() < 1e-3);
+    assert!(light.emission_cos(&V3::z()).r.abs() < 1e-3);
+    assert!(parse_gltf(&json.replace("lamp.ies", "missing.ies"), Some(dir)).is_err());
+}
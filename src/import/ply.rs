@@ -0,0 +1,356 @@
+//polygon files (PLY) of scans, in ascii or binary.
+//vertices may carry normals, texture coordinates and colors; elements other than
+//the vertices and the faces are skipped.
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Type::I8,
+            "uchar" | "uint8" => Type::U8,
+            "short" | "int16" => Type::I16,
+            "ushort" | "uint16" => Type::U16,
+            "int" | "int32" => Type::I32,
+            "uint" | "uint32" => Type::U32,
+            "float" | "float32" => Type::F32,
+            "double" | "float64" => Type::F64,
+            _ => return Err(format!("unknown type '{}'", name)),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+
+    //colors in integers span their range
+    fn color_scale(self) -> f64 {
+        match self {
+            Type::U8 => 255.0,
+            Type::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Type),
+    //types of the count and of the items
+    List(String, Type, Type),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Body<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    format: Format,
+}
+
+impl<'a> Body<'a> {
+    fn token(&mut self) -> Result<&'a str, String> {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err("unexpected end of data".into());
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).map_err(|e| e.to_string())
+    }
+
+    fn read(&mut self, ty: Type) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            let token = self.token()?;
+            return token
+                .parse::<f64>()
+                .map_err(|e| format!("'{}': {}", token, e));
+        }
+        let size = ty.size();
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + size)
+            .ok_or("unexpected end of data")?;
+        self.pos += size;
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(bytes);
+        if self.format == Format::BigEndian {
+            b[..size].reverse();
+        }
+        Ok(match ty {
+            Type::I8 => b[0] as i8 as f64,
+            Type::U8 => b[0] as f64,
+            Type::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Type::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Type::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Type::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Type::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Type::F64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+//the format, the elements and the offset of the data
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or("no end_header")?;
+    let mut offset = end + END.len();
+    //the line ends with lf or crlf
+    if bytes.get(offset) == Some(&b'\r') {
+        offset += 1;
+    }
+    if bytes.get(offset) == Some(&b'\n') {
+        offset += 1;
+    }
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|e| e.to_string())?;
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("not a ply file".into());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(format!("unknown format '{}'", name)),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("'{}': invalid count", line))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or("property before elements")?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Type::parse(count)?,
+                    Type::parse(item)?,
+                )),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or("property before elements")?
+                .properties
+                .push(Property::Scalar(name.to_string(), Type::parse(ty)?)),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(format!("'{}': unknown header line", line)),
+        }
+    }
+    Ok((format.ok_or("no format")?, elements, offset))
+}
+
+pub fn load(file: &str) -> Result<mesh::Mesh, String> {
+    let bytes = std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+    parse(&bytes).map_err(|e| format!("{}: {}", file, e))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<mesh::Mesh, String> {
+    let (format, elements, offset) = parse_header(bytes)?;
+    let mut body = Body {
+        bytes,
+        pos: offset,
+        format,
+    };
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut colors = vec![];
+    let mut faces = vec![];
+    for element in &elements {
+        let has = |names: &[&str]| {
+            names.iter().all(|n| {
+                element
+                    .properties
+                    .iter()
+                    .any(|p| matches!(p, Property::Scalar(name, _) if name == n))
+            })
+        };
+        let uv_names = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"]]
+            .iter()
+            .find(|names| has(&names[..]))
+            .copied();
+        let (has_normals, has_colors) = (has(&["nx", "ny", "nz"]), has(&["red", "green", "blue"]));
+
+        for _ in 0..element.count {
+            //the scalars by name, and the first list
+            let mut scalars = std::collections::HashMap::new();
+            let mut list = None;
+            for property in &element.properties {
+                match property {
+                    Property::Scalar(name, ty) => {
+                        let mut value = body.read(*ty)?;
+                        if name == "red" || name == "green" || name == "blue" {
+                            value /= ty.color_scale();
+                        }
+                        scalars.insert(name.as_str(), value as f32);
+                    }
+                    Property::List(name, count_type, item_type) => {
+                        let count = body.read(*count_type)? as usize;
+                        let items = (0..count)
+                            .map(|_| body.read(*item_type))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if list.is_none() && (name == "vertex_indices" || name == "vertex_index") {
+                            list = Some(items);
+                        }
+                    }
+                }
+            }
+            let get = |name: &str| scalars.get(name).copied().unwrap_or(0.0);
+            match element.name.as_str() {
+                "vertex" => {
+                    positions.push(P3::new(get("x"), get("y"), get("z")));
+                    if has_normals {
+                        normals.push(V3::new(get("nx"), get("ny"), get("nz")));
+                    }
+                    if let Some([u, v]) = uv_names {
+                        uvs.push(P2::new(get(u), get(v)));
+                    }
+                    if has_colors {
+                        colors.push(RGB::new(get("red"), get("green"), get("blue")));
+                    }
+                }
+                "face" => {
+                    let indices = list.ok_or("faces without vertex_indices")?;
+                    if indices.len() >= 3 {
+                        faces.push(indices.into_iter().map(|i| i as usize).collect::<Vec<_>>());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut mesh = mesh::Mesh::new(positions, faces)?;
+    if !normals.is_empty() {
+        mesh = mesh.set_normals(normals)?;
+    }
+    if !uvs.is_empty() {
+        mesh = mesh.set_uvs(uvs)?;
+    }
+    if !colors.is_empty() {
+        mesh = mesh.set_colors(colors)?;
+    }
+    Ok(mesh)
+}
+
+#[test]
+fn test_ply() {
+    let ascii = b"ply
+format ascii 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 1 255 0 0
+1 1 0 0 0 1 0 0 255
+0 1 0 0 0 1 0 0 255
+4 0 1 2 3
+";
+    let mesh = parse(ascii).unwrap();
+    assert_eq!(mesh.positions[2], P3::new(1.0, 1.0, 0.0));
+    assert_eq!(mesh.faces, vec![vec![0, 1, 2, 3]]);
+    assert_eq!(mesh.normals.as_ref().unwrap()[0], V3::z());
+    assert_eq!(mesh.colors.as_ref().unwrap()[3].b, 1.0);
+    assert_eq!(mesh.triangles().len(), 2);
+
+    //the same triangle in both byte orders, after an element to skip
+    let binary = |format: &str, big: bool| {
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\nelement camera 1\nproperty list uchar short tags\n\
+             element vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+             property float s\nproperty float t\n\
+             element face 1\nproperty list uchar uint vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        let f32s = |bytes: &mut Vec<u8>, xs: &[f32]| {
+            for x in xs {
+                bytes.extend(if big {
+                    x.to_be_bytes()
+                } else {
+                    x.to_le_bytes()
+                });
+            }
+        };
+        bytes.push(2);
+        bytes.extend([0u8; 4]);
+        f32s(&mut bytes, &[0.0, 0.0, 0.0, 0.0, 0.0]);
+        f32s(&mut bytes, &[2.0, 0.0, 0.0, 1.0, 0.0]);
+        f32s(&mut bytes, &[0.0, 2.0, 0.0, 0.0, 1.0]);
+        bytes.push(3);
+        for i in 0..3u32 {
+            bytes.extend(if big {
+                i.to_be_bytes()
+            } else {
+                i.to_le_bytes()
+            });
+        }
+        bytes
+    };
+    for (format, big) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+        let mesh = parse(&binary(format, big)).unwrap();
+        assert_eq!(mesh.positions[1], P3::new(2.0, 0.0, 0.0));
+        assert_eq!(mesh.uvs.as_ref().unwrap()[2], P2::new(0.0, 1.0));
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2]]);
+    }
+    assert!(
+        parse(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n").is_err()
+    );
+}
@@ -1,9 +1,13 @@
 pub mod accum;
+pub mod animation;
 pub mod math;
 pub use math::*;
 pub mod camera;
+pub mod emission;
 pub mod image;
+pub mod import;
 pub mod material;
+pub mod mesh;
 pub mod object;
 pub mod pdf;
 pub mod ray;
@@ -13,7 +17,11 @@ pub mod rgb;
 pub use rgb::*;
 pub mod example_scenes;
 pub mod kdtree;
+pub mod light;
+pub mod lpe;
 mod manager;
 pub mod scene;
 pub mod shape;
+pub mod spectrum;
+pub mod texture;
 pub mod util;
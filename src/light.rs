@@ -0,0 +1,139 @@
+//lights which are not objects: point, spot, distant and goniometric lights.
+//rays never hit them except distant lights with an angular diameter,
+//so they are reached by sampling in NEE, light tracing and BDPT.
+use crate::*;
+
+use rand::prelude::*;
+
+pub mod lights;
+pub mod photometry;
+
+use lights::LightImpl;
+
+//sphere containing the scene. distant lights emit from a disk outside of it.
+#[derive(Clone, Debug)]
+pub struct BoundingSphere {
+    pub center: P3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn around(aabb: &shape::AABB) -> Self {
+        BoundingSphere {
+            center: aabb.center(),
+            radius: (aabb.diag().norm() / 2.0).max(1e-3),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Light {
+    PointLight(lights::PointLight),
+    SpotLight(lights::SpotLight),
+    DistantLight(lights::DistantLight),
+    GoniometricLight(lights::GoniometricLight),
+}
+
+impl_wrap_from_many! {Light, lights, [PointLight, SpotLight, DistantLight, GoniometricLight]}
+
+impl Light {
+    pub fn is_delta(&self) -> bool {
+        match self {
+            Light::PointLight(l) => l.is_delta(),
+            Light::SpotLight(l) => l.is_delta(),
+            Light::DistantLight(l) => l.is_delta(),
+            Light::GoniometricLight(l) => l.is_delta(),
+        }
+    }
+
+    pub fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        bounds: &BoundingSphere,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3, RGB)> {
+        match self {
+            Light::PointLight(l) => l.sample_from(point, bounds, rng),
+            Light::SpotLight(l) => l.sample_from(point, bounds, rng),
+            Light::DistantLight(l) => l.sample_from(point, bounds, rng),
+            Light::GoniometricLight(l) => l.sample_from(point, bounds, rng),
+        }
+    }
+
+    pub fn sample_from_pdf(&self, point: &P3, pos: &P3, bounds: &BoundingSphere) -> f32 {
+        match self {
+            Light::PointLight(l) => l.sample_from_pdf(point, pos, bounds),
+            Light::SpotLight(l) => l.sample_from_pdf(point, pos, bounds),
+            Light::DistantLight(l) => l.sample_from_pdf(point, pos, bounds),
+            Light::GoniometricLight(l) => l.sample_from_pdf(point, pos, bounds),
+        }
+    }
+
+    pub fn sample_vertex<R: Rng + ?Sized>(
+        &self,
+        bounds: &BoundingSphere,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)> {
+        match self {
+            Light::PointLight(l) => l.sample_vertex(bounds, rng),
+            Light::SpotLight(l) => l.sample_vertex(bounds, rng),
+            Light::DistantLight(l) => l.sample_vertex(bounds, rng),
+            Light::GoniometricLight(l) => l.sample_vertex(bounds, rng),
+        }
+    }
+
+    pub fn sample_vertex_pdf(&self, pos: &P3, bounds: &BoundingSphere) -> f32 {
+        match self {
+            Light::PointLight(l) => l.sample_vertex_pdf(pos, bounds),
+            Light::SpotLight(l) => l.sample_vertex_pdf(pos, bounds),
+            Light::DistantLight(l) => l.sample_vertex_pdf(pos, bounds),
+            Light::GoniometricLight(l) => l.sample_vertex_pdf(pos, bounds),
+        }
+    }
+
+    pub fn sample_dir<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(V3, RGB)> {
+        match self {
+            Light::PointLight(l) => l.sample_dir(rng),
+            Light::SpotLight(l) => l.sample_dir(rng),
+            Light::DistantLight(l) => l.sample_dir(rng),
+            Light::GoniometricLight(l) => l.sample_dir(rng),
+        }
+    }
+
+    pub fn emission_toward(&self, dir: &V3) -> RGB {
+        match self {
+            Light::PointLight(l) => l.emission_toward(dir),
+            Light::SpotLight(l) => l.emission_toward(dir),
+            Light::DistantLight(l) => l.emission_toward(dir),
+            Light::GoniometricLight(l) => l.emission_toward(dir),
+        }
+    }
+
+    pub fn sample_dir_pdf(&self, dir: &V3) -> f32 {
+        match self {
+            Light::PointLight(l) => l.sample_dir_pdf(dir),
+            Light::SpotLight(l) => l.sample_dir_pdf(dir),
+            Light::DistantLight(l) => l.sample_dir_pdf(dir),
+            Light::GoniometricLight(l) => l.sample_dir_pdf(dir),
+        }
+    }
+
+    pub fn emission_cos(&self, dir: &V3) -> RGB {
+        match self {
+            Light::PointLight(l) => l.emission_cos(dir),
+            Light::SpotLight(l) => l.emission_cos(dir),
+            Light::DistantLight(l) => l.emission_cos(dir),
+            Light::GoniometricLight(l) => l.emission_cos(dir),
+        }
+    }
+
+    //the color of the intensity or irradiance, which spectral rendering uplifts
+    pub fn color(&self) -> RGB {
+        match self {
+            Light::PointLight(l) => l.intensity,
+            Light::SpotLight(l) => l.intensity,
+            Light::DistantLight(l) => l.irradiance,
+            Light::GoniometricLight(l) => l.color,
+        }
+    }
+}
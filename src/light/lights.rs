@@ -0,0 +1,121 @@
+use crate::light::*;
+
+pub trait LightImpl {
+    //false if rays can hit the light
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    //a vertex on the light to be connected to 'point', as (position, normal, emission toward
+    //'point'). the pdf is with respect to the area around the vertex, or 1 for a delta.
+    fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        bounds: &BoundingSphere,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3, RGB)>;
+
+    fn sample_from_pdf(&self, point: &P3, pos: &P3, bounds: &BoundingSphere) -> f32;
+
+    //a vertex which starts a light path
+    fn sample_vertex<R: Rng + ?Sized>(
+        &self,
+        bounds: &BoundingSphere,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)>;
+
+    fn sample_vertex_pdf(&self, pos: &P3, bounds: &BoundingSphere) -> f32;
+
+    //a direction emitted from the vertex, and the emission toward it multiplied by the cosine
+    fn sample_dir<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(V3, RGB)>;
+
+    fn sample_dir_pdf(&self, dir: &V3) -> f32;
+
+    //the value of 'sample_dir' toward 'dir'
+    fn emission_cos(&self, dir: &V3) -> RGB;
+
+    //radiance toward 'dir', which is zero if the light is a delta
+    fn emission_toward(&self, _dir: &V3) -> RGB {
+        RGB::all(0.0)
+    }
+}
+
+//1 - cos of the angle between unit vectors, without cancellation for small angles
+fn one_minus_cos(a: &V3, b: &V3) -> f32 {
+    (a - b).norm_squared() / 2.0
+}
+
+//a direction uniformly distributed in the cone around the z axis of 'lc'
+//whose 1 - cos of the half angle is 'one_minus_cos_max'. 2 gives the whole sphere.
+fn sample_cone<R: Rng + ?Sized>(
+    lc: &LocalCoord,
+    one_minus_cos_max: f32,
+    rng: &mut R,
+) -> pdf::PdfSample<V3> {
+    use rand::distributions::Uniform;
+    let one_minus_cos = Uniform::new_inclusive(0.0, one_minus_cos_max).sample(rng);
+    let phi = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+    let cos_theta = 1.0 - one_minus_cos;
+    let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).max(0.0).sqrt();
+    pdf::PdfSample {
+        value: lc.l2w() * V3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+        pdf: cone_pdf(one_minus_cos_max),
+    }
+}
+
+fn cone_pdf(one_minus_cos_max: f32) -> f32 {
+    std::f32::consts::FRAC_1_PI / 2.0 / one_minus_cos_max
+}
+
+//the vertex of a light at 'pos' with intensity 'intensity' toward a direction
+fn connect_positional<F: Fn(&V3) -> RGB>(
+    pos: &P3,
+    point: &P3,
+    intensity: F,
+) -> pdf::PdfSample<(P3, V3, RGB)> {
+    let dir = (point - pos).normalize();
+    pdf::PdfSample {
+        value: (*pos, dir, intensity(&dir)),
+        pdf: 1.0,
+    }
+}
+
+//checks that the samples agree with the pdfs and the emission, and returns the integral of
+//emission_cos over the directions by sample_dir and by uniformly distributed directions
+#[cfg(test)]
+fn sample_power<L: LightImpl>(light: &L, point: &P3) -> (f32, f32) {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let bounds = BoundingSphere {
+        center: P3::origin(),
+        radius: 10.0,
+    };
+    let sphere = LocalCoord::new_zx(&P3::origin(), &V3::z(), &V3::x());
+    let n = 100000;
+    let (mut by_sample, mut by_uniform) = (0.0, 0.0);
+    for _ in 0..n {
+        let s = light.sample_from(point, &bounds, &mut rng);
+        assert!((s.pdf - light.sample_from_pdf(point, &s.value.0, &bounds)).abs() <= s.pdf * 1e-3);
+
+        let s = light.sample_dir(&mut rng);
+        let (dir, emission) = s.value;
+        assert!((s.pdf - light.sample_dir_pdf(&dir)).abs() <= s.pdf * 1e-3);
+        assert!((emission.r - light.emission_cos(&dir).r).abs() <= emission.r * 1e-3);
+        by_sample += emission.r / s.pdf;
+
+        let s = sample_cone(&sphere, 2.0, &mut rng);
+        by_uniform += light.emission_cos(&s.value).r / s.pdf;
+    }
+    (by_sample / n as f32, by_uniform / n as f32)
+}
+
+mod point;
+pub use point::*;
+
+mod spot;
+pub use spot::*;
+
+mod distant;
+pub use distant::*;
+
+mod goniometric;
+pub use goniometric::*;
@@ -0,0 +1,177 @@
+use super::*;
+
+//light from infinitely far away, such as the sun. 'irradiance' is measured on a surface facing
+//the light. with an angular diameter, the light is a disk of constant radiance in the sky.
+//
+//light paths start from a disk outside the bounding sphere of the scene, and the vertices
+//sampled from points are placed on the plane of the disk.
+#[derive(Clone)]
+pub struct DistantLight {
+    //z is the direction toward the light
+    lc: LocalCoord,
+    pub irradiance: RGB,
+    //1 - cos of the angular radius. 0 for a delta direction.
+    one_minus_cos_max: f32,
+}
+
+impl DistantLight {
+    pub fn new(to_light: &V3, irradiance: RGB) -> Self {
+        Self::new_disk(to_light, irradiance, 0.0)
+    }
+
+    pub fn new_disk(to_light: &V3, irradiance: RGB, angular_diameter_degree: f32) -> Self {
+        let half_angle = angular_diameter_degree.to_radians() / 2.0;
+        DistantLight {
+            lc: LocalCoord::new_zx(&P3::origin(), to_light, &pick_orthogonal(to_light)),
+            irradiance,
+            one_minus_cos_max: 2.0 * (half_angle / 2.0).sin().powi(2),
+        }
+    }
+
+    pub fn to_light(&self) -> V3 {
+        self.lc.w()
+    }
+
+    //radiance of the disk which gives the irradiance
+    fn radiance(&self) -> RGB {
+        let sq_sin_max = self.one_minus_cos_max * (2.0 - self.one_minus_cos_max);
+        self.irradiance * std::f32::consts::FRAC_1_PI / sq_sin_max
+    }
+
+    //a point on the plane where light paths start
+    fn plane_origin(&self, bounds: &BoundingSphere) -> P3 {
+        bounds.center + self.to_light() * 2.0 * bounds.radius
+    }
+
+    fn in_disk(&self, to_light: &V3) -> bool {
+        one_minus_cos(to_light, &self.to_light()) <= self.one_minus_cos_max
+    }
+}
+
+impl LightImpl for DistantLight {
+    fn is_delta(&self) -> bool {
+        self.one_minus_cos_max == 0.0
+    }
+
+    fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        bounds: &BoundingSphere,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3, RGB)> {
+        let w = self.to_light();
+        let plane_dist = (self.plane_origin(bounds) - point).dot(&w);
+        if self.is_delta() {
+            //the emission is scaled to cancel the geometry term to the plane
+            return pdf::PdfSample {
+                value: (
+                    point + w * plane_dist,
+                    -w,
+                    self.irradiance * plane_dist * plane_dist,
+                ),
+                pdf: 1.0,
+            };
+        }
+        let dir = sample_cone(&self.lc, self.one_minus_cos_max, rng).value;
+        let pos = point + dir * (plane_dist / dir.dot(&w));
+        pdf::PdfSample {
+            value: (pos, -w, self.radiance()),
+            pdf: self.sample_from_pdf(point, &pos, bounds),
+        }
+    }
+
+    fn sample_from_pdf(&self, point: &P3, pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        if self.is_delta() {
+            return 1.0;
+        }
+        let r = pos - point;
+        let sq_dist = r.norm_squared();
+        let dir = r / sq_dist.sqrt();
+        if !self.in_disk(&dir) {
+            return 0.0;
+        }
+        cone_pdf(self.one_minus_cos_max) * dir.dot(&self.to_light()) / sq_dist
+    }
+
+    fn sample_vertex<R: Rng + ?Sized>(
+        &self,
+        bounds: &BoundingSphere,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)> {
+        use rand::distributions::Uniform;
+        let r = bounds.radius * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
+        let phi = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+        let offset = self.lc.u() * r * phi.cos() + self.lc.v() * r * phi.sin();
+        pdf::PdfSample {
+            value: (self.plane_origin(bounds) + offset, -self.to_light()),
+            pdf: self.sample_vertex_pdf(&P3::origin(), bounds),
+        }
+    }
+
+    fn sample_vertex_pdf(&self, _pos: &P3, bounds: &BoundingSphere) -> f32 {
+        std::f32::consts::FRAC_1_PI / bounds.radius / bounds.radius
+    }
+
+    fn sample_dir<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(V3, RGB)> {
+        if self.is_delta() {
+            return pdf::PdfSample {
+                value: (-self.to_light(), self.irradiance),
+                pdf: 1.0,
+            };
+        }
+        sample_cone(&self.lc, self.one_minus_cos_max, rng)
+            .map(|w| (-w, self.radiance() * w.dot(&self.to_light())))
+    }
+
+    fn sample_dir_pdf(&self, dir: &V3) -> f32 {
+        if self.is_delta() {
+            1.0
+        } else if self.in_disk(&-dir) {
+            cone_pdf(self.one_minus_cos_max)
+        } else {
+            0.0
+        }
+    }
+
+    fn emission_toward(&self, dir: &V3) -> RGB {
+        if !self.is_delta() && self.in_disk(&-dir) {
+            self.radiance()
+        } else {
+            RGB::all(0.0)
+        }
+    }
+
+    fn emission_cos(&self, dir: &V3) -> RGB {
+        self.emission_toward(dir) * -dir.dot(&self.to_light())
+    }
+}
+
+#[test]
+fn test_distant_disk_light() {
+    let to_light = V3::new(1.0, 2.0, 2.0).normalize();
+    let light = DistantLight::new_disk(&to_light, RGB::all(3.0), 60.0);
+    let point = P3::new(1.0, 0.0, -1.0);
+    let (by_sample, by_uniform) = super::sample_power(&light, &point);
+    //the power through a unit area facing the light is the irradiance
+    assert!((by_sample - 3.0).abs() < 3.0 * 0.01);
+    assert!((by_uniform - 3.0).abs() < 3.0 * 0.05);
+
+    //the irradiance at the point by the vertices sampled from it
+    let mut rng = SmallRng::seed_from_u64(0);
+    let bounds = BoundingSphere {
+        center: P3::origin(),
+        radius: 5.0,
+    };
+    let n = 10000;
+    let irradiance: f32 = (0..n)
+        .map(|_| {
+            let s = light.sample_from(&point, &bounds, &mut rng);
+            let (pos, normal, emission) = s.value;
+            let r = pos - point;
+            let dir = r.normalize();
+            emission.r * dir.dot(&to_light) * normal.dot(&-dir) / r.norm_squared() / s.pdf
+        })
+        .sum::<f32>()
+        / n as f32;
+    assert!((irradiance - 3.0).abs() < 3.0 * 0.01);
+}
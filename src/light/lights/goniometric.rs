@@ -0,0 +1,89 @@
+use super::*;
+use photometry::PhotometricProfile;
+
+//point light whose intensity is given by a measured profile. the profile's nadir is -'up'
+//and its horizontal angle 0 is toward 'c0'. the intensity is 'color' per candela.
+#[derive(Clone)]
+pub struct GoniometricLight {
+    lc: LocalCoord,
+    pub color: RGB,
+    profile: PhotometricProfile,
+}
+
+impl GoniometricLight {
+    pub fn new(pos: P3, up: V3, c0: V3, color: RGB, profile: PhotometricProfile) -> Self {
+        GoniometricLight {
+            lc: LocalCoord::new_zx(&pos, &up, &c0),
+            color,
+            profile,
+        }
+    }
+
+    pub fn pos(&self) -> P3 {
+        self.lc.origin()
+    }
+
+    fn intensity(&self, dir: &V3) -> RGB {
+        self.color * self.profile.candela(&(self.lc.w2l() * dir))
+    }
+}
+
+impl LightImpl for GoniometricLight {
+    fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        _bounds: &BoundingSphere,
+        _rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3, RGB)> {
+        connect_positional(&self.pos(), point, |dir| self.intensity(dir))
+    }
+
+    fn sample_from_pdf(&self, _point: &P3, _pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        1.0
+    }
+
+    fn sample_vertex<R: Rng + ?Sized>(
+        &self,
+        _bounds: &BoundingSphere,
+        _rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)> {
+        pdf::PdfSample {
+            value: (self.pos(), -self.lc.w()),
+            pdf: 1.0,
+        }
+    }
+
+    fn sample_vertex_pdf(&self, _pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        1.0
+    }
+
+    fn sample_dir<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(V3, RGB)> {
+        sample_cone(&self.lc, 2.0, rng).map(|dir| (dir, self.intensity(&dir)))
+    }
+
+    fn sample_dir_pdf(&self, _dir: &V3) -> f32 {
+        cone_pdf(2.0)
+    }
+
+    fn emission_cos(&self, dir: &V3) -> RGB {
+        self.intensity(dir)
+    }
+}
+
+#[test]
+fn test_goniometric_light() {
+    let ies = "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 3 2 1 2 0 0 0\n1 1 100\n\
+               0 60 120\n0 90\n100 50 10\n200 80 0\n";
+    let profile = PhotometricProfile::parse_ies(ies).unwrap();
+    let light = GoniometricLight::new(
+        P3::new(0.0, 0.0, 3.0),
+        V3::new(0.0, 1.0, 1.0),
+        V3::x(),
+        RGB::all(1.0),
+        profile,
+    );
+    let (by_sample, by_uniform) = super::sample_power(&light, &P3::origin());
+    assert!((by_sample - by_uniform).abs() < by_sample * 0.02);
+    //the nadir is opposite to 'up'
+    assert!((light.emission_cos(&V3::new(0.0, -1.0, -1.0).normalize()).r - 100.0).abs() < 1e-3);
+}
@@ -0,0 +1,51 @@
+use super::*;
+
+//emits 'intensity' uniformly in all directions
+#[derive(Clone)]
+pub struct PointLight {
+    pub pos: P3,
+    pub intensity: RGB,
+}
+
+impl LightImpl for PointLight {
+    fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        _bounds: &BoundingSphere,
+        _rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3, RGB)> {
+        connect_positional(&self.pos, point, |_| self.intensity)
+    }
+
+    fn sample_from_pdf(&self, _point: &P3, _pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        1.0
+    }
+
+    fn sample_vertex<R: Rng + ?Sized>(
+        &self,
+        _bounds: &BoundingSphere,
+        _rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)> {
+        pdf::PdfSample {
+            value: (self.pos, V3::z()),
+            pdf: 1.0,
+        }
+    }
+
+    fn sample_vertex_pdf(&self, _pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        1.0
+    }
+
+    fn sample_dir<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(V3, RGB)> {
+        let lc = LocalCoord::new_zx(&self.pos, &V3::z(), &V3::x());
+        sample_cone(&lc, 2.0, rng).map(|dir| (dir, self.intensity))
+    }
+
+    fn sample_dir_pdf(&self, _dir: &V3) -> f32 {
+        cone_pdf(2.0)
+    }
+
+    fn emission_cos(&self, _dir: &V3) -> RGB {
+        self.intensity
+    }
+}
@@ -0,0 +1,117 @@
+use super::*;
+
+//point light emitting into a cone. the intensity falls off smoothly
+//from 'falloff_start_degree' to 'total_width_degree' away from the axis.
+#[derive(Clone)]
+pub struct SpotLight {
+    lc: LocalCoord,
+    pub intensity: RGB,
+    cos_total_width: f32,
+    cos_falloff_start: f32,
+}
+
+impl SpotLight {
+    pub fn new(
+        pos: P3,
+        look_at: P3,
+        intensity: RGB,
+        total_width_degree: f32,
+        falloff_start_degree: f32,
+    ) -> Self {
+        assert!(falloff_start_degree <= total_width_degree);
+        let axis = look_at - pos;
+        SpotLight {
+            lc: LocalCoord::new_zx(&pos, &axis, &pick_orthogonal(&axis)),
+            intensity,
+            cos_total_width: total_width_degree.to_radians().cos(),
+            cos_falloff_start: falloff_start_degree.to_radians().cos(),
+        }
+    }
+
+    pub fn pos(&self) -> P3 {
+        self.lc.origin()
+    }
+
+    fn falloff(&self, dir: &V3) -> f32 {
+        let cos = self.lc.w().dot(dir);
+        if cos < self.cos_total_width {
+            0.0
+        } else if cos >= self.cos_falloff_start {
+            1.0
+        } else {
+            let t = (cos - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl LightImpl for SpotLight {
+    fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        _bounds: &BoundingSphere,
+        _rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3, RGB)> {
+        connect_positional(&self.pos(), point, |dir| self.intensity * self.falloff(dir))
+    }
+
+    fn sample_from_pdf(&self, _point: &P3, _pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        1.0
+    }
+
+    fn sample_vertex<R: Rng + ?Sized>(
+        &self,
+        _bounds: &BoundingSphere,
+        _rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)> {
+        pdf::PdfSample {
+            value: (self.pos(), self.lc.w()),
+            pdf: 1.0,
+        }
+    }
+
+    fn sample_vertex_pdf(&self, _pos: &P3, _bounds: &BoundingSphere) -> f32 {
+        1.0
+    }
+
+    fn sample_dir<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(V3, RGB)> {
+        sample_cone(&self.lc, 1.0 - self.cos_total_width, rng)
+            .map(|dir| (dir, self.intensity * self.falloff(&dir)))
+    }
+
+    fn sample_dir_pdf(&self, dir: &V3) -> f32 {
+        if self.lc.w().dot(dir) < self.cos_total_width {
+            0.0
+        } else {
+            cone_pdf(1.0 - self.cos_total_width)
+        }
+    }
+
+    fn emission_cos(&self, dir: &V3) -> RGB {
+        self.intensity * self.falloff(dir)
+    }
+}
+
+#[test]
+fn test_spot_light() {
+    let light = SpotLight::new(
+        P3::new(1.0, 2.0, 3.0),
+        P3::new(1.0, 0.0, 3.0),
+        RGB::all(2.0),
+        60.0,
+        40.0,
+    );
+    let (by_sample, by_uniform) = super::sample_power(&light, &P3::origin());
+    assert!((by_sample - by_uniform).abs() < by_sample * 0.02);
+    //the full intensity inside the falloff, and nothing behind
+    let toward = |point: &P3| {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let bounds = BoundingSphere {
+            center: P3::origin(),
+            radius: 10.0,
+        };
+        light.sample_from(point, &bounds, &mut rng).value.2.r
+    };
+    assert_eq!(toward(&P3::new(1.0, -5.0, 3.0)), 2.0);
+    assert_eq!(toward(&P3::new(1.0, 5.0, 3.0)), 0.0);
+}
@@ -0,0 +1,302 @@
+//luminous intensity distributions from IES LM-63 and EULUMDAT (LDT) files.
+//only the type C photometry, where the vertical angle is measured from the nadir, is supported.
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub struct PhotometricProfile {
+    //vertical angles from the nadir in degrees, ascending
+    vertical: Vec<f32>,
+    //horizontal angles in degrees, ascending over 360 degrees from the first
+    horizontal: Vec<f32>,
+    //candela[h][v]
+    candela: Vec<Vec<f32>>,
+}
+
+fn parse_numbers(tokens: &[&str]) -> Result<Vec<f32>, String> {
+    tokens
+        .iter()
+        .map(|t| t.parse::<f32>().map_err(|e| format!("'{}': {}", t, e)))
+        .collect()
+}
+
+//interval containing x and the position in it
+fn locate(xs: &[f32], x: f32) -> Option<(usize, f32)> {
+    if xs.len() == 1 {
+        return if x == xs[0] { Some((0, 0.0)) } else { None };
+    }
+    if x < xs[0] || xs[xs.len() - 1] < x {
+        return None;
+    }
+    let i = xs[1..xs.len() - 1]
+        .iter()
+        .position(|a| x < *a)
+        .unwrap_or(xs.len() - 2);
+    let t = (x - xs[i]) / (xs[i + 1] - xs[i]);
+    Some((i, t))
+}
+
+impl PhotometricProfile {
+    fn new(
+        vertical: Vec<f32>,
+        mut horizontal: Vec<f32>,
+        mut candela: Vec<Vec<f32>>,
+    ) -> Result<Self, String> {
+        if vertical.is_empty() || horizontal.is_empty() {
+            return Err("no angles".into());
+        }
+        if candela.len() != horizontal.len() || candela.iter().any(|c| c.len() != vertical.len()) {
+            return Err("wrong number of candela values".into());
+        }
+        let finite_ascending =
+            |xs: &[f32]| xs.iter().all(|x| x.is_finite()) && xs.windows(2).all(|w| w[0] < w[1]);
+        if !finite_ascending(&vertical) || !finite_ascending(&horizontal) {
+            return Err("angles are not finite and ascending".into());
+        }
+        if candela.iter().flatten().any(|c| !c.is_finite()) {
+            return Err("candela values are not finite".into());
+        }
+        //completes the horizontal angles by the symmetry:
+        //a single plane is rotationally symmetric, and the others are mirrored at the last angle.
+        //the sets are a quadrant, a half or the whole from 0, or the half from 90 to 270.
+        let (first, last) = (horizontal[0], horizontal[horizontal.len() - 1]);
+        if horizontal.len() == 1 {
+            horizontal.push(first + 360.0);
+            candela.push(candela[0].clone());
+        } else if ![(0.0, 90.0), (0.0, 180.0), (0.0, 360.0), (90.0, 270.0)].contains(&(first, last))
+        {
+            return Err(format!("horizontal angles from {} to {}", first, last));
+        }
+        while horizontal[horizontal.len() - 1] < first + 360.0 {
+            let last = horizontal[horizontal.len() - 1];
+            let n = horizontal.len();
+            for i in (0..n - 1).rev() {
+                horizontal.push(2.0 * last - horizontal[i]);
+                candela.push(candela[i].clone());
+            }
+        }
+        Ok(PhotometricProfile {
+            vertical,
+            horizontal,
+            candela,
+        })
+    }
+
+    //reads a file by its extension, .ies or .ldt
+    pub fn load(file: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+        let lower = file.to_lowercase();
+        let result = if lower.ends_with(".ies") {
+            Self::parse_ies(&text)
+        } else if lower.ends_with(".ldt") {
+            Self::parse_ldt(&text)
+        } else {
+            Err("unknown file type".into())
+        };
+        result.map_err(|e| format!("{}: {}", file, e))
+    }
+
+    pub fn parse_ies(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find(|l| l.starts_with("TILT="))
+            .ok_or("missing TILT")?;
+        let tokens: Vec<&str> = lines
+            .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|t| !t.is_empty())
+            .collect();
+        let mut values = &parse_numbers(&tokens)?[..];
+        let mut take = |n: usize| -> Result<&[f32], String> {
+            if values.len() < n {
+                return Err("unexpected end of data".into());
+            }
+            let (head, tail) = values.split_at(n);
+            values = tail;
+            Ok(head)
+        };
+        match &tilt[5..] {
+            "NONE" => (),
+            "INCLUDE" => {
+                //lamp-to-luminaire geometry, then the tilt angles and factors
+                let n = take(2)?[1] as usize;
+                take(2 * n)?;
+            }
+            _ => return Err("TILT files are not supported".into()),
+        }
+        let header = take(13)?;
+        let multiplier = header[2] * header[10];
+        let (n_vertical, n_horizontal) = (header[3] as usize, header[4] as usize);
+        if header[5] != 1.0 {
+            return Err("photometric type other than C".into());
+        }
+        let vertical = take(n_vertical)?.to_vec();
+        let horizontal = take(n_horizontal)?.to_vec();
+        let candela = (0..n_horizontal)
+            .map(|_| Ok(take(n_vertical)?.iter().map(|c| c * multiplier).collect()))
+            .collect::<Result<Vec<_>, String>>()?;
+        Self::new(vertical, horizontal, candela)
+    }
+
+    pub fn parse_ldt(text: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        let line = |i: usize| -> Result<f32, String> {
+            let l = lines.get(i - 1).ok_or("unexpected end of data")?;
+            l.parse::<f32>()
+                .map_err(|e| format!("line {}: '{}': {}", i, l, e))
+        };
+        let symmetry = line(3)? as usize;
+        let n_c = line(4)? as usize;
+        let n_gamma = line(6)? as usize;
+        let conversion = line(24)?;
+        let n_sets = line(26)? as usize;
+        //each set of lamps has 6 lines, and the total luminous flux is the third
+        let flux: f32 = (0..n_sets)
+            .map(|i| line(27 + n_sets * 2 + i))
+            .sum::<Result<f32, String>>()?;
+        let first = 27 + 6 * n_sets + 10;
+        let c_angles = (0..n_c)
+            .map(|i| line(first + i))
+            .collect::<Result<Vec<_>, _>>()?;
+        let gamma = (0..n_gamma)
+            .map(|i| line(first + n_c + i))
+            .collect::<Result<Vec<_>, _>>()?;
+        if c_angles.is_empty() {
+            return Err("no C planes".into());
+        }
+        if c_angles.iter().chain(&gamma).any(|a| !a.is_finite()) {
+            return Err("angles are not finite".into());
+        }
+
+        //planes stored in the file, as the range of indices in c_angles
+        let (begin, end) = match symmetry {
+            0 => (0, n_c),
+            1 => (0, 1),
+            2 => (0, n_c / 2 + 1),
+            3 => (n_c / 4, 3 * n_c / 4 + 1),
+            4 => (0, n_c / 4 + 1),
+            _ => return Err(format!("unknown symmetry {}", symmetry)),
+        };
+        //intensities are in cd/klm
+        let scale = conversion * flux / 1000.0;
+        let intensities = (begin..end)
+            .map(|p| {
+                (0..n_gamma)
+                    .map(|i| Ok(line(first + n_c + n_gamma + (p - begin) * n_gamma + i)? * scale))
+                    .collect::<Result<Vec<_>, String>>()
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        //planes for all angles in [0, 360]
+        let stored = |c: f32| -> &Vec<f32> {
+            let c = match symmetry {
+                1 => c_angles[0],
+                2 if c > 180.0 => 360.0 - c,
+                3 if c < 90.0 => 180.0 - c,
+                3 if c > 270.0 => 540.0 - c,
+                4 => {
+                    let c = c % 180.0;
+                    if c > 90.0 {
+                        180.0 - c
+                    } else {
+                        c
+                    }
+                }
+                _ => c,
+            };
+            //the range is not empty as there are planes
+            let p = (begin..end)
+                .min_by(|i, j| {
+                    let di = (c_angles[*i] - c).abs();
+                    let dj = (c_angles[*j] - c).abs();
+                    di.total_cmp(&dj)
+                })
+                .unwrap();
+            &intensities[p - begin]
+        };
+        let mut horizontal = c_angles.clone();
+        horizontal.push(360.0);
+        let candela = horizontal
+            .iter()
+            .map(|c| stored(*c % 360.0).clone())
+            .collect();
+        Self::new(gamma, horizontal, candela)
+    }
+
+    //intensity toward the direction in the local coordinate, whose -z is the nadir
+    pub fn candela(&self, dir: &V3) -> f32 {
+        let dir = dir.normalize();
+        let v = (-dir[2]).clamp(-1.0, 1.0).acos().to_degrees();
+        let h = dir[1].atan2(dir[0]).to_degrees();
+        let h = self.horizontal[0] + (h - self.horizontal[0]).rem_euclid(360.0);
+        let (iv, tv) = match locate(&self.vertical, v) {
+            Some(l) => l,
+            None => return 0.0,
+        };
+        let (ih, th) = locate(&self.horizontal, h).unwrap_or((0, 0.0));
+        let at = |i: usize, j: usize| {
+            let row = &self.candela[(ih + i).min(self.horizontal.len() - 1)];
+            row[(iv + j).min(self.vertical.len() - 1)]
+        };
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        lerp(
+            lerp(at(0, 0), at(0, 1), tv),
+            lerp(at(1, 0), at(1, 1), tv),
+            th,
+        )
+    }
+}
+
+#[test]
+fn test_parse_ies() {
+    let ies = "IESNA:LM-63-2002\n[TEST] test\nTILT=NONE\n\
+               1 1000 2.0 3 2 1 2 0 0 0\n1.0 1.0 100\n\
+               0 45 90\n0 90\n\
+               100 50 0\n200 100 0\n";
+    let profile = PhotometricProfile::parse_ies(ies).unwrap();
+    assert_eq!(profile.horizontal.len(), 5);
+    //straight down, in the plane 0 and in the plane 90 which is mirrored to 270
+    assert_eq!(profile.candela(&-V3::z()), 200.0);
+    assert_eq!(profile.candela(&V3::new(1.0, 0.0, -1.0)), 100.0);
+    assert_eq!(profile.candela(&V3::new(0.0, -1.0, -1.0)), 200.0);
+    assert!((profile.candela(&V3::new(1.0, 1.0, -2.0f32.sqrt())) - 150.0).abs() < 1e-3);
+    assert_eq!(profile.candela(&V3::z()), 0.0);
+
+    //the half from 90 to 270 is mirrored at the plane through them
+    let ies = ies.replace("0 90\n", "90 270\n");
+    let profile = PhotometricProfile::parse_ies(&ies).unwrap();
+    assert_eq!(profile.candela(&V3::new(0.0, 1.0, -1.0)), 100.0);
+    assert_eq!(profile.candela(&V3::new(0.0, -1.0, -1.0)), 200.0);
+    assert!((profile.candela(&V3::new(-1.0, 0.0, -1.0)) - 150.0).abs() < 1e-3);
+    assert!((profile.candela(&V3::new(1.0, 0.0, -1.0)) - 150.0).abs() < 1e-3);
+
+    //repeated, descending and infinite angles, and sets which are not of a symmetry
+    for angles in ["0 0\n", "90 0\n", "0 inf\n", "0 45\n", "90 180\n"].iter() {
+        let ies = ies.replace("90 270\n", angles);
+        assert!(PhotometricProfile::parse_ies(&ies).is_err(), "{}", angles);
+    }
+    let ies = ies.replace("0 45 90\n", "0 90 45\n");
+    assert!(PhotometricProfile::parse_ies(&ies).is_err());
+}
+
+#[test]
+fn test_parse_ldt() {
+    //rotationally symmetric, 4 C-planes and 3 gamma angles, 1000 lm
+    let mut lines = vec!["test", "1", "1", "4", "90", "3", "45"];
+    lines.extend(&["", "", "", "", ""]);
+    lines.extend(&["0"; 9]);
+    lines.extend(&["100", "100", "2.0", "0", "1"]);
+    lines.extend(&["1", "lamp", "1000", "3000", "80", "10"]);
+    lines.extend(&["0"; 10]);
+    lines.extend(&["0", "90", "180", "270", "0", "45", "90", "300", "200", "0"]);
+    let profile = PhotometricProfile::parse_ldt(&lines.join("\n")).unwrap();
+    //cd/klm * 1 klm * the conversion factor
+    assert_eq!(profile.candela(&-V3::z()), 600.0);
+    assert_eq!(profile.candela(&V3::new(0.0, -1.0, -1.0)), 400.0);
+
+    //without C planes
+    lines.truncate(lines.len() - 10);
+    lines[3] = "0";
+    lines.extend(&["0", "45", "90", "300", "200", "0"]);
+    assert!(PhotometricProfile::parse_ldt(&lines.join("\n")).is_err());
+}
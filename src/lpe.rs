@@ -0,0 +1,405 @@
+//light path expressions
+//
+//a path is described by one event per vertex, read from the light to the eye,
+//e.g. "L S+ D E" selects caustics and "L D D+ E" selects indirect diffuse.
+//
+//symbols:
+//  E  eye
+//  L  light (emitters and environment)
+//  D  diffuse (any non-delta scattering)
+//  S  specular (delta reflection or transmission)
+//  R  reflection (diffuse or specular)
+//  T  transmission (diffuse or specular)
+//  .  any event
+//  [..] / [^..]  set of symbols / complement of set
+//operators: concatenation, '|', '*', '+', '?' and parentheses. whitespace is ignored.
+use crate::accum::*;
+use crate::*;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Eye,
+    Light,
+    Scatter { specular: bool, transmission: bool },
+}
+
+impl Event {
+    pub fn scatter(specular: bool, win_local: &V3, wout_local: &V3) -> Self {
+        Event::Scatter {
+            specular,
+            transmission: win_local[2] * wout_local[2] < 0.0,
+        }
+    }
+
+    fn matches(&self, symbol: char) -> bool {
+        use Event::*;
+        match (symbol, self) {
+            ('.', _) => true,
+            ('E', Eye) => true,
+            ('L', Light) => true,
+            ('D', Scatter { specular, .. }) => !specular,
+            ('S', Scatter { specular, .. }) => *specular,
+            ('R', Scatter { transmission, .. }) => !transmission,
+            ('T', Scatter { transmission, .. }) => *transmission,
+            _ => false,
+        }
+    }
+}
+
+const SYMBOLS: &str = "ELDSRT.";
+
+#[derive(Clone, Debug)]
+enum Node {
+    Set { symbols: Vec<char>, negate: bool },
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Star(Box<Node>),
+    Plus(Box<Node>),
+    Opt(Box<Node>),
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else {
+                return Some(*c);
+            }
+        }
+        None
+    }
+
+    fn symbol(c: char) -> Result<char, String> {
+        if SYMBOLS.contains(c) {
+            Ok(c)
+        } else {
+            Err(format!("unknown event symbol '{}'", c))
+        }
+    }
+
+    fn alt(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.chars.next();
+            alts.push(self.concat()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            Node::Alt(alts)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            nodes.push(self.repeat()?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn repeat(&mut self) -> Result<Node, String> {
+        let mut node = self.atom()?;
+        loop {
+            node = match self.peek() {
+                Some('*') => Node::Star(Box::new(node)),
+                Some('+') => Node::Plus(Box::new(node)),
+                Some('?') => Node::Opt(Box::new(node)),
+                _ => return Ok(node),
+            };
+            self.chars.next();
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.chars.next() {
+            Some('(') => {
+                let node = self.alt()?;
+                if self.peek() != Some(')') {
+                    return Err("missing ')'".into());
+                }
+                self.chars.next();
+                Ok(node)
+            }
+            Some('[') => {
+                let negate = if self.peek() == Some('^') {
+                    self.chars.next();
+                    true
+                } else {
+                    false
+                };
+                let mut symbols = vec![];
+                loop {
+                    match self.peek() {
+                        Some(']') => {
+                            self.chars.next();
+                            break;
+                        }
+                        Some(c) => {
+                            self.chars.next();
+                            symbols.push(Self::symbol(c)?);
+                        }
+                        None => return Err("missing ']'".into()),
+                    }
+                }
+                Ok(Node::Set { symbols, negate })
+            }
+            Some(c) => Ok(Node::Set {
+                symbols: vec![Self::symbol(c)?],
+                negate: false,
+            }),
+            None => Err("unexpected end of expression".into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Match { symbols: Vec<char>, negate: bool },
+    Split(usize, usize),
+    Jump(usize),
+    Accept,
+}
+
+//an expression compiled into a Thompson NFA
+#[derive(Clone, Debug)]
+pub struct Lpe {
+    source: String,
+    prog: Vec<Inst>,
+}
+
+impl Lpe {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: source.chars().peekable(),
+        };
+        let node = parser.alt()?;
+        if let Some(c) = parser.peek() {
+            return Err(format!("unexpected '{}'", c));
+        }
+        let mut prog = vec![];
+        Self::compile(&node, &mut prog);
+        prog.push(Inst::Accept);
+        Ok(Lpe {
+            source: source.into(),
+            prog,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn compile(node: &Node, prog: &mut Vec<Inst>) {
+        match node {
+            Node::Set { symbols, negate } => prog.push(Inst::Match {
+                symbols: symbols.clone(),
+                negate: *negate,
+            }),
+            Node::Concat(nodes) => {
+                for n in nodes {
+                    Self::compile(n, prog);
+                }
+            }
+            Node::Alt(nodes) => {
+                let mut jumps = vec![];
+                for (i, n) in nodes.iter().enumerate() {
+                    if i + 1 < nodes.len() {
+                        let split = prog.len();
+                        prog.push(Inst::Split(split + 1, 0));
+                        Self::compile(n, prog);
+                        jumps.push(prog.len());
+                        prog.push(Inst::Jump(0));
+                        let next = prog.len();
+                        prog[split] = Inst::Split(split + 1, next);
+                    } else {
+                        Self::compile(n, prog);
+                    }
+                }
+                let end = prog.len();
+                for j in jumps {
+                    prog[j] = Inst::Jump(end);
+                }
+            }
+            Node::Star(n) => {
+                let split = prog.len();
+                prog.push(Inst::Split(split + 1, 0));
+                Self::compile(n, prog);
+                prog.push(Inst::Jump(split));
+                let end = prog.len();
+                prog[split] = Inst::Split(split + 1, end);
+            }
+            Node::Plus(n) => {
+                let begin = prog.len();
+                Self::compile(n, prog);
+                let end = prog.len() + 1;
+                prog.push(Inst::Split(begin, end));
+            }
+            Node::Opt(n) => {
+                let split = prog.len();
+                prog.push(Inst::Split(split + 1, 0));
+                Self::compile(n, prog);
+                let end = prog.len();
+                prog[split] = Inst::Split(split + 1, end);
+            }
+        }
+    }
+
+    fn add_state(&self, states: &mut Vec<usize>, visited: &mut Vec<bool>, pc: usize) {
+        if visited[pc] {
+            return;
+        }
+        visited[pc] = true;
+        match self.prog[pc] {
+            Inst::Split(a, b) => {
+                self.add_state(states, visited, a);
+                self.add_state(states, visited, b);
+            }
+            Inst::Jump(a) => self.add_state(states, visited, a),
+            _ => states.push(pc),
+        }
+    }
+
+    //'events' are ordered from the eye to the light, as they are traced by a path tracer
+    pub fn matches(&self, events: &[Event]) -> bool {
+        let mut states = vec![];
+        let mut visited = vec![false; self.prog.len()];
+        self.add_state(&mut states, &mut visited, 0);
+        for event in events.iter().rev() {
+            let mut next_states = vec![];
+            let mut visited = vec![false; self.prog.len()];
+            for pc in states {
+                if let Inst::Match {
+                    ref symbols,
+                    negate,
+                } = self.prog[pc]
+                {
+                    if symbols.iter().any(|s| event.matches(*s)) != negate {
+                        self.add_state(&mut next_states, &mut visited, pc + 1);
+                    }
+                }
+            }
+            if next_states.is_empty() {
+                return false;
+            }
+            states = next_states;
+        }
+        states
+            .iter()
+            .any(|pc| matches!(self.prog[*pc], Inst::Accept))
+    }
+}
+
+//one RGB buffer per expression
+#[derive(Clone)]
+pub struct LpeBuffers {
+    exprs: Arc<Vec<Lpe>>,
+    buffers: Vec<RGB>,
+}
+
+impl LpeBuffers {
+    pub fn new(exprs: Vec<Lpe>) -> Self {
+        let buffers = vec![RGB::all(0.0); exprs.len()];
+        LpeBuffers {
+            exprs: Arc::new(exprs),
+            buffers,
+        }
+    }
+
+    pub fn exprs(&self) -> &[Lpe] {
+        &self.exprs[..]
+    }
+
+    pub fn buffers(&self) -> &[RGB] {
+        &self.buffers[..]
+    }
+}
+
+impl Accumulator for LpeBuffers {
+    //contributions without a path description cannot be classified
+    fn accum(&mut self, _color: &(RGB, usize)) {}
+
+    fn accum_path(&mut self, color: &RGB, events: &[Event]) {
+        for (expr, buffer) in self.exprs.iter().zip(self.buffers.iter_mut()) {
+            if expr.matches(events) {
+                *buffer += *color;
+            }
+        }
+    }
+
+    fn merge(&mut self, another: &Self) {
+        for (b, a) in self.buffers.iter_mut().zip(another.buffers.iter()) {
+            *b += *a;
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        self.buffers.iter().all(RGB::is_finite)
+    }
+
+    fn reset(&mut self) {
+        for b in self.buffers.iter_mut() {
+            b.reset()
+        }
+    }
+
+    fn scale(&mut self, a: f32) {
+        for b in self.buffers.iter_mut() {
+            b.scale(a)
+        }
+    }
+}
+
+#[test]
+fn test_matches() {
+    use Event::*;
+    let d = Scatter {
+        specular: false,
+        transmission: false,
+    };
+    let s = Scatter {
+        specular: true,
+        transmission: false,
+    };
+    let t = Scatter {
+        specular: true,
+        transmission: true,
+    };
+    let caustics = Lpe::parse("L S+ D E").unwrap();
+    assert!(caustics.matches(&[Eye, d, s, Light]));
+    assert!(caustics.matches(&[Eye, d, t, t, Light]));
+    assert!(!caustics.matches(&[Eye, d, Light]));
+    assert!(!caustics.matches(&[Eye, d, d, s, Light]));
+
+    let direct_diffuse = Lpe::parse("LDE").unwrap();
+    assert!(direct_diffuse.matches(&[Eye, d, Light]));
+    assert!(!direct_diffuse.matches(&[Eye, s, Light]));
+
+    let indirect_specular = Lpe::parse("L .+ S E").unwrap();
+    assert!(indirect_specular.matches(&[Eye, s, d, Light]));
+    assert!(indirect_specular.matches(&[Eye, s, t, Light]));
+    assert!(!indirect_specular.matches(&[Eye, s, Light]));
+    assert!(!indirect_specular.matches(&[Eye, d, s, Light]));
+
+    let non_specular = Lpe::parse("L [^S]* E").unwrap();
+    assert!(non_specular.matches(&[Eye, Light]));
+    assert!(non_specular.matches(&[Eye, d, d, Light]));
+    assert!(!non_specular.matches(&[Eye, d, t, Light]));
+
+    let alt = Lpe::parse("L (T | D)? E").unwrap();
+    assert!(alt.matches(&[Eye, Light]));
+    assert!(alt.matches(&[Eye, t, Light]));
+    assert!(!alt.matches(&[Eye, s, Light]));
+
+    assert!(Lpe::parse("L X E").is_err());
+    assert!(Lpe::parse("L (D E").is_err());
+}
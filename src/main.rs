@@ -44,6 +44,9 @@ struct ProgramOptions {
     time_limit: Option<OrInf<f64>>,
     integrator: Option<IntegratorType>,
     nthread_limit: Option<OrInf<usize>>,
+    lpes: Vec<(String, lpe::Lpe)>,
+    frames: Option<(usize, usize)>,
+    spectral: bool,
 }
 
 impl ProgramOptions {
@@ -87,10 +90,156 @@ impl ProgramOptions {
                     OrInf::Only(s.parse().expect(&format!("failed to parse number {}", s)))
                 }
             }),
+            lpes: matches
+                .opt_strs("lpe")
+                .iter()
+                .map(|s| {
+                    let mut split = s.splitn(2, '=');
+                    let name = split.next().unwrap().to_string();
+                    //the name goes into the output file name
+                    if name.is_empty()
+                        || !name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        panic!("lpe name {:?} has to be of A-Z, a-z, 0-9, _ and -", name);
+                    }
+                    let expr = split
+                        .next()
+                        .unwrap_or_else(|| panic!("expected NAME=EXPR but got {}", s));
+                    let lpe = lpe::Lpe::parse(expr)
+                        .unwrap_or_else(|e| panic!("failed to parse lpe {}: {}", expr, e));
+                    (name, lpe)
+                })
+                .collect(),
+            frames: matches.opt_str("frames").map(|s| {
+                let mut split = s.splitn(2, ':');
+                let mut frame = || {
+                    split
+                        .next()
+                        .and_then(|f| f.parse().ok())
+                        .unwrap_or_else(|| panic!("expected START:END but got {}", s))
+                };
+                let (start, end) = (frame(), frame());
+                if start > end {
+                    panic!("the first frame {} is after the last {}", start, end);
+                }
+                (start, end)
+            }),
+            spectral: matches.opt_present("spectral"),
         }
     }
 }
 
+type Pixel = (Vec<RGB>, (RGB, lpe::LpeBuffers));
+
+fn make_sched(
+    max_spp: OrInf<usize>,
+    time_limit: OrInf<f64>,
+    report_freq: f64,
+    nthread: usize,
+) -> Box<dyn FnMut(usize, usize) -> Option<usize> + Send> {
+    let start = std::time::Instant::now();
+    Box::new(move |next_cycle: usize, completed_samples: usize| {
+        if next_cycle <= 0 {
+            Some(1)
+        } else {
+            let elapsed = std::time::Instant::now().duration_since(start);
+            let ms = { elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64 };
+            let secs = (ms as f64) / 1000.0;
+            let progress = max_spp.map(|max_spp| completed_samples as f64 / max_spp as f64);
+            let eta = progress.map(|progress| secs * (1.0 - progress) / progress);
+            let spd = completed_samples as f64 / secs;
+            let spd_pc = spd / nthread as f64;
+            info!(
+                "{} / {} ({} %) elapsed {:.2} sec",
+                completed_samples,
+                max_spp
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or("Inf".into()),
+                progress
+                    .map(|x| format!("{:.2}", x * 100.0))
+                    .unwrap_or("N/A".into()),
+                secs,
+            );
+            info!(
+                "    ETA {} sec ({:?} for limit)",
+                eta.map(|x| format!("{:.2}", x)).unwrap_or("N/A".into()),
+                time_limit.map(|x| x - secs)
+            );
+
+            info!("    Speed {:.2} spp/sec {:.2} spp/sec/core", spd, spd_pc);
+            //let film = film.lock().unwrap();
+            //film.to_image(RGBPixel::average).write_exr(&format!("output/{}.exr", cycle));
+            if max_spp
+                .map(|max_spp| completed_samples >= max_spp)
+                .unwrap_or(false)
+            {
+                None
+            } else if time_limit.map(|lim| secs >= lim).unwrap_or(false) {
+                info!("stopping due to time limit");
+                None
+            } else {
+                let mut next_cycle_time = report_freq;
+                if let OrInf::Only(time_limit) = time_limit {
+                    next_cycle_time = next_cycle_time.min(time_limit - secs);
+                }
+                let next_report: usize = (next_cycle_time * spd) as usize;
+                if let OrInf::Only(max_spp) = max_spp {
+                    let rest = max_spp - completed_samples;
+                    Some(rest.min(next_report).max(1))
+                } else {
+                    Some(next_report.max(1))
+                }
+            }
+        }
+    })
+}
+
+//renders into a new film and writes {outdir}/{name}{suffix}.exr for each buffer
+#[allow(clippy::too_many_arguments)]
+fn render_frame<C: Send + Clone + camera::Camera + 'static>(
+    scene: Arc<scene::Scene>,
+    camera: &C,
+    v: &Pixel,
+    render_config: RenderConfig,
+    sched: Box<dyn FnMut(usize, usize) -> Option<usize> + Send>,
+    outdir: &str,
+    suffix: &str,
+    lpe_names: &[String],
+) {
+    let film = {
+        let s = 50;
+        image::Film::new(16 * s, 9 * s, v.clone()).into_arc()
+        //image::Film::new(20, 20, v.clone()).into_arc()
+    };
+
+    let film_config = FilmConfig {
+        film_arc: film.clone(),
+        accum_init: v.clone(),
+    };
+
+    let renderer = Renderer;
+    renderer
+        .render(scene, camera, film_config, render_config, sched)
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    film.with_lock(|film| {
+        for i in 0..v.0.len() {
+            film.to_image(|v| v.accum.0[i] / v.samples as f32)
+                .write_exr(&format!("{}/len{:>02}{}.exr", outdir, i, suffix));
+        }
+        film.to_image(|v| v.accum.1 .0 / v.samples as f32)
+            .write_exr(&format!("{}/total{}.exr", outdir, suffix));
+        for (i, name) in lpe_names.iter().enumerate() {
+            film.to_image(|v| v.accum.1 .1.buffers()[i] / v.samples as f32)
+                .write_exr(&format!("{}/lpe_{}{}.exr", outdir, name, suffix));
+        }
+    })
+    .unwrap();
+}
+
 fn main() -> Result<(), std::io::Error> {
     let env = env_logger::Env::new().default_filter_or("sabiptrace=info");
     env_logger::init_from_env(env);
@@ -103,6 +252,9 @@ fn main() -> Result<(), std::io::Error> {
     opts.optopt("s", "spp", "spp limit", "SEC");
     opts.optopt("i", "integrator", "show help", "pt|nee|bdpt");
     opts.optopt("", "nthreads", "maximum numer of threads", "N|inf");
+    opts.optmulti("", "lpe", "light path expression buffer", "NAME=EXPR");
+    opts.optopt("", "frames", "render the animation frames", "START:END");
+    opts.optflag("", "spectral", "render with sampled wavelengths");
     opts.optflag("h", "help", "show help");
 
     let matches = match opts.parse(&args[1..]) {
@@ -128,22 +280,12 @@ fn main() -> Result<(), std::io::Error> {
         .integrator
         .unwrap_or(IntegratorType::PathTraceWithNee);
     let nthread_limit = program_options.nthread_limit.unwrap_or(OrInf::Inf);
+    let (lpe_names, lpes): (Vec<_>, Vec<_>) = program_options.lpes.into_iter().unzip();
 
-    let v = (vec![RGB::all(0.0); 20], RGB::all(0.0));
-    let film = {
-        let s = 50;
-        image::Film::new(16 * s, 9 * s, v.clone()).into_arc()
-        //image::Film::new(20, 20, v.clone()).into_arc()
-    };
-
-    //let (camera, scene) = example_scenes::make_debug();
-    let (camera, scene) = example_scenes::make_box();
-    let scene = Arc::new(scene);
-
-    let film_config = FilmConfig {
-        film_arc: film.clone(),
-        accum_init: v.clone(),
-    };
+    let v: Pixel = (
+        vec![RGB::all(0.0); 20],
+        (RGB::all(0.0), lpe::LpeBuffers::new(lpes)),
+    );
 
     let render_config = RenderConfig {
         integrator,
@@ -151,87 +293,57 @@ fn main() -> Result<(), std::io::Error> {
             OrInf::Inf => num_cpus::get(),
             OrInf::Only(n) => num_cpus::get().min(n).max(1),
         },
+        spectral: program_options.spectral,
     };
 
     info!("outdir {}", outdir);
     info!("threads      :{:?}", render_config.nthread);
     info!("integrator   :{:?}", render_config.integrator);
+    info!("spectral     :{:?}", render_config.spectral);
     info!("max spp      :{:?}", max_spp);
     info!("time limit   :{:?}", time_limit);
     info!("report freq  :{:?}", report_freq);
-
-    let sched = {
-        let start = std::time::Instant::now();
-        let _film = film.clone();
-        Box::new(move |next_cycle: usize, completed_samples: usize| {
-            if next_cycle <= 0 {
-                Some(1)
-            } else {
-                let elapsed = std::time::Instant::now().duration_since(start);
-                let ms = { elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64 };
-                let secs = (ms as f64) / 1000.0;
-                let progress = max_spp.map(|max_spp| completed_samples as f64 / max_spp as f64);
-                let eta = progress.map(|progress| secs * (1.0 - progress) / progress);
-                let spd = completed_samples as f64 / secs;
-                let spd_pc = spd / render_config.nthread as f64;
-                info!(
-                    "{} / {} ({} %) elapsed {:.2} sec",
-                    completed_samples,
-                    max_spp
-                        .as_ref()
-                        .map(ToString::to_string)
-                        .unwrap_or("Inf".into()),
-                    progress
-                        .map(|x| format!("{:.2}", x * 100.0))
-                        .unwrap_or("N/A".into()),
-                    secs,
-                );
-                info!(
-                    "    ETA {} sec ({:?} for limit)",
-                    eta.map(|x| format!("{:.2}", x)).unwrap_or("N/A".into()),
-                    time_limit.map(|x| x - secs)
-                );
-
-                info!("    Speed {:.2} spp/sec {:.2} spp/sec/core", spd, spd_pc);
-                //let film = film.lock().unwrap();
-                //film.to_image(RGBPixel::average).write_exr(&format!("output/{}.exr", cycle));
-                if max_spp
-                    .map(|max_spp| completed_samples >= max_spp)
-                    .unwrap_or(false)
-                {
-                    None
-                } else if time_limit.map(|lim| secs >= lim).unwrap_or(false) {
-                    info!("stopping due to time limit");
-                    None
-                } else {
-                    let mut next_cycle_time = report_freq;
-                    if let OrInf::Only(time_limit) = time_limit {
-                        next_cycle_time = next_cycle_time.min(time_limit - secs);
-                    }
-                    let next_report: usize = (next_cycle_time * spd) as usize;
-                    if let OrInf::Only(max_spp) = max_spp {
-                        let rest = max_spp - completed_samples;
-                        Some(rest.min(next_report).max(1))
-                    } else {
-                        Some(next_report.max(1))
-                    }
-                }
-            }
-        })
-    };
-
-    let renderer = Renderer;
-    renderer.render(scene, &camera, film_config, render_config, sched);
+    for (name, lpe) in lpe_names.iter().zip(v.1 .1.exprs()) {
+        info!("lpe          :{} = {}", name, lpe.source());
+    }
 
     std::fs::create_dir_all(&outdir)?;
-    film.with_lock(|film| {
-        for i in 0..v.0.len() {
-            film.to_image(|v| v.accum.0[i] / v.samples as f32)
-                .write_exr(&format!("{}/len{:>02}.exr", outdir, i));
+    if let Some((start, end)) = program_options.frames {
+        let animation = example_scenes::make_box_animation();
+        let mut scene = None;
+        for frame in start..=end {
+            info!("frame        :{}", frame);
+            //the bvh is rebuilt only if objects or lights change
+            if scene.is_none() || !animation.is_scene_static() {
+                scene = Some(Arc::new(animation.scene_at(frame)));
+            }
+            let sched = make_sched(max_spp, time_limit, report_freq, render_config.nthread);
+            render_frame(
+                scene.clone().unwrap(),
+                &animation.camera_at(frame),
+                &v,
+                render_config,
+                sched,
+                &outdir,
+                &format!("_{:>04}", frame),
+                &lpe_names,
+            );
         }
-        film.to_image(|v| v.accum.1 / v.samples as f32)
-            .write_exr(&format!("{}/total.exr", outdir));
-    })
-    .unwrap();
+    } else {
+        //let (camera, scene) = example_scenes::make_debug();
+        let (camera, scene) = example_scenes::make_box();
+        let scene = Arc::new(scene);
+        let sched = make_sched(max_spp, time_limit, report_freq, render_config.nthread);
+        render_frame(
+            scene,
+            &camera,
+            &v,
+            render_config,
+            sched,
+            &outdir,
+            "",
+            &lpe_names,
+        );
+    }
     Ok(())
 }
@@ -9,11 +9,36 @@ pub enum Material {
     Lambert(materials::Lambert),
     Mirror(materials::Mirror),
     Transparent(materials::Transparent),
-    Mix(f32, Box<Material>, Box<Material>),
+    Coated(materials::Coated),
+    Principled(materials::Principled),
+    Measured(materials::Measured),
+    Subsurface(materials::Subsurface),
+    OrenNayar(materials::OrenNayar),
+    Retroreflective(materials::Retroreflective),
+    AnisotropicGgx(materials::AnisotropicGgx),
+    Ward(materials::Ward),
+    Hair(materials::Hair),
+    //the weight of the first material is in the red channel
+    Mix(texture::Texture, Box<Material>, Box<Material>),
 }
 use materials::MaterialImpl;
 
-impl_wrap_from_many! {Material, materials, [Lambert, Mirror, Transparent]}
+impl_wrap_from_many! {Material, materials, [Lambert, Mirror, Transparent, Coated, Principled, Measured, Subsurface, OrenNayar, Retroreflective, AnisotropicGgx, Ward, Hair]}
+
+//attenuation through the absorbing medium along the distance (Beer-Lambert law).
+//the distance may be infinite, through which the channels without absorption still pass.
+pub fn transmittance(medium: Option<&RGB>, dist: f32) -> RGB {
+    let tr = |a: f32| if a == 0.0 { 1.0 } else { (-a * dist).exp() };
+    match medium {
+        Some(a) => RGB::new(tr(a.r), tr(a.g), tr(a.b)),
+        None => RGB::all(1.0),
+    }
+}
+
+//textured weights are resolved at hits by at_uv
+fn mix_ratio(weight: &texture::Texture) -> f32 {
+    weight.at(&P2::origin()).r
+}
 
 use Material::*;
 impl Material {
@@ -22,11 +47,176 @@ impl Material {
     }
 
     pub fn new_mirror(color: RGB) -> Self {
-        Mirror(materials::Mirror(color))
+        Mirror(materials::Mirror::new(color))
     }
 
     pub fn mix(r: f32, m1: Self, m2: Self) -> Self {
-        Mix(r, Box::new(m1), Box::new(m2))
+        Self::mix_textured(RGB::all(r).into(), m1, m2)
+    }
+
+    pub fn mix_textured(weight: texture::Texture, m1: Self, m2: Self) -> Self {
+        Mix(weight, Box::new(m1), Box::new(m2))
+    }
+
+    //resolves the textured parameters at the texture coordinates
+    pub fn at_uv(&self, uv: &P2) -> Self {
+        match self {
+            Coated(m) => Coated(materials::Coated {
+                base: Box::new(m.base.at_uv(uv)),
+                ..m.clone()
+            }),
+            Mix(weight, m1, m2) => Self::mix(weight.at(uv).r, m1.at_uv(uv), m2.at_uv(uv)),
+            _ => self.clone(),
+        }
+    }
+
+    //resolves the parameters at the wavelengths, whose values are stored in the RGB slots
+    pub fn at_wavelengths(&self, wavelengths: &spectrum::Wavelengths) -> Self {
+        match self {
+            Lambert(m) => Self::new_lambert(wavelengths.uplift(&m.0)),
+            Mirror(m) => Mirror(materials::Mirror {
+                color: wavelengths.uplift(&m.color),
+                film: m.film.as_ref().map(|f| f.at_wavelengths(wavelengths)),
+            }),
+            Transparent(m) => Transparent(m.at_wavelengths(wavelengths)),
+            Coated(m) => Coated(m.at_wavelengths(wavelengths)),
+            Principled(m) => Principled(materials::Principled {
+                base_color: wavelengths.uplift(&m.base_color),
+                ..m.clone()
+            }),
+            Measured(m) => Measured(materials::Measured {
+                tint: wavelengths.uplift(&m.tint),
+                wavelengths: Some(*wavelengths),
+                ..m.clone()
+            }),
+            Subsurface(m) => Subsurface(m.at_wavelengths(wavelengths)),
+            OrenNayar(m) => OrenNayar(materials::OrenNayar {
+                color: wavelengths.uplift(&m.color),
+                ..m.clone()
+            }),
+            Retroreflective(m) => Retroreflective(materials::Retroreflective {
+                color: wavelengths.uplift(&m.color),
+                ..m.clone()
+            }),
+            AnisotropicGgx(m) => AnisotropicGgx(materials::AnisotropicGgx {
+                color: wavelengths.uplift(&m.color),
+                ..m.clone()
+            }),
+            Ward(m) => Ward(materials::Ward {
+                diffuse: wavelengths.uplift(&m.diffuse),
+                specular: wavelengths.uplift(&m.specular),
+                ..m.clone()
+            }),
+            Hair(m) => Hair(materials::Hair {
+                sigma_a: wavelengths.uplift(&m.sigma_a),
+                ..m.clone()
+            }),
+            Mix(weight, m1, m2) => Self::mix_textured(
+                weight.clone(),
+                m1.at_wavelengths(wavelengths),
+                m2.at_wavelengths(wavelengths),
+            ),
+        }
+    }
+
+    pub fn map_colors<F: Fn(&RGB) -> RGB>(&self, f: &F) -> Self {
+        match self {
+            Lambert(m) => Self::new_lambert(f(&m.0)),
+            Mirror(m) => Mirror(materials::Mirror {
+                color: f(&m.color),
+                ..m.clone()
+            }),
+            Transparent(m) => Transparent(materials::Transparent {
+                color: f(&m.color),
+                ..m.clone()
+            }),
+            Coated(m) => Coated(materials::Coated {
+                base: Box::new(m.base.map_colors(f)),
+                ..m.clone()
+            }),
+            Principled(m) => Principled(materials::Principled {
+                base_color: f(&m.base_color),
+                ..m.clone()
+            }),
+            Measured(m) => Measured(materials::Measured {
+                tint: f(&m.tint),
+                ..m.clone()
+            }),
+            //the medium is colored by the albedo, which is not a weight of the paths
+            Subsurface(m) => Subsurface(m.clone()),
+            OrenNayar(m) => OrenNayar(materials::OrenNayar {
+                color: f(&m.color),
+                ..m.clone()
+            }),
+            Retroreflective(m) => Retroreflective(materials::Retroreflective {
+                color: f(&m.color),
+                ..m.clone()
+            }),
+            AnisotropicGgx(m) => AnisotropicGgx(materials::AnisotropicGgx {
+                color: f(&m.color),
+                ..m.clone()
+            }),
+            Ward(m) => Ward(materials::Ward {
+                diffuse: f(&m.diffuse),
+                specular: f(&m.specular),
+                ..m.clone()
+            }),
+            //the color comes from the absorption, which is not a weight of the paths
+            Hair(m) => Hair(m.clone()),
+            Mix(weight, m1, m2) => {
+                Self::mix_textured(weight.clone(), m1.map_colors(f), m2.map_colors(f))
+            }
+        }
+    }
+
+    //whether the scattered direction depends on the wavelength
+    pub fn is_dispersive(&self) -> bool {
+        match self {
+            Transparent(m) => m.dispersion.is_some(),
+            Coated(m) => m.base.is_dispersive(),
+            Mix(_, m1, m2) => m1.is_dispersive() || m2.is_dispersive(),
+            _ => false,
+        }
+    }
+
+    //absorption coefficient inside the closed surface, whose normals face outward
+    pub fn interior_absorption(&self) -> Option<RGB> {
+        match self {
+            Transparent(m) if m.absorption.max() > 0.0 => Some(m.absorption),
+            Mix(_, m1, m2) => m1
+                .interior_absorption()
+                .or_else(|| m2.interior_absorption()),
+            _ => None,
+        }
+    }
+
+    //scattering medium inside the closed surface, which the path tracer walks
+    pub fn subsurface(&self) -> Option<&materials::Subsurface> {
+        match self {
+            Subsurface(m) => Some(m),
+            Mix(_, m1, m2) => m1.subsurface().or_else(|| m2.subsurface()),
+            _ => None,
+        }
+    }
+
+    //absorbing medium on the side of 'wto_local', given the one on the side of 'wfrom_local'.
+    //paths enter the interior by the transmission against the normal and leave it along the normal.
+    //media do not nest: entering one replaces the current medium and leaving any returns to
+    //vacuum, and paths start in vacuum even if the camera or the light is inside a closed surface.
+    //the boundaries are assumed closed, so paths escaping in a medium travel through it forever.
+    pub fn medium_toward(
+        &self,
+        wto_local: &V3,
+        wfrom_local: &V3,
+        medium: Option<RGB>,
+    ) -> Option<RGB> {
+        if wto_local[2] * wfrom_local[2] >= 0.0 {
+            medium
+        } else if wto_local[2] < 0.0 {
+            self.interior_absorption()
+        } else {
+            None
+        }
     }
 
     pub fn sample_win<R: ?Sized>(
@@ -41,9 +231,17 @@ impl Material {
             Lambert(m) => m.sample_win(wout_local, rng),
             Mirror(m) => m.sample_win(wout_local, rng),
             Transparent(m) => m.sample_win(wout_local, rng),
-            Mix(_, m1, m2) => {
-                use rand::distributions::Uniform;
-                let m = if Uniform::new(0.0, 1.0).sample(rng) < 0.5 {
+            Coated(m) => m.sample_win(wout_local, rng),
+            Principled(m) => m.sample_win(wout_local, rng),
+            Measured(m) => m.sample_win(wout_local, rng),
+            Subsurface(m) => m.sample_win(wout_local, rng),
+            OrenNayar(m) => m.sample_win(wout_local, rng),
+            Retroreflective(m) => m.sample_win(wout_local, rng),
+            AnisotropicGgx(m) => m.sample_win(wout_local, rng),
+            Ward(m) => m.sample_win(wout_local, rng),
+            Hair(m) => m.sample_win(wout_local, rng),
+            Mix(weight, m1, m2) => {
+                let m = if rng.gen::<f32>() < mix_ratio(weight) {
                     &m1
                 } else {
                     &m2
@@ -55,7 +253,7 @@ impl Material {
                 pdf::PdfSample {
                     value: (
                         win_local,
-                        self.bsdf(wout_local, &win_local, specular),
+                        self.bsdf(&win_local, wout_local, specular),
                         specular,
                     ),
                     pdf: self.sample_win_pdf(wout_local, &win_local, specular),
@@ -76,9 +274,17 @@ impl Material {
             Lambert(m) => m.sample_win_cos(wout_local, rng),
             Mirror(m) => m.sample_win_cos(wout_local, rng),
             Transparent(m) => m.sample_win_cos(wout_local, rng),
-            Mix(_, m1, m2) => {
-                use rand::distributions::Uniform;
-                let m = if Uniform::new(0.0, 1.0).sample(rng) < 0.5 {
+            Coated(m) => m.sample_win_cos(wout_local, rng),
+            Principled(m) => m.sample_win_cos(wout_local, rng),
+            Measured(m) => m.sample_win_cos(wout_local, rng),
+            Subsurface(m) => m.sample_win_cos(wout_local, rng),
+            OrenNayar(m) => m.sample_win_cos(wout_local, rng),
+            Retroreflective(m) => m.sample_win_cos(wout_local, rng),
+            AnisotropicGgx(m) => m.sample_win_cos(wout_local, rng),
+            Ward(m) => m.sample_win_cos(wout_local, rng),
+            Hair(m) => m.sample_win_cos(wout_local, rng),
+            Mix(weight, m1, m2) => {
+                let m = if rng.gen::<f32>() < mix_ratio(weight) {
                     &m1
                 } else {
                     &m2
@@ -104,10 +310,19 @@ impl Material {
             Lambert(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
             Mirror(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
             Transparent(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
-            Mix(_r, m1, m2) => {
-                (m1.sample_win_pdf(wout_local, win_local, specular_component)
-                    + m2.sample_win_pdf(wout_local, win_local, specular_component))
-                    / 2.0
+            Coated(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Principled(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Measured(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Subsurface(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            OrenNayar(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Retroreflective(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            AnisotropicGgx(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Ward(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Hair(m) => m.sample_win_pdf(wout_local, win_local, specular_component),
+            Mix(weight, m1, m2) => {
+                let r = mix_ratio(weight);
+                m1.sample_win_pdf(wout_local, win_local, specular_component) * r
+                    + m2.sample_win_pdf(wout_local, win_local, specular_component) * (1.0 - r)
             }
         }
     }
@@ -120,8 +335,18 @@ impl Material {
             Lambert(m) => m.bsdf(win_local, wout_local, specular_component),
             Mirror(m) => m.bsdf(win_local, wout_local, specular_component),
             Transparent(m) => m.bsdf(win_local, wout_local, specular_component),
-            Mix(r, m1, m2) => {
-                m1.bsdf(win_local, wout_local, specular_component) * *r
+            Coated(m) => m.bsdf(win_local, wout_local, specular_component),
+            Principled(m) => m.bsdf(win_local, wout_local, specular_component),
+            Measured(m) => m.bsdf(win_local, wout_local, specular_component),
+            Subsurface(m) => m.bsdf(win_local, wout_local, specular_component),
+            OrenNayar(m) => m.bsdf(win_local, wout_local, specular_component),
+            Retroreflective(m) => m.bsdf(win_local, wout_local, specular_component),
+            AnisotropicGgx(m) => m.bsdf(win_local, wout_local, specular_component),
+            Ward(m) => m.bsdf(win_local, wout_local, specular_component),
+            Hair(m) => m.bsdf(win_local, wout_local, specular_component),
+            Mix(weight, m1, m2) => {
+                let r = mix_ratio(weight);
+                m1.bsdf(win_local, wout_local, specular_component) * r
                     + m2.bsdf(win_local, wout_local, specular_component) * (1.0 - r)
             }
         }
@@ -132,8 +357,18 @@ impl Material {
             Lambert(m) => m.bsdf_cos(win_local, wout_local, specular_component),
             Mirror(m) => m.bsdf_cos(win_local, wout_local, specular_component),
             Transparent(m) => m.bsdf_cos(win_local, wout_local, specular_component),
-            Mix(r, m1, m2) => {
-                m1.bsdf_cos(win_local, wout_local, specular_component) * *r
+            Coated(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Principled(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Measured(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Subsurface(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            OrenNayar(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Retroreflective(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            AnisotropicGgx(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Ward(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Hair(m) => m.bsdf_cos(win_local, wout_local, specular_component),
+            Mix(weight, m1, m2) => {
+                let r = mix_ratio(weight);
+                m1.bsdf_cos(win_local, wout_local, specular_component) * r
                     + m2.bsdf_cos(win_local, wout_local, specular_component) * (1.0 - r)
             }
         }
@@ -144,6 +379,15 @@ impl Material {
             Lambert(m) => m.all_specular(),
             Mirror(m) => m.all_specular(),
             Transparent(m) => m.all_specular(),
+            Coated(m) => m.all_specular(),
+            Principled(m) => m.all_specular(),
+            Measured(m) => m.all_specular(),
+            Subsurface(m) => m.all_specular(),
+            OrenNayar(m) => m.all_specular(),
+            Retroreflective(m) => m.all_specular(),
+            AnisotropicGgx(m) => m.all_specular(),
+            Ward(m) => m.all_specular(),
+            Hair(m) => m.all_specular(),
             Mix(_, m1, m2) => m1.all_specular() && m2.all_specular(),
         }
     }
@@ -153,6 +397,15 @@ impl Material {
             Lambert(m) => m.has_specular(),
             Mirror(m) => m.has_specular(),
             Transparent(m) => m.has_specular(),
+            Coated(m) => m.has_specular(),
+            Principled(m) => m.has_specular(),
+            Measured(m) => m.has_specular(),
+            Subsurface(m) => m.has_specular(),
+            OrenNayar(m) => m.has_specular(),
+            Retroreflective(m) => m.has_specular(),
+            AnisotropicGgx(m) => m.has_specular(),
+            Ward(m) => m.has_specular(),
+            Hair(m) => m.has_specular(),
             Mix(_, m1, m2) => m1.has_specular() || m2.has_specular(),
         }
     }
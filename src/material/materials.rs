@@ -34,6 +34,31 @@ pub trait MaterialImpl {
     fn has_specular(&self) -> bool;
 }
 
+//weights of the channels for choosing between lobes
+fn luminance(c: &RGB) -> f32 {
+    0.3 * c.r + 0.6 * c.g + 0.1 * c.b
+}
+
+//the albedo toward 'wout' by the sampling and by the uniform integration of the bsdf over the
+//sphere. specular components are only in the sampled one.
+#[cfg(test)]
+fn sampled_albedo<M: MaterialImpl>(m: &M, wout: &V3, n: usize) -> (RGB, RGB) {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let uniform = pdf::UniformUnitHemisphere {
+        normal: V3::z(),
+        xvec: V3::x(),
+    };
+    let (mut sampled, mut integrated) = (RGB::all(0.0), RGB::all(0.0));
+    for _ in 0..n {
+        let s = m.sample_win_cos(wout, &mut rng);
+        sampled += s.value.1 / s.pdf;
+        let s = uniform.sample(&mut rng);
+        let win = if rng.gen() { s.value } else { -s.value };
+        integrated += m.bsdf_cos(&win, wout, false) / (s.pdf / 2.0);
+    }
+    (sampled / n as f32, integrated / n as f32)
+}
+
 mod lambert;
 pub use lambert::*;
 
@@ -42,3 +67,36 @@ pub use mirror::*;
 
 mod transparent;
 pub use transparent::*;
+
+mod coated;
+pub use coated::*;
+
+mod microfacet;
+pub use microfacet::*;
+
+mod principled;
+pub use principled::*;
+
+mod measured;
+pub use measured::*;
+
+mod subsurface;
+pub use subsurface::*;
+
+mod thin_film;
+pub use thin_film::*;
+
+mod oren_nayar;
+pub use oren_nayar::*;
+
+mod retroreflective;
+pub use retroreflective::*;
+
+mod anisotropic_ggx;
+pub use anisotropic_ggx::*;
+
+mod ward;
+pub use ward::*;
+
+mod hair;
+pub use hair::*;
@@ -0,0 +1,104 @@
+use super::microfacet::*;
+use crate::material::*;
+use crate::pdf::*;
+
+//rough conductor whose roughness differs along the tangent (x) and the bitangent (y),
+//like brushed metal. the color is the reflectance at the normal incidence.
+#[derive(Clone, Debug)]
+pub struct AnisotropicGgx {
+    pub color: RGB,
+    pub roughness_x: f32,
+    pub roughness_y: f32,
+}
+
+impl AnisotropicGgx {
+    pub fn new(color: RGB, roughness_x: f32, roughness_y: f32) -> Self {
+        AnisotropicGgx {
+            color,
+            roughness_x,
+            roughness_y,
+        }
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::new_anisotropic(self.roughness_x, self.roughness_y)
+    }
+}
+
+//the directions flipped to the upper side, lit from the side of 'wout'
+fn upper(win_local: &V3, wout_local: &V3) -> (V3, V3) {
+    if wout_local[2] < 0.0 {
+        (-win_local, -wout_local)
+    } else {
+        (*win_local, *wout_local)
+    }
+}
+
+impl MaterialImpl for AnisotropicGgx {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        let sgn = if wout_local[2] < 0.0 { -1.0 } else { 1.0 };
+        let h = sgn * self.ggx().sample_h(rng);
+        let win_local = reflect(wout_local, &h);
+        if win_local[2] * wout_local[2] <= 0.0 {
+            return PdfSample {
+                value: (win_local, RGB::all(0.0), false),
+                pdf: 1.0,
+            };
+        }
+        PdfSample {
+            value: (win_local, self.bsdf(&win_local, wout_local, false), false),
+            pdf: self.sample_win_pdf(wout_local, &win_local, false),
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return 0.0;
+        }
+        let (wi, wo) = upper(win_local, wout_local);
+        let h = (wi + wo).normalize();
+        self.ggx().pdf_h(&h) / (4.0 * wo.dot(&h).abs())
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return RGB::all(0.0);
+        }
+        let (wi, wo) = upper(win_local, wout_local);
+        let h = (wi + wo).normalize();
+        let ggx = self.ggx();
+        schlick(&self.color, wo.dot(&h)) * (ggx.d(&h) * ggx.g(&wi, &wo) / (4.0 * wi[2] * wo[2]))
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_anisotropic_ggx() {
+    let m = AnisotropicGgx::new(RGB::all(0.9), 0.2, 0.6);
+    let wout = V3::new(0.3, 0.4, 0.866);
+    let (sampled, integrated) = super::sampled_albedo(&m, &wout, 200_000);
+    let (sampled, integrated) = (sampled.r, integrated.r);
+    assert!(
+        (sampled - integrated).abs() < 0.03,
+        "{} {}",
+        sampled,
+        integrated
+    );
+    assert!(sampled < 1.0);
+    //the highlight stretches along the rougher bitangent
+    let (wout, tilt) = (V3::z(), 0.3);
+    let along_x = V3::new(tilt, 0.0, 1.0).normalize();
+    let along_y = V3::new(0.0, tilt, 1.0).normalize();
+    assert!(m.bsdf(&along_y, &wout, false).r > 2.0 * m.bsdf(&along_x, &wout, false).r);
+}
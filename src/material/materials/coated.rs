@@ -0,0 +1,189 @@
+use crate::material::*;
+
+//smooth dielectric layer over a base material. the light scattered by the base is attenuated by
+//the transmission through the interface and the absorption in the layer.
+//the interreflections inside the layer are ignored.
+#[derive(Clone, Debug)]
+pub struct Coated {
+    pub base: Box<Material>,
+    pub index: f32,
+    //absorption coefficient of the layer per unit length
+    pub absorption: RGB,
+    pub thickness: f32,
+}
+
+impl Coated {
+    pub fn new(base: Material, index: f32) -> Self {
+        Coated {
+            base: Box::new(base),
+            index,
+            absorption: RGB::all(0.0),
+            thickness: 0.0,
+        }
+    }
+
+    pub fn set_absorption(mut self, absorption: RGB, thickness: f32) -> Self {
+        self.absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    pub fn at_wavelengths(&self, wavelengths: &spectrum::Wavelengths) -> Self {
+        Coated {
+            base: Box::new(self.base.at_wavelengths(wavelengths)),
+            absorption: wavelengths.uplift(&self.absorption),
+            ..self.clone()
+        }
+    }
+
+    //reflectance of the interface for the direction outside
+    fn fresnel(&self, w: &V3) -> f32 {
+        let cos = w[2].abs().min(1.0);
+        let sin_inside = (1.0 - cos * cos).sqrt() / self.index;
+        let cos_inside = (1.0 - sin_inside * sin_inside).sqrt();
+        super::Transparent::fresnel_reflection(1.0, cos, self.index, cos_inside)
+    }
+
+    //the direction in the layer refracted from the one outside, on the same side
+    fn to_inside(&self, w: &V3) -> V3 {
+        let (x, y) = (w[0] / self.index, w[1] / self.index);
+        V3::new(x, y, w[2].signum() * (1.0 - x * x - y * y).max(0.0).sqrt())
+    }
+
+    //None if totally reflected inside
+    fn to_outside(&self, w: &V3) -> Option<V3> {
+        let (x, y) = (w[0] * self.index, w[1] * self.index);
+        let sq_cos = 1.0 - x * x - y * y;
+        if sq_cos <= 0.0 {
+            None
+        } else {
+            Some(V3::new(x, y, w[2].signum() * sq_cos.sqrt()))
+        }
+    }
+
+    //transmission into and out of the layer for the directions outside
+    fn transmission(&self, win_local: &V3, wout_local: &V3) -> RGB {
+        let (win_inside, wout_inside) = (self.to_inside(win_local), self.to_inside(wout_local));
+        let length = self.thickness * (1.0 / win_inside[2].abs() + 1.0 / wout_inside[2].abs());
+        let a = &self.absorption;
+        let attenuation = RGB::new(
+            (-a.r * length).exp(),
+            (-a.g * length).exp(),
+            (-a.b * length).exp(),
+        );
+        attenuation * (1.0 - self.fresnel(win_local)) * (1.0 - self.fresnel(wout_local))
+    }
+}
+
+impl MaterialImpl for Coated {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB, bool)> {
+        self.sample_win_cos(wout_local, rng)
+            .map(|(win_local, bsdf, spec)| (win_local, bsdf / win_local[2].abs(), spec))
+    }
+
+    //reflects at the interface in proportion to the reflectance, or samples the base
+    fn sample_win_cos<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB, bool)> {
+        let (win_local, specular) = if rng.gen::<f32>() < self.fresnel(wout_local) {
+            (V3::new(-wout_local[0], -wout_local[1], wout_local[2]), true)
+        } else {
+            let base = self.base.sample_win_cos(&self.to_inside(wout_local), rng);
+            let (win_inside, _, specular) = base.value;
+            match self.to_outside(&win_inside) {
+                Some(win_local) => (win_local, specular),
+                None => {
+                    return pdf::PdfSample {
+                        value: (win_inside, RGB::all(0.0), specular),
+                        pdf: 1.0,
+                    }
+                }
+            }
+        };
+        pdf::PdfSample {
+            value: (
+                win_local,
+                self.bsdf_cos(&win_local, wout_local, specular),
+                specular,
+            ),
+            pdf: self.sample_win_pdf(wout_local, &win_local, specular),
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        let f = self.fresnel(wout_local);
+        let (win_inside, wout_inside) = (self.to_inside(win_local), self.to_inside(wout_local));
+        let base = self
+            .base
+            .sample_win_pdf(&wout_inside, &win_inside, specular_component);
+        if specular_component {
+            let top = if win_local[2] * wout_local[2] > 0.0 {
+                f
+            } else {
+                0.0
+            };
+            top + (1.0 - f) * base
+        } else {
+            //solid angles shrink by the square of the index in the layer
+            let jacobian = win_local[2].abs() / win_inside[2].abs() / (self.index * self.index);
+            (1.0 - f) * base * jacobian
+        }
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component {
+            self.bsdf_cos(win_local, wout_local, true) / win_local[2].abs()
+        } else {
+            let (win_inside, wout_inside) = (self.to_inside(win_local), self.to_inside(wout_local));
+            self.base.bsdf(&win_inside, &wout_inside, false)
+                * self.transmission(win_local, wout_local)
+                / (self.index * self.index)
+        }
+    }
+
+    fn bsdf_cos(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component {
+            let (win_inside, wout_inside) = (self.to_inside(win_local), self.to_inside(wout_local));
+            let top = if win_local[2] * wout_local[2] > 0.0 {
+                self.fresnel(wout_local)
+            } else {
+                0.0
+            };
+            self.base.bsdf_cos(&win_inside, &wout_inside, true)
+                * self.transmission(win_local, wout_local)
+                + RGB::all(top)
+        } else {
+            self.bsdf(win_local, wout_local, false) * win_local[2].abs()
+        }
+    }
+
+    fn all_specular(&self) -> bool {
+        self.base.all_specular()
+    }
+
+    fn has_specular(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_coated_sampling() {
+    let coated =
+        Coated::new(Material::new_lambert(RGB::all(0.8)), 1.5).set_absorption(RGB::all(0.5), 0.2);
+    let wout = V3::new(0.6, 0.0, 0.8);
+    let (sampled, integrated) = super::sampled_albedo(&coated, &wout, 100_000);
+    let (sampled, integrated) = (sampled.r, coated.fresnel(&wout) + integrated.r);
+    assert!(
+        (sampled - integrated).abs() < 0.01,
+        "{} {}",
+        sampled,
+        integrated
+    );
+    assert!(coated.fresnel(&wout) < sampled && sampled < 0.8);
+}
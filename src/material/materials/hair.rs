@@ -0,0 +1,392 @@
+use crate::material::*;
+use crate::pdf::*;
+use std::f32::consts::PI;
+
+//lobes of the paths through the fiber: R, TT, TRT and the rest
+const P_MAX: usize = 3;
+
+//gauss-legendre nodes and weights on [-1, 1], over which the offset across the fiber is integrated
+const OFFSETS: [(f32, f32); 8] = [
+    (-0.960_289_9, 0.101_228_54),
+    (-0.796_666_5, 0.222_381_03),
+    (-0.525_532_4, 0.313_706_65),
+    (-0.183_434_6, 0.362_683_8),
+    (0.183_434_6, 0.362_683_8),
+    (0.525_532_4, 0.313_706_65),
+    (0.796_666_5, 0.222_381_03),
+    (0.960_289_9, 0.101_228_54),
+];
+
+//dielectric fiber of absorbing cuticle scales (Chiang et al. 2016), averaged over the offsets
+//across the fiber to depend only on the directions (d'Eon et al. 2011).
+//the fiber runs along the tangent (x). 'sigma_a' is the absorption per the radius,
+//'beta_m' and 'beta_n' the longitudinal and azimuthal roughness in [0, 1]
+//and 'alpha' the tilt of the scales in degrees.
+#[derive(Clone, Debug)]
+pub struct Hair {
+    pub sigma_a: RGB,
+    pub eta: f32,
+    pub beta_m: f32,
+    pub beta_n: f32,
+    pub alpha: f32,
+}
+
+//variances, logistic scale and the tilts of the lobes derived from the roughness
+struct Lobes {
+    v: [f32; P_MAX + 1],
+    s: f32,
+    sin_2k_alpha: [f32; 3],
+    cos_2k_alpha: [f32; 3],
+}
+
+//the fiber seen from the outgoing direction at an offset
+struct Fiber {
+    sin_o: f32,
+    cos_o: f32,
+    phi_o: f32,
+    gamma_o: f32,
+    gamma_t: f32,
+    ap: [RGB; P_MAX + 1],
+}
+
+fn safe_sqrt(x: f32) -> f32 {
+    x.max(0.0).sqrt()
+}
+
+fn i0(x: f32) -> f32 {
+    let (mut val, mut x2i, mut ifact, mut i4) = (0.0, 1.0, 1.0, 1.0);
+    for i in 0..10 {
+        if i > 1 {
+            ifact *= i as f32;
+        }
+        val += x2i / (i4 * ifact * ifact);
+        x2i *= x * x;
+        i4 *= 4.0;
+    }
+    val
+}
+
+fn log_i0(x: f32) -> f32 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+//longitudinal scattering
+fn mp(cos_i: f32, cos_o: f32, sin_i: f32, sin_o: f32, v: f32) -> f32 {
+    let a = cos_i * cos_o / v;
+    let b = sin_i * sin_o / v;
+    if v <= 0.1 {
+        (log_i0(a) - b - 1.0 / v + std::f32::consts::LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+fn logistic(x: f32, s: f32) -> f32 {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: f32, s: f32) -> f32 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f32, s: f32) -> f32 {
+    logistic(x, s) / (logistic_cdf(PI, s) - logistic_cdf(-PI, s))
+}
+
+fn sample_trimmed_logistic(u: f32, s: f32) -> f32 {
+    let k = logistic_cdf(PI, s) - logistic_cdf(-PI, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(-PI, s)) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
+
+//azimuth of the exit of the lobe
+fn exit_phi(p: usize, gamma_o: f32, gamma_t: f32) -> f32 {
+    2.0 * p as f32 * gamma_t - 2.0 * gamma_o + p as f32 * PI
+}
+
+//azimuthal scattering
+fn np(phi: f32, p: usize, s: f32, gamma_o: f32, gamma_t: f32) -> f32 {
+    let dphi = (phi - exit_phi(p, gamma_o, gamma_t) + PI).rem_euclid(2.0 * PI) - PI;
+    trimmed_logistic(dphi, s)
+}
+
+fn fresnel(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin_t = safe_sqrt(1.0 - cos_i * cos_i) / eta;
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    super::Transparent::fresnel_reflection(1.0, cos_i, eta, safe_sqrt(1.0 - sin_t * sin_t))
+}
+
+impl Hair {
+    pub fn new(sigma_a: RGB, beta_m: f32, beta_n: f32) -> Self {
+        Hair {
+            sigma_a,
+            eta: 1.55,
+            beta_m,
+            beta_n,
+            alpha: 2.0,
+        }
+    }
+
+    //absorption by the concentrations of eumelanin, from blond to black, and pheomelanin, red
+    pub fn from_melanin(eumelanin: f32, pheomelanin: f32, beta_m: f32, beta_n: f32) -> Self {
+        let eu = RGB::new(0.419, 0.697, 1.37);
+        let pheo = RGB::new(0.187, 0.4, 1.05);
+        Self::new(eu * eumelanin + pheo * pheomelanin, beta_m, beta_n)
+    }
+
+    //absorption making the multiply scattered color of the hair roughly 'color'
+    pub fn from_color(color: RGB, beta_m: f32, beta_n: f32) -> Self {
+        let b = beta_n;
+        let denom = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3)
+            + 5.574 * b.powi(4)
+            + 0.245 * b.powi(5);
+        let sigma = |c: f32| (c.max(1e-4).ln() / denom).powi(2);
+        Self::new(
+            RGB::new(sigma(color.r), sigma(color.g), sigma(color.b)),
+            beta_m,
+            beta_n,
+        )
+    }
+
+    fn lobes(&self) -> Lobes {
+        let bm = self.beta_m;
+        let v0 = (0.726 * bm + 0.812 * bm * bm + 3.7 * bm.powi(20)).powi(2);
+        let bn = self.beta_n;
+        let s = (PI / 8.0).sqrt() * (0.265 * bn + 1.194 * bn * bn + 5.372 * bn.powi(22));
+        let mut sin_2k_alpha = [self.alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0] * sin_2k_alpha[0]), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        Lobes {
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    fn fiber(&self, h: f32, wout_local: &V3) -> Fiber {
+        let sin_o = wout_local[0].clamp(-1.0, 1.0);
+        let cos_o = safe_sqrt(1.0 - sin_o * sin_o);
+        let sin_t = sin_o / self.eta;
+        let cos_t = safe_sqrt(1.0 - sin_t * sin_t);
+        //the index for the projection onto the plane perpendicular to the fiber (Bravais)
+        let etap = safe_sqrt(self.eta * self.eta - sin_o * sin_o) / cos_o.max(1e-6);
+        let sin_gamma_t = (h / etap).clamp(-1.0, 1.0);
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let len = 2.0 * cos_gamma_t / cos_t;
+        let t = RGB::new(
+            (-self.sigma_a.r * len).exp(),
+            (-self.sigma_a.g * len).exp(),
+            (-self.sigma_a.b * len).exp(),
+        );
+
+        let f = fresnel(cos_o * safe_sqrt(1.0 - h * h), self.eta);
+        let mut ap = [
+            RGB::all(f),
+            t * (1.0 - f) * (1.0 - f),
+            RGB::all(0.0),
+            RGB::all(0.0),
+        ];
+        ap[2] = ap[1] * t * f;
+        ap[3] = ap[2] * t * f / (RGB::all(1.0) - t * f);
+        Fiber {
+            sin_o,
+            cos_o,
+            phi_o: wout_local[2].atan2(wout_local[1]),
+            gamma_o: h.clamp(-1.0, 1.0).asin(),
+            gamma_t: sin_gamma_t.asin(),
+            ap,
+        }
+    }
+
+    //the outgoing inclination tilted by the scales for the lobe
+    fn tilted(lobes: &Lobes, p: usize, sin_o: f32, cos_o: f32) -> (f32, f32) {
+        let (sa, ca) = (&lobes.sin_2k_alpha, &lobes.cos_2k_alpha);
+        let (sin_op, cos_op) = match p {
+            0 => (sin_o * ca[1] - cos_o * sa[1], cos_o * ca[1] + sin_o * sa[1]),
+            1 => (sin_o * ca[0] + cos_o * sa[0], cos_o * ca[0] - sin_o * sa[0]),
+            2 => (sin_o * ca[2] + cos_o * sa[2], cos_o * ca[2] - sin_o * sa[2]),
+            _ => (sin_o, cos_o),
+        };
+        (sin_op, cos_op.abs())
+    }
+
+    //probabilities to sample the lobes
+    fn lobe_pdfs(fiber: &Fiber) -> [f32; P_MAX + 1] {
+        let mean = |c: &RGB| (c.r + c.g + c.b) / 3.0;
+        let total: f32 = fiber.ap.iter().map(mean).sum();
+        let mut pdfs = [0.0; P_MAX + 1];
+        for (pdf, ap) in pdfs.iter_mut().zip(fiber.ap.iter()) {
+            *pdf = mean(ap) / total;
+        }
+        pdfs
+    }
+
+    //sum over the lobes of their weights times the scattering toward 'win_local'
+    fn sum_lobes<T, F>(lobes: &Lobes, fiber: &Fiber, win_local: &V3, weight: F) -> T
+    where
+        T: std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+        F: Fn(usize) -> T,
+    {
+        let sin_i = win_local[0].clamp(-1.0, 1.0);
+        let cos_i = safe_sqrt(1.0 - sin_i * sin_i);
+        let phi = win_local[2].atan2(win_local[1]) - fiber.phi_o;
+        let rest = mp(cos_i, fiber.cos_o, sin_i, fiber.sin_o, lobes.v[P_MAX]) / (2.0 * PI);
+        (0..P_MAX).fold(weight(P_MAX) * rest, |acc, p| {
+            let (sin_op, cos_op) = Self::tilted(lobes, p, fiber.sin_o, fiber.cos_o);
+            let m = mp(cos_i, cos_op, sin_i, sin_op, lobes.v[p]);
+            acc + weight(p) * (m * np(phi, p, lobes.s, fiber.gamma_o, fiber.gamma_t))
+        })
+    }
+
+    fn sample_at<R: Rng + ?Sized>(
+        &self,
+        lobes: &Lobes,
+        h: f32,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> V3 {
+        let fiber = self.fiber(h, wout_local);
+        let pdfs = Self::lobe_pdfs(&fiber);
+        let mut u = rng.gen::<f32>();
+        let mut p = 0;
+        while p < P_MAX && u >= pdfs[p] {
+            u -= pdfs[p];
+            p += 1;
+        }
+
+        let (sin_op, cos_op) = Self::tilted(lobes, p, fiber.sin_o, fiber.cos_o);
+        let v = lobes.v[p];
+        let u1 = rng.gen::<f32>().max(1e-5);
+        let cos_theta = 1.0 + v * (u1 + (1.0 - u1) * (-2.0 / v).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * rng.gen::<f32>()).cos();
+        let sin_i = (-cos_theta * sin_op + sin_theta * cos_phi * cos_op).clamp(-1.0, 1.0);
+        let cos_i = safe_sqrt(1.0 - sin_i * sin_i);
+
+        let dphi = if p < P_MAX {
+            exit_phi(p, fiber.gamma_o, fiber.gamma_t) + sample_trimmed_logistic(rng.gen(), lobes.s)
+        } else {
+            2.0 * PI * rng.gen::<f32>()
+        };
+        let phi_i = fiber.phi_o + dphi;
+        V3::new(sin_i, cos_i * phi_i.cos(), cos_i * phi_i.sin())
+    }
+}
+
+impl MaterialImpl for Hair {
+    //an offset is chosen by its weight and the direction is sampled as for the offset
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        self.sample_win_cos(wout_local, rng)
+            .map(|(win_local, bsdf_cos, spec)| {
+                let cos = win_local[2].abs();
+                let bsdf = if cos > 0.0 {
+                    bsdf_cos / cos
+                } else {
+                    RGB::all(0.0)
+                };
+                (win_local, bsdf, spec)
+            })
+    }
+
+    fn sample_win_cos<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        let mut u = rng.gen::<f32>() * 2.0;
+        let mut k = 0;
+        while k + 1 < OFFSETS.len() && u >= OFFSETS[k].1 {
+            u -= OFFSETS[k].1;
+            k += 1;
+        }
+        let win_local = self.sample_at(&self.lobes(), OFFSETS[k].0, wout_local, rng);
+        PdfSample {
+            value: (
+                win_local,
+                self.bsdf_cos(&win_local, wout_local, false),
+                false,
+            ),
+            pdf: self.sample_win_pdf(wout_local, &win_local, false),
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component {
+            return 0.0;
+        }
+        let lobes = self.lobes();
+        OFFSETS
+            .iter()
+            .map(|(h, w)| {
+                let fiber = self.fiber(*h, wout_local);
+                let pdfs = Self::lobe_pdfs(&fiber);
+                0.5 * w * Self::sum_lobes(&lobes, &fiber, win_local, |p| pdfs[p])
+            })
+            .sum()
+    }
+
+    //the scattering is given per the solid angle, so the cosine of the shading frame is divided out
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        let cos = win_local[2].abs();
+        if cos == 0.0 {
+            return RGB::all(0.0);
+        }
+        self.bsdf_cos(win_local, wout_local, specular_component) / cos
+    }
+
+    fn bsdf_cos(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component {
+            return RGB::all(0.0);
+        }
+        let lobes = self.lobes();
+        OFFSETS.iter().fold(RGB::all(0.0), |acc, (h, w)| {
+            let fiber = self.fiber(*h, wout_local);
+            acc + Self::sum_lobes(&lobes, &fiber, win_local, |p| fiber.ap[p]) * (0.5 * w)
+        })
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_hair() {
+    let wout = V3::new(0.4, -0.3, 0.866).normalize();
+    let integrate = |m: &Hair| {
+        let (sampled, integrated) = super::sampled_albedo(m, &wout, 200_000);
+        (sampled.g, integrated.g)
+    };
+    //no energy is lost in a transparent fiber
+    let (sampled, integrated) = integrate(&Hair::new(RGB::all(0.0), 0.3, 0.3));
+    assert!((sampled - 1.0).abs() < 0.02, "{}", sampled);
+    assert!((integrated - 1.0).abs() < 0.03, "{}", integrated);
+    let (sampled, integrated) = integrate(&Hair::from_melanin(0.8, 0.0, 0.25, 0.3));
+    assert!(
+        (sampled - integrated).abs() < 0.03,
+        "{} {}",
+        sampled,
+        integrated
+    );
+    assert!(sampled < 0.9);
+}
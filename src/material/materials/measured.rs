@@ -0,0 +1,401 @@
+//measured isotropic brdfs from the MERL database (Matusik et al. 2003) or tabulated data,
+//both parametrized by the half and difference angles (Rusinkiewicz 1998)
+use crate::material::*;
+use crate::pdf::*;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::sync::Arc;
+
+//resolution of the outgoing elevations and the incoming cells in the sampling tables
+const SAMPLING_THETA_OUT: usize = 16;
+const SAMPLING_THETA: usize = 32;
+const SAMPLING_PHI: usize = 64;
+//chance to sample the cosine instead, covering the peaks missed by the coarse tables
+const COS_CHANCE: f32 = 0.1;
+
+//the samples are at i / n of the ranges [0, pi/2) of theta_h and theta_d, and [0, pi) of phi_d
+pub struct BrdfTable {
+    //resolutions in theta_h, theta_d and phi_d. phi_d is the fastest in the values.
+    dims: [usize; 3],
+    //theta_h is sampled on the square root of the range as in the MERL database
+    sqrt_theta_h: bool,
+    values: Vec<RGB>,
+    //distributions of the incoming cells for the outgoing elevations
+    sampling: Vec<Option<Discrete>>,
+}
+
+impl std::fmt::Debug for BrdfTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let [n_th, n_td, n_pd] = self.dims;
+        write!(f, "BrdfTable({}x{}x{})", n_th, n_td, n_pd)
+    }
+}
+
+//theta_h, theta_d and phi_d of the directions in the upper hemisphere
+fn half_diff(win: &V3, wout: &V3) -> (f32, f32, f32) {
+    let h = (win + wout).normalize();
+    let theta_h = h[2].min(1.0).acos();
+    //win rotated by -phi_h around the normal and -theta_h around the binormal
+    let (sin_p, cos_p) = h[1].atan2(h[0]).sin_cos();
+    let (sin_t, cos_t) = theta_h.sin_cos();
+    let x = win[0] * cos_p + win[1] * sin_p;
+    let y = win[1] * cos_p - win[0] * sin_p;
+    let (dx, dz) = (x * cos_t - win[2] * sin_t, x * sin_t + win[2] * cos_t);
+    (theta_h, dz.clamp(-1.0, 1.0).acos(), y.atan2(dx))
+}
+
+//the neighbouring samples of the continuous index and their weights
+fn neighbours(x: f32, n: usize, periodic: bool) -> [(usize, f32); 2] {
+    if periodic {
+        let i = x.floor();
+        let t = x - i;
+        let i = (i as i64).rem_euclid(n as i64) as usize;
+        [(i, 1.0 - t), ((i + 1) % n, t)]
+    } else {
+        let x = x.clamp(0.0, (n - 1) as f32);
+        let i = x.floor() as usize;
+        [
+            (i, 1.0 - (x - i as f32)),
+            ((i + 1).min(n - 1), x - i as f32),
+        ]
+    }
+}
+
+//bounds of theta and phi of the incoming cell. phi is relative to the outgoing direction.
+fn cell_bounds(cell: usize) -> (f32, f32, f32, f32) {
+    let (i, j) = (cell / SAMPLING_PHI, cell % SAMPLING_PHI);
+    let d_theta = FRAC_PI_2 / SAMPLING_THETA as f32;
+    let d_phi = 2.0 * PI / SAMPLING_PHI as f32;
+    (
+        i as f32 * d_theta,
+        (i + 1) as f32 * d_theta,
+        j as f32 * d_phi,
+        (j + 1) as f32 * d_phi,
+    )
+}
+
+fn cell_solid_angle(cell: usize) -> f32 {
+    let (theta0, theta1, phi0, phi1) = cell_bounds(cell);
+    (theta0.cos() - theta1.cos()) * (phi1 - phi0)
+}
+
+fn sampling_bin(wout: &V3) -> usize {
+    let theta = wout[2].clamp(-1.0, 1.0).acos();
+    ((theta / FRAC_PI_2 * SAMPLING_THETA_OUT as f32) as usize).min(SAMPLING_THETA_OUT - 1)
+}
+
+impl BrdfTable {
+    pub fn new(dims: [usize; 3], sqrt_theta_h: bool, values: Vec<RGB>) -> Result<Self, String> {
+        if dims.iter().any(|n| *n == 0) || values.len() != dims.iter().product::<usize>() {
+            return Err(format!(
+                "{} values for the resolution {:?}",
+                values.len(),
+                dims
+            ));
+        }
+        let mut table = BrdfTable {
+            dims,
+            sqrt_theta_h,
+            values,
+            sampling: vec![],
+        };
+        table.sampling = (0..SAMPLING_THETA_OUT)
+            .map(|k| table.sampling_distribution(k))
+            .collect();
+        Ok(table)
+    }
+
+    //the binary format of the MERL database: the resolutions as 32-bit integers, then the
+    //values of the red, green and blue channels as doubles
+    pub fn parse_merl(bytes: &[u8]) -> Result<Self, String> {
+        let int = |i: usize| {
+            let mut b = [0; 4];
+            b.copy_from_slice(&bytes[4 * i..4 * i + 4]);
+            i32::from_le_bytes(b)
+        };
+        if bytes.len() < 12 || (0..3).any(|i| int(i) <= 0) {
+            return Err("invalid header".into());
+        }
+        //the header gives the samples of phi_d on disk, which cover [0, pi) (90 90 180 in MERL)
+        let dims = [int(0) as usize, int(1) as usize, int(2) as usize];
+        //the resolutions come from the file, so the sizes may overflow
+        let n = dims
+            .iter()
+            .try_fold(1usize, |n, d| n.checked_mul(*d))
+            .ok_or("too many samples")?;
+        let size = n
+            .checked_mul(3 * 8)
+            .and_then(|s| s.checked_add(12))
+            .ok_or("too many samples")?;
+        if bytes.len() != size {
+            return Err("wrong size of data".into());
+        }
+        let value = |channel: usize, i: usize| {
+            let offset = 12 + 8 * (channel * n + i);
+            let mut b = [0; 8];
+            b.copy_from_slice(&bytes[offset..offset + 8]);
+            //missing samples are negative
+            (f64::from_le_bytes(b) as f32).max(0.0)
+        };
+        let values = (0..n)
+            .map(|i| {
+                RGB::new(
+                    value(0, i) / 1500.0,
+                    value(1, i) * 1.15 / 1500.0,
+                    value(2, i) * 1.66 / 1500.0,
+                )
+            })
+            .collect();
+        Self::new(dims, true, values)
+    }
+
+    //text with the resolutions in theta_h, theta_d and phi_d, then the rgb values with theta_h
+    //sampled linearly. lines starting with # are comments.
+    pub fn parse_tabulated(text: &str) -> Result<Self, String> {
+        let numbers = text
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace)
+            .map(|t| t.parse::<f32>().map_err(|e| format!("'{}': {}", t, e)))
+            .collect::<Result<Vec<f32>, String>>()?;
+        if numbers.len() < 3 {
+            return Err("missing resolutions".into());
+        }
+        let (dims, values) = numbers.split_at(3);
+        let dims = [dims[0] as usize, dims[1] as usize, dims[2] as usize];
+        let values = values
+            .chunks(3)
+            .map(|c| match c {
+                [r, g, b] => Ok(RGB::new(*r, *g, *b)),
+                _ => Err("incomplete color".to_string()),
+            })
+            .collect::<Result<Vec<RGB>, String>>()?;
+        Self::new(dims, false, values)
+    }
+
+    //trilinearly interpolated for the directions in the upper hemisphere
+    pub fn at(&self, win: &V3, wout: &V3) -> RGB {
+        let (theta_h, theta_d, phi_d) = half_diff(win, wout);
+        let [n_th, n_td, n_pd] = self.dims;
+        let th = if self.sqrt_theta_h {
+            (theta_h / FRAC_PI_2).sqrt()
+        } else {
+            theta_h / FRAC_PI_2
+        };
+        //by the reciprocity phi_d is periodic in pi
+        let th = neighbours(th * n_th as f32, n_th, false);
+        let td = neighbours(theta_d / FRAC_PI_2 * n_td as f32, n_td, false);
+        let pd = neighbours(phi_d / PI * n_pd as f32, n_pd, true);
+        let mut value = RGB::all(0.0);
+        for (i, wi) in th.iter() {
+            for (j, wj) in td.iter() {
+                for (k, wk) in pd.iter() {
+                    value += self.values[(i * n_td + j) * n_pd + k] * (wi * wj * wk);
+                }
+            }
+        }
+        value
+    }
+
+    //the cells weighted by the brdf and the cosine at their centers
+    fn sampling_distribution(&self, bin: usize) -> Option<Discrete> {
+        let theta_out = (bin as f32 + 0.5) / SAMPLING_THETA_OUT as f32 * FRAC_PI_2;
+        let wout = V3::new(theta_out.sin(), 0.0, theta_out.cos());
+        let weights: Vec<f32> = (0..SAMPLING_THETA * SAMPLING_PHI)
+            .map(|cell| {
+                let (theta0, theta1, phi0, phi1) = cell_bounds(cell);
+                let (theta, phi) = ((theta0 + theta1) / 2.0, (phi0 + phi1) / 2.0);
+                let win = V3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                self.at(&win, &wout).max() * win[2] * cell_solid_angle(cell)
+            })
+            .collect();
+        Discrete::new(&weights)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Measured {
+    pub table: Arc<BrdfTable>,
+    //scales the measured values. the colors of the material are mapped through it.
+    pub tint: RGB,
+    //the measured values are uplifted to the wavelengths when set
+    pub wavelengths: Option<spectrum::Wavelengths>,
+}
+
+impl Measured {
+    pub fn new(table: BrdfTable) -> Self {
+        Measured {
+            table: Arc::new(table),
+            tint: RGB::all(1.0),
+            wavelengths: None,
+        }
+    }
+
+    //reads a MERL .binary file, or a tabulated text file otherwise
+    pub fn load(file: &str) -> Result<Self, String> {
+        let table = if file.to_lowercase().ends_with(".binary") {
+            let bytes = std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+            BrdfTable::parse_merl(&bytes)
+        } else {
+            let text = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+            BrdfTable::parse_tabulated(&text)
+        };
+        table.map(Self::new).map_err(|e| format!("{}: {}", file, e))
+    }
+
+    pub fn set_tint(mut self, tint: RGB) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    //the directions mirrored to the upper hemisphere, as both sides reflect the same
+    fn oriented(win_local: &V3, wout_local: &V3) -> (V3, V3) {
+        let sgn = wout_local[2].signum();
+        let flip = |w: &V3| V3::new(w[0], w[1], w[2] * sgn);
+        (flip(win_local), flip(wout_local))
+    }
+}
+
+impl MaterialImpl for Measured {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB, bool)> {
+        self.sample_win_cos(wout_local, rng)
+            .map(|(win_local, bsdf, spec)| (win_local, bsdf / win_local[2].abs(), spec))
+    }
+
+    fn sample_win_cos<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB, bool)> {
+        let (_, wo) = Self::oriented(&V3::z(), wout_local);
+        let table = &self.table.sampling[sampling_bin(&wo)];
+        let wi = match table {
+            Some(table) if rng.gen::<f32>() >= COS_CHANCE => {
+                //uniform in the solid angle of the cell
+                let (theta0, theta1, phi0, phi1) = cell_bounds(table.sample(rng).value);
+                let (cos0, cos1) = (theta0.cos(), theta1.cos());
+                let cos = cos1 + (cos0 - cos1) * rng.gen::<f32>();
+                let sin = (1.0 - cos * cos).max(0.0).sqrt();
+                let phi = wo[1].atan2(wo[0]) + phi0 + (phi1 - phi0) * rng.gen::<f32>();
+                V3::new(sin * phi.cos(), sin * phi.sin(), cos)
+            }
+            _ => CosUnitHemisphere::from_normal(&V3::z()).sample(rng).value,
+        };
+        let win_local = V3::new(wi[0], wi[1], wi[2] * wout_local[2].signum());
+        let pdf = self.sample_win_pdf(wout_local, &win_local, false);
+        if pdf > 0.0 {
+            PdfSample {
+                value: (
+                    win_local,
+                    self.bsdf_cos(&win_local, wout_local, false),
+                    false,
+                ),
+                pdf,
+            }
+        } else {
+            PdfSample {
+                value: (win_local, RGB::all(0.0), false),
+                pdf: 1.0,
+            }
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return 0.0;
+        }
+        let (wi, wo) = Self::oriented(win_local, wout_local);
+        let cos_pdf = wi[2] * std::f32::consts::FRAC_1_PI;
+        match &self.table.sampling[sampling_bin(&wo)] {
+            Some(table) => {
+                let theta = wi[2].min(1.0).acos();
+                let phi = (wi[1].atan2(wi[0]) - wo[1].atan2(wo[0])).rem_euclid(2.0 * PI);
+                let i =
+                    ((theta / FRAC_PI_2 * SAMPLING_THETA as f32) as usize).min(SAMPLING_THETA - 1);
+                let j = ((phi / (2.0 * PI) * SAMPLING_PHI as f32) as usize).min(SAMPLING_PHI - 1);
+                let cell = i * SAMPLING_PHI + j;
+                COS_CHANCE * cos_pdf
+                    + (1.0 - COS_CHANCE) * table.pdf(&cell) / cell_solid_angle(cell)
+            }
+            None => cos_pdf,
+        }
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return RGB::all(0.0);
+        }
+        let (wi, wo) = Self::oriented(win_local, wout_local);
+        let value = self.table.at(&wi, &wo);
+        match &self.wavelengths {
+            Some(wavelengths) => wavelengths.uplift(&value) * self.tint,
+            None => value * self.tint,
+        }
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+fn glossy_table() -> BrdfTable {
+    //a lobe around the mirror direction over a diffuse part
+    let dims = [16, 16, 32];
+    let values = (0..dims.iter().product::<usize>())
+        .map(|i| {
+            let theta_h = (i / (dims[1] * dims[2])) as f32 / dims[0] as f32 * FRAC_PI_2;
+            RGB::new(0.1, 0.2, 0.3) + RGB::all(4.0 * (-30.0 * theta_h * theta_h).exp())
+        })
+        .collect();
+    BrdfTable::new(dims, false, values).unwrap()
+}
+
+#[test]
+fn test_measured_sampling() {
+    let m = Measured::new(glossy_table());
+    for wout in [V3::new(0.6, 0.0, 0.8), V3::new(0.0, 0.8, -0.6)].iter() {
+        let (sampled, integrated) = super::sampled_albedo(&m, wout, 200_000);
+        assert!(
+            (sampled.r - integrated.r).abs() < 0.02,
+            "{} {}",
+            sampled.r,
+            integrated.r
+        );
+    }
+}
+
+#[test]
+fn test_merl_binary() {
+    let (n_th, n_td, n_pd) = (4, 4, 8);
+    let n = n_th * n_td * n_pd;
+    let mut bytes = vec![];
+    for d in [n_th, n_td, n_pd].iter() {
+        bytes.extend_from_slice(&(*d as i32).to_le_bytes());
+    }
+    for _ in 0..3 * n {
+        bytes.extend_from_slice(&1500.0f64.to_le_bytes());
+    }
+    let table = BrdfTable::parse_merl(&bytes).unwrap();
+    let value = table.at(&V3::new(0.6, 0.0, 0.8), &V3::new(0.0, 0.6, 0.8));
+    assert!((value.r - 1.0).abs() < 1e-5 && (value.b - 1.66).abs() < 1e-5);
+    assert!(BrdfTable::parse_merl(&bytes[..bytes.len() - 8]).is_err());
+    assert!(BrdfTable::parse_merl(&bytes[..8]).is_err());
+    //resolutions whose sizes overflow
+    let mut huge = bytes.clone();
+    for i in 0..3 {
+        huge[4 * i..4 * i + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+    }
+    assert!(BrdfTable::parse_merl(&huge).is_err());
+}
@@ -0,0 +1,119 @@
+use crate::*;
+use rand::prelude::*;
+
+//GGX (Trowbridge-Reitz) distribution of the microfacet normals in the local frame
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl Ggx {
+    //alpha is the square of the roughness, bounded to keep the distribution finite
+    pub fn new(roughness: f32) -> Self {
+        Self::new_anisotropic(roughness, roughness)
+    }
+
+    pub fn new_anisotropic(roughness_x: f32, roughness_y: f32) -> Self {
+        Ggx {
+            alpha_x: (roughness_x * roughness_x).max(1e-3),
+            alpha_y: (roughness_y * roughness_y).max(1e-3),
+        }
+    }
+
+    pub fn d(&self, h: &V3) -> f32 {
+        if h[2] <= 0.0 {
+            return 0.0;
+        }
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        let t = (h[0] / ax).powi(2) + (h[1] / ay).powi(2) + h[2] * h[2];
+        std::f32::consts::FRAC_1_PI / (ax * ay * t * t)
+    }
+
+    fn lambda(&self, w: &V3) -> f32 {
+        let sq_tan_alpha =
+            ((w[0] * self.alpha_x).powi(2) + (w[1] * self.alpha_y).powi(2)) / (w[2] * w[2]);
+        ((1.0 + sq_tan_alpha).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &V3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    //height-correlated masking and shadowing
+    pub fn g(&self, win: &V3, wout: &V3) -> f32 {
+        1.0 / (1.0 + self.lambda(win) + self.lambda(wout))
+    }
+
+    //normals distributed by D(h) cos(h), from the slopes of the unit distribution stretched by alpha
+    pub fn sample_h<R: Rng + ?Sized>(&self, rng: &mut R) -> V3 {
+        let u1: f32 = rng.gen();
+        let u2: f32 = rng.gen();
+        let r = (u1 / (1.0 - u1)).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u2;
+        V3::new(
+            -self.alpha_x * r * phi.cos(),
+            -self.alpha_y * r * phi.sin(),
+            1.0,
+        )
+        .normalize()
+    }
+
+    pub fn pdf_h(&self, h: &V3) -> f32 {
+        self.d(h) * h[2].abs()
+    }
+}
+
+pub fn reflect(w: &V3, h: &V3) -> V3 {
+    2.0 * w.dot(h) * h - w
+}
+
+//the direction refracted from 'w' (pointing away) through the interface with normal 'h'.
+//'eta' is the ratio of the index on the side of 'w' to the one on the other side.
+pub fn refract(w: &V3, h: &V3, eta: f32) -> Option<V3> {
+    let cos = w.dot(h);
+    let sq_sin_t = eta * eta * (1.0 - cos * cos).max(0.0);
+    if sq_sin_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sq_sin_t).sqrt();
+    Some((-w * eta + h * (eta * cos - cos.signum() * cos_t)).normalize())
+}
+
+pub fn schlick(f0: &RGB, cos: f32) -> RGB {
+    let m = (1.0 - cos.abs()).max(0.0).powi(5);
+    *f0 * (1.0 - m) + RGB::all(m)
+}
+
+//reflectance for light at the cosine 'cos' to the normal on the side of 'eta_1'
+pub fn fresnel_dielectric(cos: f32, eta_1: f32, eta_2: f32) -> f32 {
+    let cos = cos.abs().min(1.0);
+    let sin_t = eta_1 / eta_2 * (1.0 - cos * cos).sqrt();
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).sqrt();
+    super::Transparent::fresnel_reflection(eta_1, cos, eta_2, cos_t)
+}
+
+#[test]
+fn test_ggx_sampling() {
+    //D(h) cos(h) integrates to one, and the sampled normals follow it
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+    let hemisphere = pdf::UniformUnitHemisphere {
+        normal: V3::z(),
+        xvec: V3::x(),
+    };
+    for ggx in [Ggx::new(0.5), Ggx::new_anisotropic(0.3, 0.8)].iter() {
+        let n = 200_000;
+        let (mut area, mut mean_x) = (0.0, 0.0);
+        for _ in 0..n {
+            let s = hemisphere.sample(&mut rng);
+            area += ggx.pdf_h(&s.value) / s.pdf;
+            mean_x += s.value[0].abs() * ggx.pdf_h(&s.value) / s.pdf;
+        }
+        assert!((area / n as f32 - 1.0).abs() < 0.05);
+        let sampled_x = (0..n).map(|_| ggx.sample_h(&mut rng)[0].abs()).sum::<f32>();
+        assert!((sampled_x - mean_x).abs() / (n as f32) < 0.01);
+    }
+}
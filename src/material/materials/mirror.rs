@@ -1,7 +1,30 @@
+use super::{Substrate, ThinFilm};
 use crate::material::*;
 
 #[derive(Clone, Debug)]
-pub struct Mirror(pub RGB);
+pub struct Mirror {
+    pub color: RGB,
+    pub film: Option<ThinFilm>,
+}
+
+impl Mirror {
+    //an uncoated mirror
+    pub fn new(color: RGB) -> Self {
+        Mirror { color, film: None }
+    }
+
+    pub fn set_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    fn reflectance(&self, wout_local: &V3) -> RGB {
+        match &self.film {
+            Some(film) => film.reflectance(wout_local[2], &Substrate::Reflector(self.color)),
+            None => self.color,
+        }
+    }
+}
 
 impl MaterialImpl for Mirror {
     fn sample_win<R: ?Sized>(&self, wout_local: &V3, rng: &mut R) -> pdf::PdfSample<(V3, RGB, bool)>
@@ -24,7 +47,7 @@ impl MaterialImpl for Mirror {
         dir[0] *= -1.0;
         dir[1] *= -1.0;
         pdf::PdfSample {
-            value: (dir.normalize(), self.0, true),
+            value: (dir.normalize(), self.reflectance(wout_local), true),
             pdf: 1.0,
         }
     }
@@ -45,9 +68,9 @@ impl MaterialImpl for Mirror {
         }
     }
 
-    fn bsdf_cos(&self, _win_local: &V3, _wout_local: &V3, specular_component: bool) -> RGB {
+    fn bsdf_cos(&self, _win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
         if specular_component {
-            self.0
+            self.reflectance(wout_local)
         } else {
             RGB::all(0.0)
         }
@@ -0,0 +1,95 @@
+use crate::material::*;
+use crate::pdf::*;
+
+//rough diffuse surface of v-shaped facets with the slopes of the standard deviation 'sigma' in
+//radians (Oren and Nayar 1994, the qualitative model)
+#[derive(Clone, Debug)]
+pub struct OrenNayar {
+    pub color: RGB,
+    pub sigma: f32,
+}
+
+impl OrenNayar {
+    pub fn new(color: RGB, sigma: f32) -> Self {
+        OrenNayar { color, sigma }
+    }
+}
+
+impl MaterialImpl for OrenNayar {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        let sgn = if wout_local[2] > 0.0 { 1.0 } else { -1.0 };
+        let next_dir = CosUnitHemisphere {
+            normal: sgn * V3::z(),
+            xvec: V3::x(),
+        }
+        .sample(rng);
+        PdfSample {
+            value: (
+                next_dir.value,
+                self.bsdf(&next_dir.value, wout_local, false),
+                false,
+            ),
+            pdf: next_dir.pdf,
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            0.0
+        } else {
+            win_local[2].abs() * std::f32::consts::FRAC_1_PI
+        }
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return RGB::all(0.0);
+        }
+        let sq_sigma = self.sigma * self.sigma;
+        let a = 1.0 - 0.5 * sq_sigma / (sq_sigma + 0.33);
+        let b = 0.45 * sq_sigma / (sq_sigma + 0.09);
+        //cos(phi_in - phi_out) sin(alpha) tan(beta), where alpha is the larger angle to the normal
+        let cos_max = win_local[2].abs().max(wout_local[2].abs());
+        let tangential = win_local[0] * wout_local[0] + win_local[1] * wout_local[1];
+        let term = tangential.max(0.0) / cos_max.max(1e-4);
+        self.color * ((a + b * term) * std::f32::consts::FRAC_1_PI)
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_oren_nayar() {
+    use rand::rngs::SmallRng;
+    let mut rng = SmallRng::seed_from_u64(0);
+    let smooth = OrenNayar::new(RGB::all(0.8), 0.0);
+    let rough = OrenNayar::new(RGB::all(0.8), 0.5);
+    let (win, wout) = (V3::new(0.6, 0.0, 0.8), V3::new(0.0, -0.8, -0.6));
+    //lambertian without the roughness
+    assert!((smooth.bsdf(&win, &-wout, false).r - 0.8 / std::f32::consts::PI).abs() < 1e-6);
+    assert_eq!(smooth.bsdf(&win, &wout, false).r, 0.0);
+    //rough surfaces look flatter, darker at the normal and brighter back toward grazing light
+    let wout = V3::new(0.8, 0.0, 0.6);
+    let albedo = |m: &OrenNayar, rng: &mut SmallRng| {
+        (0..10_000)
+            .map(|_| {
+                let s = m.sample_win_cos(&wout, rng);
+                s.value.1.r / s.pdf
+            })
+            .sum::<f32>()
+            / 10_000.0
+    };
+    assert!(albedo(&rough, &mut rng) < albedo(&smooth, &mut rng));
+    let grazing = V3::new(0.9, 0.0, 0.436);
+    assert!(rough.bsdf(&grazing, &wout, false).r > smooth.bsdf(&grazing, &wout, false).r);
+}
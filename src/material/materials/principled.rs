@@ -0,0 +1,358 @@
+use super::luminance;
+use super::microfacet::*;
+use crate::material::*;
+
+//multi-lobe material in the parametrization of Burley (2012, 2015)
+#[derive(Clone, Debug)]
+pub struct Principled {
+    pub base_color: RGB,
+    pub metallic: f32,
+    pub roughness: f32,
+    //stretches the highlights along the tangent, up to the aspect of 10
+    pub anisotropic: f32,
+    //dielectrics reflect 0.08 specular at the normal incidence
+    pub specular: f32,
+    pub specular_tint: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+    pub transmission: f32,
+    pub index: f32,
+}
+
+fn lerp(a: &RGB, b: &RGB, t: f32) -> RGB {
+    *a * (1.0 - t) + *b * t
+}
+
+impl Principled {
+    pub fn new(base_color: RGB) -> Self {
+        Principled {
+            base_color,
+            metallic: 0.0,
+            roughness: 0.5,
+            anisotropic: 0.0,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            index: 1.5,
+        }
+    }
+
+    //parameters exported by dcc tools, as named by Blender, glTF and Autodesk Standard Surface
+    pub fn set_param(mut self, name: &str, values: &[f32]) -> Result<Self, String> {
+        let key = name
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        let scalar = || {
+            values
+                .first()
+                .copied()
+                .ok_or(format!("{}: expected a value", name))
+        };
+        let color = || match values {
+            [r, g, b, ..] => Ok(RGB::new(*r, *g, *b)),
+            _ => Err(format!("{}: expected a color", name)),
+        };
+        match key.as_str() {
+            "basecolor" | "basecolorfactor" => self.base_color = color()?,
+            "metallic" | "metalness" | "metallicfactor" => self.metallic = scalar()?,
+            "roughness" | "roughnessfactor" | "specularroughness" => self.roughness = scalar()?,
+            "anisotropic" | "anisotropystrength" | "specularanisotropy" => {
+                self.anisotropic = scalar()?
+            }
+            "specular" => self.specular = scalar()?,
+            "speculartint" => self.specular_tint = scalar()?,
+            "ior" | "specularior" => {
+                self.index = scalar()?;
+                let f0 = ((self.index - 1.0) / (self.index + 1.0)).powi(2);
+                self.specular = f0 / 0.08;
+            }
+            "sheen" | "sheenweight" => self.sheen = scalar()?,
+            "sheentint" => self.sheen_tint = scalar()?,
+            "clearcoat" | "clearcoatfactor" | "coat" | "coatweight" => self.clearcoat = scalar()?,
+            "clearcoatgloss" => self.clearcoat_gloss = scalar()?,
+            "clearcoatroughness" | "clearcoatroughnessfactor" | "coatroughness" => {
+                self.clearcoat_gloss = 1.0 - scalar()?
+            }
+            "transmission" | "transmissionfactor" | "transmissionweight" => {
+                self.transmission = scalar()?
+            }
+            _ => return Err(format!("{}: unknown parameter", name)),
+        }
+        Ok(self)
+    }
+
+    fn ggx(&self) -> Ggx {
+        let aspect = (1.0 - 0.9 * self.anisotropic.clamp(0.0, 1.0)).sqrt();
+        Ggx::new_anisotropic(
+            self.roughness / aspect.sqrt(),
+            self.roughness * aspect.sqrt(),
+        )
+    }
+
+    fn clearcoat_ggx(&self) -> Ggx {
+        let alpha = 0.1 * (1.0 - self.clearcoat_gloss) + 0.001 * self.clearcoat_gloss;
+        Ggx::new(alpha.sqrt())
+    }
+
+    //weights of the diffuse, specular, clearcoat and transmission lobes
+    fn lobes(&self) -> [f32; 4] {
+        let transmission = (1.0 - self.metallic) * self.transmission;
+        let diffuse = (1.0 - self.metallic) * (1.0 - self.transmission);
+        [
+            diffuse,
+            1.0 - transmission,
+            0.25 * self.clearcoat,
+            transmission,
+        ]
+    }
+
+    //probabilities to sample the lobes
+    fn lobe_chances(&self) -> [f32; 4] {
+        let [d, s, c, t] = self.lobes();
+        let d = d * luminance(&self.base_color).max(0.1);
+        let sum = d + s + c + t;
+        [d / sum, s / sum, c / sum, t / sum]
+    }
+
+    //reflectance of the specular lobe at the normal incidence
+    fn specular_f0(&self) -> RGB {
+        let lum = luminance(&self.base_color);
+        let tint = if lum > 0.0 {
+            self.base_color / lum
+        } else {
+            RGB::all(1.0)
+        };
+        let dielectric = lerp(&RGB::all(1.0), &tint, self.specular_tint) * (0.08 * self.specular);
+        lerp(&dielectric, &self.base_color, self.metallic)
+    }
+
+    //the directions mirrored to have 'wout' above the surface, and the indices on their sides
+    fn oriented(&self, win_local: &V3, wout_local: &V3) -> (V3, V3, f32, f32) {
+        if wout_local[2] >= 0.0 {
+            (*win_local, *wout_local, self.index, 1.0)
+        } else {
+            let flip = |w: &V3| V3::new(w[0], w[1], -w[2]);
+            (flip(win_local), flip(wout_local), 1.0, self.index)
+        }
+    }
+
+    //normal of the microfacet refracting between the directions
+    fn transmission_h(wi: &V3, wo: &V3, eta_i: f32, eta_o: f32) -> Option<V3> {
+        let h = -(wo * eta_o + wi * eta_i);
+        if h.norm_squared() == 0.0 {
+            return None;
+        }
+        //the facet faces the side of 'wout'
+        let h = h.normalize() * h[2].signum();
+        if wo.dot(&h) <= 0.0 || wi.dot(&h) >= 0.0 {
+            None
+        } else {
+            Some(h)
+        }
+    }
+}
+
+impl MaterialImpl for Principled {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB, bool)> {
+        self.sample_win_cos(wout_local, rng)
+            .map(|(win_local, bsdf, spec)| (win_local, bsdf / win_local[2].abs(), spec))
+    }
+
+    fn sample_win_cos<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB, bool)> {
+        let (_, wo, eta_i, eta_o) = self.oriented(&V3::z(), wout_local);
+        let chances = self.lobe_chances();
+        let mut u = rng.gen::<f32>();
+        let mut lobe = 0;
+        while lobe < 3 && u >= chances[lobe] {
+            u -= chances[lobe];
+            lobe += 1;
+        }
+        let wi = match lobe {
+            0 => {
+                let cos_hemisphere = pdf::CosUnitHemisphere {
+                    normal: V3::z(),
+                    xvec: V3::x(),
+                };
+                Some(cos_hemisphere.sample(rng).value)
+            }
+            1 | 2 => {
+                let ggx = if lobe == 1 {
+                    self.ggx()
+                } else {
+                    self.clearcoat_ggx()
+                };
+                let h = ggx.sample_h(rng);
+                Some(reflect(&wo, &h)).filter(|wi| wo.dot(&h) > 0.0 && wi[2] > 0.0)
+            }
+            _ => {
+                let h = self.ggx().sample_h(rng);
+                let f = fresnel_dielectric(wo.dot(&h), eta_o, eta_i);
+                //the directions ending on the wrong side are not accounted for by the pdf
+                if wo.dot(&h) <= 0.0 {
+                    None
+                } else if rng.gen::<f32>() < f {
+                    Some(reflect(&wo, &h)).filter(|wi| wi[2] > 0.0)
+                } else {
+                    refract(&wo, &h, eta_o / eta_i).filter(|wi| wi[2] < 0.0)
+                }
+            }
+        };
+        let win_local = wi.map(|wi| V3::new(wi[0], wi[1], wi[2] * wout_local[2].signum()));
+        let pdf = win_local.map_or(0.0, |w| self.sample_win_pdf(wout_local, &w, false));
+        match win_local {
+            Some(win_local) if pdf > 0.0 => pdf::PdfSample {
+                value: (
+                    win_local,
+                    self.bsdf_cos(&win_local, wout_local, false),
+                    false,
+                ),
+                pdf,
+            },
+            _ => pdf::PdfSample {
+                value: (V3::z(), RGB::all(0.0), false),
+                pdf: 1.0,
+            },
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component {
+            return 0.0;
+        }
+        let (wi, wo, eta_i, eta_o) = self.oriented(win_local, wout_local);
+        let [c_diffuse, c_specular, c_clearcoat, c_transmission] = self.lobe_chances();
+        if wi[2] > 0.0 {
+            let h = (wi + wo).normalize();
+            let jacobian = 1.0 / (4.0 * wo.dot(&h).abs());
+            let f = fresnel_dielectric(wo.dot(&h), eta_o, eta_i);
+            c_diffuse * wi[2] * std::f32::consts::FRAC_1_PI
+                + (c_specular + c_transmission * f) * self.ggx().pdf_h(&h) * jacobian
+                + c_clearcoat * self.clearcoat_ggx().pdf_h(&h) * jacobian
+        } else {
+            match Self::transmission_h(&wi, &wo, eta_i, eta_o) {
+                Some(h) => {
+                    let f = fresnel_dielectric(wo.dot(&h), eta_o, eta_i);
+                    let denom = (eta_o * wo.dot(&h) + eta_i * wi.dot(&h)).powi(2);
+                    let jacobian = eta_i * eta_i * wi.dot(&h).abs() / denom;
+                    c_transmission * (1.0 - f) * self.ggx().pdf_h(&h) * jacobian
+                }
+                None => 0.0,
+            }
+        }
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component {
+            return RGB::all(0.0);
+        }
+        let (wi, wo, eta_i, eta_o) = self.oriented(win_local, wout_local);
+        let [w_diffuse, w_specular, w_clearcoat, w_transmission] = self.lobes();
+        if wi[2] == 0.0 || wo[2] == 0.0 {
+            return RGB::all(0.0);
+        }
+        if wi[2] > 0.0 {
+            let h = (wi + wo).normalize();
+            let cos_d = wi.dot(&h);
+            let schlick_weight = |cos: f32| (1.0 - cos).max(0.0).powi(5);
+
+            let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let fd = |cos: f32| 1.0 + (fd90 - 1.0) * schlick_weight(cos);
+            let diffuse = self.base_color * fd(wi[2]) * fd(wo[2]) * std::f32::consts::FRAC_1_PI;
+            let sheen_color = lerp(
+                &RGB::all(1.0),
+                &(self.base_color / luminance(&self.base_color).max(1e-6)),
+                self.sheen_tint,
+            );
+            let sheen = sheen_color * self.sheen * schlick_weight(cos_d);
+
+            let microfacet = |ggx: &Ggx| ggx.d(&h) * ggx.g(&wi, &wo) / (4.0 * wi[2] * wo[2]);
+            let specular = schlick(&self.specular_f0(), cos_d) * microfacet(&self.ggx());
+            let clearcoat = schlick(&RGB::all(0.04), cos_d).r * microfacet(&self.clearcoat_ggx());
+            let dielectric = fresnel_dielectric(wo.dot(&h), eta_o, eta_i) * microfacet(&self.ggx());
+
+            (diffuse + sheen) * w_diffuse
+                + specular * w_specular
+                + RGB::all(clearcoat * w_clearcoat + dielectric * w_transmission)
+        } else {
+            //symmetric in the directions, as the radiance is not scaled by the indices
+            match Self::transmission_h(&wi, &wo, eta_i, eta_o) {
+                Some(h) => {
+                    let ggx = self.ggx();
+                    let f = fresnel_dielectric(wo.dot(&h), eta_o, eta_i);
+                    let denom = (eta_o * wo.dot(&h) + eta_i * wi.dot(&h)).powi(2);
+                    let t = (1.0 - f) * ggx.d(&h) * ggx.g(&wi, &wo) * eta_i * eta_o / denom
+                        * (wi.dot(&h) * wo.dot(&h) / (wi[2] * wo[2])).abs();
+                    self.base_color * t * w_transmission
+                }
+                None => RGB::all(0.0),
+            }
+        }
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_principled_sampling() {
+    let m = Principled {
+        metallic: 0.3,
+        anisotropic: 0.5,
+        clearcoat: 0.5,
+        sheen: 0.5,
+        transmission: 0.4,
+        ..Principled::new(RGB::new(0.8, 0.6, 0.4))
+    };
+    for wout in [V3::new(0.6, 0.0, 0.8), V3::new(0.0, 0.6, -0.8)].iter() {
+        let (sampled, integrated) = super::sampled_albedo(&m, wout, 200_000);
+        assert!(
+            (sampled.r - integrated.r).abs() < 0.02,
+            "{} {}",
+            sampled.r,
+            integrated.r
+        );
+    }
+}
+
+#[test]
+fn test_principled_params() {
+    let m = Principled::new(RGB::all(0.5))
+        .set_param("Base Color", &[0.1, 0.2, 0.3, 1.0])
+        .and_then(|m| m.set_param("metallicFactor", &[1.0]))
+        .and_then(|m| m.set_param("coat_roughness", &[0.25]))
+        .and_then(|m| m.set_param("IOR", &[1.5]))
+        .unwrap();
+    assert_eq!(m.base_color.b, 0.3);
+    assert_eq!(m.metallic, 1.0);
+    assert_eq!(m.clearcoat_gloss, 0.75);
+    assert!((m.specular - 0.5).abs() < 1e-6);
+    assert_eq!(
+        m.clone()
+            .set_param("Anisotropic", &[0.5])
+            .unwrap()
+            .anisotropic,
+        0.5
+    );
+    assert!(m.set_param("anisotropic_rotation", &[0.5]).is_err());
+}
@@ -0,0 +1,96 @@
+use crate::material::*;
+use crate::pdf::*;
+
+//reflects back toward the incident direction in a Phong-like lobe sharpening with the exponent,
+//as road signs and cat's eyes do
+#[derive(Clone, Debug)]
+pub struct Retroreflective {
+    pub color: RGB,
+    pub exponent: f32,
+}
+
+impl Retroreflective {
+    pub fn new(color: RGB, exponent: f32) -> Self {
+        Retroreflective { color, exponent }
+    }
+
+    //normalized over the directions around the outgoing one
+    fn lobe(&self, win_local: &V3, wout_local: &V3) -> f32 {
+        let cos = win_local.dot(wout_local);
+        if cos <= 0.0 {
+            0.0
+        } else {
+            (self.exponent + 1.0) / (2.0 * std::f32::consts::PI) * cos.powf(self.exponent)
+        }
+    }
+}
+
+impl MaterialImpl for Retroreflective {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        let cos = rng.gen::<f32>().powf(1.0 / (self.exponent + 1.0));
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+        let x = pick_orthogonal(wout_local);
+        let y = wout_local.cross(&x);
+        let win_local = cos * wout_local + sin * (phi.cos() * x + phi.sin() * y);
+        let pdf = self.sample_win_pdf(wout_local, &win_local, false);
+        if pdf <= 0.0 {
+            //below the surface, or at the rim of the lobe
+            return PdfSample {
+                value: (win_local, RGB::all(0.0), false),
+                pdf: 1.0,
+            };
+        }
+        PdfSample {
+            value: (win_local, self.bsdf(&win_local, wout_local, false), false),
+            pdf,
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            0.0
+        } else {
+            self.lobe(win_local, wout_local)
+        }
+    }
+
+    //bounded by the color in the albedo, as the lobe integrates to one
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            RGB::all(0.0)
+        } else {
+            self.color * self.lobe(win_local, wout_local)
+        }
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_retroreflective_sampling() {
+    let m = Retroreflective::new(RGB::all(0.8), 20.0);
+    let wout = V3::new(0.6, 0.0, 0.8);
+    let (sampled, integrated) = super::sampled_albedo(&m, &wout, 100_000);
+    let (sampled, integrated) = (sampled.r, integrated.r);
+    assert!(
+        (sampled - integrated).abs() < 0.02,
+        "{} {}",
+        sampled,
+        integrated
+    );
+    assert!(sampled < 0.8);
+    //brighter back toward the light than in the mirror direction
+    let mirror = V3::new(-0.6, 0.0, 0.8);
+    assert!(m.bsdf(&wout, &wout, false).r > 100.0 * m.bsdf(&mirror, &wout, false).r);
+}
@@ -0,0 +1,206 @@
+use crate::material::*;
+use crate::pdf::*;
+
+//homogeneous scattering medium filling a closed object whose normals face outward.
+//from the outside, the boundary reflects specularly by the Fresnel reflectance and transmits
+//diffusely otherwise. the path tracer walks the paths inside and refracts them out; the
+//bidirectional integrators do not support it.
+#[derive(Clone, Debug)]
+pub struct Subsurface {
+    //single-scattering albedo
+    pub albedo: RGB,
+    //mean distance between the interactions in the medium
+    pub mean_free_path: RGB,
+    pub index: f32,
+    //mean cosine of the Henyey-Greenstein phase function
+    pub anisotropy: f32,
+}
+
+impl Subsurface {
+    pub fn new(albedo: RGB, mean_free_path: RGB, index: f32) -> Self {
+        Subsurface {
+            albedo,
+            mean_free_path,
+            index,
+            anisotropy: 0.0,
+        }
+    }
+
+    //the medium looking approximately like the color, which light scatters about the distance in
+    //(Chiang et al. 2016)
+    pub fn from_color(color: RGB, distance: RGB, index: f32) -> Self {
+        let albedo = |a: f32| 1.0 - (-11.43 * a + 15.38 * a * a - 13.91 * a * a * a).exp();
+        let scale = |a: f32| 1.9 - a + 3.5 * (a - 0.8) * (a - 0.8);
+        Self::new(
+            RGB::new(albedo(color.r), albedo(color.g), albedo(color.b)),
+            RGB::new(
+                distance.r * scale(color.r),
+                distance.g * scale(color.g),
+                distance.b * scale(color.b),
+            ),
+            index,
+        )
+    }
+
+    pub fn set_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn at_wavelengths(&self, wavelengths: &spectrum::Wavelengths) -> Self {
+        Subsurface {
+            albedo: wavelengths.uplift(&self.albedo),
+            mean_free_path: wavelengths.uplift(&self.mean_free_path),
+            ..self.clone()
+        }
+    }
+
+    pub fn sigma_t(&self) -> RGB {
+        let mfp = &self.mean_free_path;
+        RGB::new(1.0 / mfp.r, 1.0 / mfp.g, 1.0 / mfp.b)
+    }
+
+    //direction after scattering in the medium, with the pdf cancelled by the phase function
+    pub fn sample_phase<R: Rng + ?Sized>(&self, dir: &V3, rng: &mut R) -> V3 {
+        let g = self.anisotropy;
+        let u = rng.gen::<f32>();
+        let cos = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin = (1.0 - cos * cos).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+        let x = pick_orthogonal(dir);
+        let y = dir.cross(&x);
+        cos * dir + sin * (phi.cos() * x + phi.sin() * y)
+    }
+
+    //reflectance of the boundary for the direction outside
+    fn fresnel(&self, w: &V3) -> f32 {
+        super::fresnel_dielectric(w[2], 1.0, self.index)
+    }
+}
+
+impl MaterialImpl for Subsurface {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        self.sample_win_cos(wout_local, rng)
+            .map(|(win_local, bsdf, spec)| (win_local, bsdf / win_local[2].abs(), spec))
+    }
+
+    //reflects in proportion to the reflectance, or crosses the boundary diffusely
+    fn sample_win_cos<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        let outside = wout_local[2] > 0.0;
+        let (win_local, specular) = if outside && rng.gen::<f32>() < self.fresnel(wout_local) {
+            (V3::new(-wout_local[0], -wout_local[1], wout_local[2]), true)
+        } else {
+            let sgn = if outside { -1.0 } else { 1.0 };
+            let cos_hemisphere = CosUnitHemisphere {
+                normal: sgn * V3::z(),
+                xvec: V3::x(),
+            };
+            (cos_hemisphere.sample(rng).value, false)
+        };
+        PdfSample {
+            value: (
+                win_local,
+                self.bsdf_cos(&win_local, wout_local, specular),
+                specular,
+            ),
+            pdf: self.sample_win_pdf(wout_local, &win_local, specular),
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        let outside = wout_local[2] > 0.0;
+        if specular_component {
+            if outside && win_local[2] > 0.0 {
+                self.fresnel(wout_local)
+            } else {
+                0.0
+            }
+        } else if win_local[2] * wout_local[2] >= 0.0 {
+            0.0
+        } else if outside {
+            (1.0 - self.fresnel(wout_local)) * win_local[2].abs() * std::f32::consts::FRAC_1_PI
+        } else {
+            win_local[2].abs() * std::f32::consts::FRAC_1_PI
+        }
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component {
+            self.bsdf_cos(win_local, wout_local, true) / win_local[2].abs()
+        } else if win_local[2] * wout_local[2] >= 0.0 {
+            RGB::all(0.0)
+        } else {
+            //transmitted by the direction outside, either way
+            let w = if win_local[2] > 0.0 {
+                win_local
+            } else {
+                wout_local
+            };
+            RGB::all((1.0 - self.fresnel(w)) * std::f32::consts::FRAC_1_PI)
+        }
+    }
+
+    fn bsdf_cos(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component {
+            if win_local[2] > 0.0 && wout_local[2] > 0.0 {
+                RGB::all(self.fresnel(wout_local))
+            } else {
+                RGB::all(0.0)
+            }
+        } else {
+            self.bsdf(win_local, wout_local, false) * win_local[2].abs()
+        }
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_subsurface_boundary() {
+    use rand::rngs::SmallRng;
+    let mut rng = SmallRng::seed_from_u64(0);
+    let m = Subsurface::from_color(RGB::new(0.8, 0.5, 0.3), RGB::all(1.0), 1.4);
+    assert!(m.albedo.r > m.albedo.g && m.albedo.g > m.albedo.b && m.albedo.r < 1.0);
+    //the boundary passes what it does not reflect from the outside, and less from the inside
+    for wout in [V3::new(0.6, 0.0, 0.8), V3::new(0.0, 0.6, -0.8)].iter() {
+        let n = 10_000;
+        let total = (0..n)
+            .map(|_| {
+                let s = m.sample_win_cos(wout, &mut rng);
+                s.value.1.r / s.pdf
+            })
+            .sum::<f32>()
+            / n as f32;
+        if wout[2] > 0.0 {
+            assert!((total - 1.0).abs() < 1e-3, "{}", total);
+        } else {
+            assert!(total < 1.0 - m.fresnel(&V3::z()));
+        }
+    }
+    //the directions after scattering follow the mean cosine
+    let m = m.set_anisotropy(0.6);
+    let mean_cos = (0..100_000)
+        .map(|_| m.sample_phase(&V3::y(), &mut rng)[1])
+        .sum::<f32>()
+        / 100_000.0;
+    assert!((mean_cos - 0.6).abs() < 0.01, "{}", mean_cos);
+}
@@ -0,0 +1,101 @@
+use crate::*;
+use nalgebra::Complex;
+
+//representative wavelengths of the rgb channels in nanometers
+const RGB_WAVELENGTHS: [f32; 3] = [630.0, 532.0, 465.0];
+
+//dielectric film over a surface, reflecting by the interference of the light reflected at its two
+//sides (Airy summation). the channels are evaluated at their wavelengths.
+#[derive(Clone, Debug)]
+pub struct ThinFilm {
+    //in nanometers
+    pub thickness: f32,
+    pub index: f32,
+    pub wavelengths: [f32; 3],
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, index: f32) -> Self {
+        ThinFilm {
+            thickness,
+            index,
+            wavelengths: RGB_WAVELENGTHS,
+        }
+    }
+
+    pub fn at_wavelengths(&self, wavelengths: &spectrum::Wavelengths) -> Self {
+        ThinFilm {
+            wavelengths: wavelengths.lambda,
+            ..self.clone()
+        }
+    }
+
+    //reflectance for the cosine in the outer medium of the index 1, averaged over the polarizations
+    pub fn reflectance(&self, cos: f32, substrate: &Substrate) -> RGB {
+        let one = Complex::new(1.0, 0.0);
+        let cos1 = Complex::new(cos.abs().min(1.0), 0.0);
+        let sq_sin1 = one - cos1 * cos1;
+        //cosine in the medium of the index by Snell's law, imaginary beyond the critical angle
+        let cos_in = |n: f32| (one - sq_sin1 / (n * n)).sqrt();
+        let (n1, n2) = (1.0, self.index);
+        let cos2 = cos_in(n2);
+        let r = |n_a: f32, cos_a: Complex<f32>, n_b: f32, cos_b: Complex<f32>| {
+            let s = (cos_a * n_a - cos_b * n_b) / (cos_a * n_a + cos_b * n_b);
+            let p = (cos_a * n_b - cos_b * n_a) / (cos_a * n_b + cos_b * n_a);
+            (s, p)
+        };
+        let r12 = r(n1, cos1, n2, cos2);
+        //coefficients at the substrate for its reflectance in the channel
+        let r23 = |reflectance: f32| match substrate {
+            Substrate::Dielectric(n3) => r(n2, cos2, *n3, cos_in(*n3)),
+            Substrate::Reflector(_) => {
+                let r = Complex::new(-reflectance.clamp(0.0, 1.0).sqrt(), 0.0);
+                (r, r)
+            }
+        };
+        let reflectance = |lambda: f32, r23: (Complex<f32>, Complex<f32>)| {
+            //phase difference of the light going through the film and back
+            let delta = cos2 * (4.0 * std::f32::consts::PI * n2 * self.thickness / lambda);
+            let e = (Complex::<f32>::i() * delta).exp();
+            let airy = |r12: Complex<f32>, r23: Complex<f32>| {
+                ((r12 + r23 * e) / (one + r12 * r23 * e)).norm_sqr()
+            };
+            ((airy(r12.0, r23.0) + airy(r12.1, r23.1)) / 2.0).min(1.0)
+        };
+        let color = match substrate {
+            Substrate::Reflector(color) => *color,
+            Substrate::Dielectric(_) => RGB::all(1.0),
+        };
+        let [l0, l1, l2] = self.wavelengths;
+        RGB::new(
+            reflectance(l0, r23(color.r)),
+            reflectance(l1, r23(color.g)),
+            reflectance(l2, r23(color.b)),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Substrate {
+    Dielectric(f32),
+    //reflecting the color at any angle with the phase shift of a conductor
+    Reflector(RGB),
+}
+
+#[test]
+fn test_thin_film() {
+    use super::Transparent;
+    let cos_t = |cos: f32, n: f32| (1.0 - (1.0 - cos * cos) / (n * n)).sqrt();
+    for cos in [1.0, 0.7, 0.2].iter() {
+        //vanishing films reflect as the bare substrate
+        let bare = Transparent::fresnel_reflection(1.0, *cos, 1.5, cos_t(*cos, 1.5));
+        let r = ThinFilm::new(0.0, 1.33).reflectance(*cos, &Substrate::Dielectric(1.5));
+        assert!((r.g - bare).abs() < 1e-4, "{} {}", r.g, bare);
+        //lossless films over perfect reflectors reflect everything
+        let r = ThinFilm::new(300.0, 1.4).reflectance(*cos, &Substrate::Reflector(RGB::all(1.0)));
+        assert!((r.r - 1.0).abs() < 1e-4);
+    }
+    //quarter-wave films cancel the reflection of the substrate of the squared index
+    let r = ThinFilm::new(532.0 / 4.0 / 1.5, 1.5).reflectance(1.0, &Substrate::Dielectric(2.25));
+    assert!(r.g < 1e-6 && r.r > 1e-3 && r.b > 1e-3);
+}
@@ -1,17 +1,99 @@
+use super::{Substrate, ThinFilm};
 use crate::material::*;
 
+//wavelength dependent index of refraction. wavelengths are in micrometers in the formulas.
+#[derive(Clone, Debug)]
+pub enum Dispersion {
+    //n = a + b / lambda^2
+    Cauchy { a: f32, b: f32 },
+    //n^2 = 1 + sum_i b_i lambda^2 / (lambda^2 - c_i)
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.039_612, 0.231_792_3, 1.010_469],
+            c: [0.006_000_699, 0.020_017_91, 103.560_6],
+        }
+    }
+
+    pub fn index(&self, lambda_nm: f32) -> f32 {
+        let l2 = (lambda_nm * 1e-3) * (lambda_nm * 1e-3);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f32>();
+                n2.sqrt()
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Transparent {
     pub color: RGB,
+    //used when not rendering spectrally
     pub index: f32,
+    pub dispersion: Option<Dispersion>,
+    pub film: Option<ThinFilm>,
+    //absorption coefficient per unit length inside the closed surface
+    pub absorption: RGB,
 }
 
 impl Transparent {
-    fn fresnel_reflection(ix1: f32, cos1: f32, ix2: f32, cos2: f32) -> f32 {
+    //the index is taken at the Fraunhofer d line
+    pub fn new_dispersive(color: RGB, dispersion: Dispersion) -> Self {
+        Transparent {
+            color,
+            index: dispersion.index(587.6),
+            dispersion: Some(dispersion),
+            film: None,
+            absorption: RGB::all(0.0),
+        }
+    }
+
+    pub fn set_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    pub fn set_absorption(mut self, absorption: RGB) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    //the index is resolved at the hero wavelength
+    pub fn at_wavelengths(&self, wavelengths: &spectrum::Wavelengths) -> Self {
+        Transparent {
+            color: wavelengths.uplift(&self.color),
+            index: self
+                .dispersion
+                .as_ref()
+                .map_or(self.index, |d| d.index(wavelengths.hero())),
+            dispersion: self.dispersion.clone(),
+            film: self.film.as_ref().map(|f| f.at_wavelengths(wavelengths)),
+            absorption: wavelengths.uplift(&self.absorption),
+        }
+    }
+
+    pub(crate) fn fresnel_reflection(ix1: f32, cos1: f32, ix2: f32, cos2: f32) -> f32 {
         let p = (ix2 * cos1 - ix1 * cos2) / (ix2 * cos1 + ix1 * cos2);
         let s = (ix1 * cos1 - ix2 * cos2) / (ix1 * cos1 + ix2 * cos2);
         return (p * p + s * s) / 2.0;
     }
+
+    //reflectance for the cosines on the sides of the indices, one of which is outside
+    fn reflectance(&self, ix1: f32, cos1: f32, ix2: f32, cos2: f32) -> RGB {
+        match &self.film {
+            //the film reflects the same from both sides
+            Some(film) => {
+                let cos_outside = if ix1 == 1.0 { cos1 } else { cos2 };
+                film.reflectance(cos_outside, &Substrate::Dielectric(self.index))
+            }
+            None => RGB::all(Self::fresnel_reflection(ix1, cos1, ix2, cos2)),
+        }
+    }
 }
 
 impl MaterialImpl for Transparent {
@@ -46,15 +128,17 @@ impl MaterialImpl for Transparent {
 
         if sin_in < 1.0 {
             let cos_in = (1.0 - sin_in * sin_in).sqrt();
-            let c_ref = Self::fresnel_reflection(index_in, cos_in.abs(), index_out, cos_out.abs());
-            if Uniform::new(0.0, 1.0).sample(rng) < c_ref {
+            let c_ref = self.reflectance(index_in, cos_in.abs(), index_out, cos_out.abs());
+            //chosen by the mean over the channels, which differ with films
+            let p_ref = (c_ref.r + c_ref.g + c_ref.b) / 3.0;
+            if Uniform::new(0.0, 1.0).sample(rng) < p_ref {
                 let win_local = V3::new(-wout_local[0], -wout_local[1], wout_local[2]);
                 pdf::PdfSample {
                     value: (win_local.normalize(), self.color * c_ref, true),
-                    pdf: c_ref,
+                    pdf: p_ref,
                 }
             } else {
-                let c_trans = 1.0 - c_ref;
+                let c_trans = RGB::all(1.0) - c_ref;
                 let win_local = V3::new(
                     -wout_local[0] * index_ratio,
                     -wout_local[1] * index_ratio,
@@ -62,7 +146,7 @@ impl MaterialImpl for Transparent {
                 );
                 pdf::PdfSample {
                     value: (win_local.normalize(), self.color * c_trans, true),
-                    pdf: c_trans,
+                    pdf: 1.0 - p_ref,
                 }
             }
         } else {
@@ -92,11 +176,12 @@ impl MaterialImpl for Transparent {
 
         if sin_in < 1.0 {
             let cos_in = (1.0 - sin_in * sin_in).sqrt();
-            let c_ref = Self::fresnel_reflection(index_in, cos_in.abs(), index_out, cos_out.abs());
+            let c_ref = self.reflectance(index_in, cos_in.abs(), index_out, cos_out.abs());
+            let p_ref = (c_ref.r + c_ref.g + c_ref.b) / 3.0;
             if win_local[2] * wout_local[2] > 0.0 {
-                c_ref
+                p_ref
             } else {
-                1.0 - c_ref
+                1.0 - p_ref
             }
         } else {
             1.0
@@ -125,15 +210,14 @@ impl MaterialImpl for Transparent {
 
             let c = if sin_in < 1.0 {
                 let cos_in = (1.0 - sin_in * sin_in).sqrt();
-                let c_ref =
-                    Self::fresnel_reflection(index_in, cos_in.abs(), index_out, cos_out.abs());
+                let c_ref = self.reflectance(index_in, cos_in.abs(), index_out, cos_out.abs());
                 if win_local[2] * wout_local[2] > 0.0 {
                     c_ref
                 } else {
-                    1.0 - c_ref
+                    RGB::all(1.0) - c_ref
                 }
             } else {
-                1.0
+                RGB::all(1.0)
             };
             self.color * c
         } else {
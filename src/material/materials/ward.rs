@@ -0,0 +1,149 @@
+use super::luminance;
+use crate::material::*;
+use crate::pdf::*;
+
+//anisotropic Ward model over a Lambertian base, in the normalization of Walter (2005).
+//alpha_x and alpha_y are the slopes of the highlight along the tangent and the bitangent.
+#[derive(Clone, Debug)]
+pub struct Ward {
+    pub diffuse: RGB,
+    pub specular: RGB,
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl Ward {
+    pub fn new(diffuse: RGB, specular: RGB, alpha_x: f32, alpha_y: f32) -> Self {
+        Ward {
+            diffuse,
+            specular,
+            alpha_x: alpha_x.max(1e-3),
+            alpha_y: alpha_y.max(1e-3),
+        }
+    }
+
+    //probability of sampling the specular lobe
+    fn specular_chance(&self) -> f32 {
+        let (d, s) = (luminance(&self.diffuse), luminance(&self.specular));
+        if d + s > 0.0 {
+            s / (d + s)
+        } else {
+            0.5
+        }
+    }
+
+    fn exponent(&self, h: &V3) -> f32 {
+        -((h[0] / self.alpha_x).powi(2) + (h[1] / self.alpha_y).powi(2)) / (h[2] * h[2])
+    }
+
+    //distribution of the half vectors by D(h) cos(h)
+    fn pdf_h(&self, h: &V3) -> f32 {
+        if h[2] <= 0.0 {
+            return 0.0;
+        }
+        self.exponent(h).exp() / (std::f32::consts::PI * self.alpha_x * self.alpha_y * h[2].powi(3))
+    }
+
+    fn sample_h<R: Rng + ?Sized>(&self, rng: &mut R) -> V3 {
+        let u1: f32 = rng.gen();
+        let u2: f32 = rng.gen();
+        let phi = 2.0 * std::f32::consts::PI * u2;
+        let phi = (self.alpha_y * phi.sin()).atan2(self.alpha_x * phi.cos());
+        let (sin, cos) = phi.sin_cos();
+        let sq_tan =
+            -(1.0 - u1).ln() / ((cos / self.alpha_x).powi(2) + (sin / self.alpha_y).powi(2));
+        V3::new(sq_tan.sqrt() * cos, sq_tan.sqrt() * sin, 1.0).normalize()
+    }
+}
+
+impl MaterialImpl for Ward {
+    fn sample_win<R: Rng + ?Sized>(
+        &self,
+        wout_local: &V3,
+        rng: &mut R,
+    ) -> PdfSample<(V3, RGB, bool)> {
+        let sgn = if wout_local[2] < 0.0 { -1.0 } else { 1.0 };
+        let win_local = if rng.gen::<f32>() < self.specular_chance() {
+            let h = sgn * self.sample_h(rng);
+            2.0 * wout_local.dot(&h) * h - wout_local
+        } else {
+            let cos_hemisphere = CosUnitHemisphere {
+                normal: sgn * V3::z(),
+                xvec: V3::x(),
+            };
+            cos_hemisphere.sample(rng).value
+        };
+        if win_local[2] * wout_local[2] <= 0.0 {
+            return PdfSample {
+                value: (win_local, RGB::all(0.0), false),
+                pdf: 1.0,
+            };
+        }
+        PdfSample {
+            value: (win_local, self.bsdf(&win_local, wout_local, false), false),
+            pdf: self.sample_win_pdf(wout_local, &win_local, false),
+        }
+    }
+
+    fn sample_win_pdf(&self, wout_local: &V3, win_local: &V3, specular_component: bool) -> f32 {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return 0.0;
+        }
+        let sgn = wout_local[2].signum();
+        let h = (win_local + wout_local).normalize() * sgn;
+        let c = self.specular_chance();
+        c * self.pdf_h(&h) / (4.0 * wout_local.dot(&h).abs())
+            + (1.0 - c) * win_local[2].abs() * std::f32::consts::FRAC_1_PI
+    }
+
+    fn bsdf(&self, win_local: &V3, wout_local: &V3, specular_component: bool) -> RGB {
+        if specular_component || win_local[2] * wout_local[2] <= 0.0 {
+            return RGB::all(0.0);
+        }
+        let h = (win_local + wout_local).normalize() * wout_local[2].signum();
+        let (ci, co) = (win_local[2].abs(), wout_local[2].abs());
+        let specular = self.exponent(&h).exp()
+            / (4.0 * std::f32::consts::PI * self.alpha_x * self.alpha_y * (ci * co).sqrt());
+        self.diffuse * std::f32::consts::FRAC_1_PI + self.specular * specular
+    }
+
+    fn all_specular(&self) -> bool {
+        false
+    }
+
+    fn has_specular(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_ward() {
+    use rand::rngs::SmallRng;
+    let mut rng = SmallRng::seed_from_u64(0);
+    let m = Ward::new(RGB::all(0.3), RGB::all(0.5), 0.1, 0.4);
+    //from either side
+    for wout in [V3::new(0.3, -0.4, 0.866), V3::new(-0.5, 0.0, -0.866)].iter() {
+        let (sampled, integrated) = super::sampled_albedo(&m, wout, 200_000);
+        let (sampled, integrated) = (sampled.r, integrated.r);
+        assert!(
+            (sampled - integrated).abs() < 0.03,
+            "{} {}",
+            sampled,
+            integrated
+        );
+        assert!(sampled < 0.8);
+    }
+    //the sampled half vectors follow D(h) cos(h), which integrates to one
+    let uniform = UniformUnitHemisphere {
+        normal: V3::z(),
+        xvec: V3::x(),
+    };
+    let area = (0..200_000)
+        .map(|_| {
+            let s = uniform.sample(&mut rng);
+            m.pdf_h(&s.value) / s.pdf
+        })
+        .sum::<f32>()
+        / 200_000.0;
+    assert!((area - 1.0).abs() < 0.05, "{}", area);
+}
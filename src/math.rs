@@ -60,3 +60,19 @@ pub fn pick_orthogonal(v: &V3) -> V3 {
     };
     like.cross(v).normalize()
 }
+
+//value in [0, 1) determined by the bits of the inputs, for decisions that can not draw random numbers
+pub fn hash_unit(xs: &[f32]) -> f32 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for x in xs {
+        h ^= u64::from(x.to_bits());
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    //finalizer of murmur3
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
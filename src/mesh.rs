@@ -0,0 +1,720 @@
+use crate::*;
+use nalgebra::Matrix4;
+use std::collections::HashMap;
+
+//polygon mesh refined and displaced before it is split into triangles for the scene.
+//texture coordinates and normals are given per vertex.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub positions: Vec<P3>,
+    //vertex indices of the polygons, counterclockwise seen from the front
+    pub faces: Vec<Vec<usize>>,
+    pub uvs: Option<Vec<P2>>,
+    pub normals: Option<Vec<V3>>,
+    //tints of the materials, as scanners record them
+    pub colors: Option<Vec<RGB>>,
+}
+
+//offsets of the vertices looked up by their texture coordinates
+#[derive(Clone, Debug)]
+pub enum Displacement {
+    //heights in the red channel along the normal, scaled
+    Scalar(texture::Texture, f32),
+    //offsets in the frame of the tangent, the bitangent and the normal in the colors, scaled
+    Vector(texture::Texture, f32),
+}
+
+//the edges keyed by their ends in order, with the faces sharing them
+struct Edges {
+    ends: Vec<(usize, usize)>,
+    faces: Vec<Vec<usize>>,
+    index: HashMap<(usize, usize), usize>,
+}
+
+impl Edges {
+    fn new(mesh: &Mesh) -> Self {
+        let mut edges = Edges {
+            ends: vec![],
+            faces: vec![],
+            index: HashMap::new(),
+        };
+        for (f, face) in mesh.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let key = Self::key(face[i], face[(i + 1) % face.len()]);
+                let next = edges.ends.len();
+                let e = *edges.index.entry(key).or_insert(next);
+                if e == next {
+                    edges.ends.push(key);
+                    edges.faces.push(vec![]);
+                }
+                edges.faces[e].push(f);
+            }
+        }
+        edges
+    }
+
+    fn key(a: usize, b: usize) -> (usize, usize) {
+        (a.min(b), a.max(b))
+    }
+
+    fn get(&self, a: usize, b: usize) -> usize {
+        self.index[&Self::key(a, b)]
+    }
+
+    fn is_boundary(&self, e: usize) -> bool {
+        self.faces[e].len() != 2
+    }
+
+    //the neighbors of the vertices, and those along the boundary
+    fn neighbors(&self, n: usize) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut all = vec![vec![]; n];
+        let mut boundary = vec![vec![]; n];
+        for (e, &(a, b)) in self.ends.iter().enumerate() {
+            all[a].push(b);
+            all[b].push(a);
+            if self.is_boundary(e) {
+                boundary[a].push(b);
+                boundary[b].push(a);
+            }
+        }
+        (all, boundary)
+    }
+}
+
+fn average(points: impl Iterator<Item = P3>) -> P3 {
+    let mut sum = V3::zeros();
+    let mut count = 0;
+    for p in points {
+        sum += p.coords;
+        count += 1;
+    }
+    P3::from(sum / count as f32)
+}
+
+fn midpoint(a: &P3, b: &P3) -> P3 {
+    P3::from((a.coords + b.coords) / 2.0)
+}
+
+fn average_uv(uvs: &[P2]) -> P2 {
+    P2::from(uvs.iter().map(|uv| uv.coords).sum::<V2>() / uvs.len() as f32)
+}
+
+fn average_color(colors: &[RGB]) -> RGB {
+    colors.iter().fold(RGB::all(0.0), |sum, c| sum + c) / colors.len() as f32
+}
+
+//attributes interpolated linearly to the points added by a level,
+//at the centers of 'centers' and then at the midpoints of the edges
+fn refine<T: Clone>(
+    values: &[T],
+    centers: &[Vec<usize>],
+    edges: &Edges,
+    average: impl Fn(&[T]) -> T,
+) -> Vec<T> {
+    let mut refined = values.to_vec();
+    refined.extend(centers.iter().map(|face| {
+        let corners: Vec<T> = face.iter().map(|&v| values[v].clone()).collect();
+        average(&corners)
+    }));
+    refined.extend(
+        edges
+            .ends
+            .iter()
+            .map(|&(a, b)| average(&[values[a].clone(), values[b].clone()])),
+    );
+    refined
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<P3>, faces: Vec<Vec<usize>>) -> Result<Self, String> {
+        if faces.iter().any(|face| face.len() < 3) {
+            return Err("faces need three vertices or more".into());
+        }
+        if faces.iter().flatten().any(|&i| i >= positions.len()) {
+            return Err("vertex index out of range".into());
+        }
+        //repeated corners in a row are merged and the faces still repeating a vertex are dropped,
+        //as they have no area and no edges to subdivide
+        let faces = faces
+            .into_iter()
+            .filter_map(|mut face| {
+                face.dedup();
+                while face.len() > 1 && face.first() == face.last() {
+                    face.pop();
+                }
+                let distinct = (0..face.len()).all(|i| !face[..i].contains(&face[i]));
+                if face.len() >= 3 && distinct {
+                    Some(face)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Ok(Mesh {
+            positions,
+            faces,
+            uvs: None,
+            normals: None,
+            colors: None,
+        })
+    }
+
+    //one value for each vertex
+    fn check_count(&self, name: &str, count: usize) -> Result<(), String> {
+        if count == self.positions.len() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} {} for {} vertices",
+                count,
+                name,
+                self.positions.len()
+            ))
+        }
+    }
+
+    pub fn set_uvs(mut self, uvs: Vec<P2>) -> Result<Self, String> {
+        self.check_count("texture coordinates", uvs.len())?;
+        self.uvs = Some(uvs);
+        Ok(self)
+    }
+
+    pub fn set_normals(mut self, normals: Vec<V3>) -> Result<Self, String> {
+        self.check_count("normals", normals.len())?;
+        self.normals = Some(normals);
+        Ok(self)
+    }
+
+    pub fn set_colors(mut self, colors: Vec<RGB>) -> Result<Self, String> {
+        self.check_count("colors", colors.len())?;
+        self.colors = Some(colors);
+        Ok(self)
+    }
+
+    pub fn is_triangular(&self) -> bool {
+        self.faces.iter().all(|face| face.len() == 3)
+    }
+
+    pub fn max_edge_length(&self) -> f32 {
+        Edges::new(self)
+            .ends
+            .iter()
+            .map(|&(a, b)| (self.positions[a] - self.positions[b]).norm())
+            .fold(0.0, f32::max)
+    }
+
+    //one level of loop's scheme, splitting each triangle into four.
+    //the texture coordinates and the colors are interpolated linearly and the normals are dropped.
+    pub fn subdivide_loop(&self) -> Result<Self, String> {
+        if !self.is_triangular() {
+            return Err("loop's scheme needs triangles".into());
+        }
+        Ok(self.loop_level())
+    }
+
+    fn loop_level(&self) -> Self {
+        let n = self.positions.len();
+        let edges = Edges::new(self);
+        let (neighbors, boundary) = edges.neighbors(n);
+        let p = &self.positions;
+
+        let mut positions: Vec<P3> = (0..n)
+            .map(|v| {
+                if !boundary[v].is_empty() {
+                    //corners of non-manifold boundaries stay
+                    if boundary[v].len() != 2 {
+                        return p[v];
+                    }
+                    let (b0, b1) = (p[boundary[v][0]].coords, p[boundary[v][1]].coords);
+                    return P3::from(p[v].coords * 0.75 + (b0 + b1) * 0.125);
+                }
+                let k = neighbors[v].len();
+                let beta = if k == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * k as f32)
+                };
+                let sum: V3 = neighbors[v].iter().map(|&u| p[u].coords).sum();
+                P3::from(p[v].coords * (1.0 - k as f32 * beta) + sum * beta)
+            })
+            .collect();
+        for (e, &(a, b)) in edges.ends.iter().enumerate() {
+            if edges.is_boundary(e) {
+                positions.push(midpoint(&p[a], &p[b]));
+                continue;
+            }
+            let opposite: V3 = edges.faces[e]
+                .iter()
+                .map(|&f| {
+                    let c = self.faces[f].iter().find(|&&c| c != a && c != b).unwrap();
+                    p[*c].coords
+                })
+                .sum();
+            positions.push(P3::from(
+                (p[a].coords + p[b].coords) * 0.375 + opposite * 0.125,
+            ));
+        }
+
+        let uvs = self
+            .uvs
+            .as_ref()
+            .map(|uvs| refine(uvs, &[], &edges, average_uv));
+        let colors = self
+            .colors
+            .as_ref()
+            .map(|colors| refine(colors, &[], &edges, average_color));
+
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|face| {
+                let (a, b, c) = (face[0], face[1], face[2]);
+                let ab = n + edges.get(a, b);
+                let bc = n + edges.get(b, c);
+                let ca = n + edges.get(c, a);
+                vec![
+                    vec![a, ab, ca],
+                    vec![b, bc, ab],
+                    vec![c, ca, bc],
+                    vec![ab, bc, ca],
+                ]
+            })
+            .collect();
+        Mesh {
+            positions,
+            faces,
+            uvs,
+            normals: None,
+            colors,
+        }
+    }
+
+    //one level of catmull-clark's scheme, splitting each polygon into quadrilaterals.
+    //the texture coordinates and the colors are interpolated linearly and the normals are dropped.
+    pub fn subdivide_catmull_clark(&self) -> Self {
+        let n = self.positions.len();
+        let n_faces = self.faces.len();
+        let edges = Edges::new(self);
+        let (neighbors, boundary) = edges.neighbors(n);
+        let p = &self.positions;
+
+        let mut faces_of = vec![vec![]; n];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in face {
+                faces_of[v].push(f);
+            }
+        }
+        let face_points: Vec<P3> = self
+            .faces
+            .iter()
+            .map(|face| average(face.iter().map(|&v| p[v])))
+            .collect();
+        let edge_points = edges.ends.iter().enumerate().map(|(e, &(a, b))| {
+            if edges.is_boundary(e) {
+                midpoint(&p[a], &p[b])
+            } else {
+                let (f0, f1) = (edges.faces[e][0], edges.faces[e][1]);
+                average(
+                    [p[a], p[b], face_points[f0], face_points[f1]]
+                        .iter()
+                        .cloned(),
+                )
+            }
+        });
+
+        let mut positions: Vec<P3> = (0..n)
+            .map(|v| {
+                if !boundary[v].is_empty() {
+                    if boundary[v].len() != 2 {
+                        return p[v];
+                    }
+                    let (b0, b1) = (p[boundary[v][0]].coords, p[boundary[v][1]].coords);
+                    return P3::from(p[v].coords * 0.75 + (b0 + b1) * 0.125);
+                }
+                let k = neighbors[v].len() as f32;
+                let f = average(faces_of[v].iter().map(|&f| face_points[f]));
+                let r = average(neighbors[v].iter().map(|&u| midpoint(&p[v], &p[u])));
+                P3::from((f.coords + r.coords * 2.0 + p[v].coords * (k - 3.0)) / k)
+            })
+            .collect();
+        positions.extend(face_points.iter().cloned());
+        positions.extend(edge_points);
+
+        let uvs = self
+            .uvs
+            .as_ref()
+            .map(|uvs| refine(uvs, &self.faces, &edges, average_uv));
+        let colors = self
+            .colors
+            .as_ref()
+            .map(|colors| refine(colors, &self.faces, &edges, average_color));
+
+        let faces = self
+            .faces
+            .iter()
+            .enumerate()
+            .flat_map(|(f, face)| {
+                let k = face.len();
+                let edge = |i: usize, j: usize| n + n_faces + edges.get(face[i % k], face[j % k]);
+                (0..k)
+                    .map(|i| vec![face[i], edge(i, i + 1), n + f, edge(i + k - 1, i)])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Mesh {
+            positions,
+            faces,
+            uvs,
+            normals: None,
+            colors,
+        }
+    }
+
+    //subdivides until no edge is longer than 'edge_length' or the next level would make more
+    //faces than 'max_faces', by loop's scheme for triangle meshes and catmull-clark's otherwise,
+    //then smooths the normals
+    pub fn subdivide_to(self, edge_length: f32, max_faces: usize) -> Self {
+        let mut mesh = self;
+        while mesh.max_edge_length() > edge_length {
+            let triangular = mesh.is_triangular();
+            let next_faces: usize = if triangular {
+                4 * mesh.faces.len()
+            } else {
+                mesh.faces.iter().map(Vec::len).sum()
+            };
+            if next_faces > max_faces {
+                break;
+            }
+            mesh = if triangular {
+                mesh.loop_level()
+            } else {
+                mesh.subdivide_catmull_clark()
+            };
+        }
+        if mesh.normals.is_none() {
+            mesh.normals = Some(mesh.vertex_normals());
+        }
+        mesh
+    }
+
+    //normals of the polygons weighted by their areas
+    pub fn vertex_normals(&self) -> Vec<V3> {
+        let mut normals = vec![V3::zeros(); self.positions.len()];
+        for face in &self.faces {
+            let a = self.positions[face[0]];
+            let normal: V3 = (1..face.len() - 1)
+                .map(|i| (self.positions[face[i]] - a).cross(&(self.positions[face[i + 1]] - a)))
+                .sum();
+            for &v in face {
+                normals[v] += normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| {
+                if n.norm_squared() > 0.0 {
+                    n.normalize()
+                } else {
+                    V3::z()
+                }
+            })
+            .collect()
+    }
+
+    //directions of u perpendicular to 'normals', the way triangles take them from their uvs
+    fn vertex_tangents(&self, uvs: &[P2], normals: &[V3]) -> Vec<V3> {
+        let mut tangents = vec![V3::zeros(); self.positions.len()];
+        for face in &self.faces {
+            for i in 1..face.len() - 1 {
+                let (a, b, c) = (face[0], face[i], face[i + 1]);
+                let (e1, e2) = (
+                    self.positions[b] - self.positions[a],
+                    self.positions[c] - self.positions[a],
+                );
+                let (d1, d2) = (uvs[b] - uvs[a], uvs[c] - uvs[a]);
+                let det = d1[0] * d2[1] - d2[0] * d1[1];
+                if det.abs() <= 1e-12 {
+                    continue;
+                }
+                //weighted by the area through the magnitude
+                let dpdu = (e1 * d2[1] - e2 * d1[1]) * det.signum();
+                for v in [a, b, c] {
+                    tangents[v] += dpdu;
+                }
+            }
+        }
+        tangents
+            .iter()
+            .zip(normals)
+            .map(|(t, n)| {
+                let t = t - n * n.dot(t);
+                if t.norm_squared() > 0.0 {
+                    t.normalize()
+                } else {
+                    pick_orthogonal(n)
+                }
+            })
+            .collect()
+    }
+
+    //moves the vertices by the texture at their uvs, then smooths the normals again
+    pub fn displace(mut self, displacement: &Displacement) -> Result<Self, String> {
+        let uvs = self
+            .uvs
+            .clone()
+            .ok_or("displacement needs texture coordinates")?;
+        let normals = self.normals.take().unwrap_or_else(|| self.vertex_normals());
+        match displacement {
+            Displacement::Scalar(texture, scale) => {
+                for ((p, uv), n) in self.positions.iter_mut().zip(&uvs).zip(&normals) {
+                    *p += n * texture.at(uv).r * *scale;
+                }
+            }
+            Displacement::Vector(texture, scale) => {
+                let tangents = self.vertex_tangents(&uvs, &normals);
+                for (((p, uv), n), t) in self
+                    .positions
+                    .iter_mut()
+                    .zip(&uvs)
+                    .zip(&normals)
+                    .zip(&tangents)
+                {
+                    let c = texture.at(uv);
+                    let b = n.cross(t);
+                    *p += (t * c.r + b * c.g + n * c.b) * *scale;
+                }
+            }
+        }
+        self.normals = Some(self.vertex_normals());
+        Ok(self)
+    }
+
+    //the polygons split into fans of triangles
+    pub fn triangles(&self) -> Vec<shape::shapes::Triangle> {
+        let mut triangles = vec![];
+        for face in &self.faces {
+            for i in 1..face.len() - 1 {
+                let vs = [face[0], face[i], face[i + 1]];
+                let mut triangle = shape::shapes::Triangle::new(vs.map(|v| self.positions[v]));
+                if let Some(uvs) = &self.uvs {
+                    triangle = triangle.set_uvs(vs.map(|v| uvs[v]));
+                }
+                if let Some(normals) = &self.normals {
+                    triangle = triangle.set_normals(vs.map(|v| normals[v]));
+                }
+                triangles.push(triangle);
+            }
+        }
+        triangles
+    }
+
+    //materials of the triangles in order, tinted by the average colors of their vertices
+    pub fn tinted(&self, material: &material::Material) -> Vec<material::Material> {
+        let mut materials = vec![];
+        for face in &self.faces {
+            for i in 1..face.len() - 1 {
+                materials.push(match &self.colors {
+                    Some(colors) => {
+                        let tint =
+                            average_color(&[colors[face[0]], colors[face[i]], colors[face[i + 1]]]);
+                        material.map_colors(&|c| *c * tint)
+                    }
+                    None => material.clone(),
+                });
+            }
+        }
+        materials
+    }
+
+    //copies of 'template' with the triangles as their shapes, to be put in a scene
+    pub fn objects(&self, template: &object::SimpleObject) -> Vec<object::SimpleObject> {
+        self.triangles()
+            .into_iter()
+            .zip(self.tinted(&template.material))
+            .map(|(triangle, material)| object::SimpleObject {
+                shape: triangle.into(),
+                material,
+                ..template.clone()
+            })
+            .collect()
+    }
+
+    //applies an affine transform, keeping the polygons facing outward under mirroring
+    pub fn transformed(mut self, transform: &Matrix4<f32>) -> Self {
+        for p in self.positions.iter_mut() {
+            *p = transform.transform_point(p);
+        }
+        let linear = transform.fixed_view::<3, 3>(0, 0).into_owned();
+        if let Some(normals) = self.normals.as_mut() {
+            let cofactor = linear.try_inverse().unwrap_or(linear).transpose();
+            for n in normals.iter_mut() {
+                *n = (cofactor * *n).normalize();
+            }
+        }
+        if linear.determinant() < 0.0 {
+            for face in self.faces.iter_mut() {
+                face.reverse();
+            }
+        }
+        self
+    }
+}
+
+#[test]
+fn test_subdivision() {
+    //an octahedron shrinks toward its center under loop's scheme
+    let octahedron = Mesh::new(
+        vec![
+            P3::new(1.0, 0.0, 0.0),
+            P3::new(-1.0, 0.0, 0.0),
+            P3::new(0.0, 1.0, 0.0),
+            P3::new(0.0, -1.0, 0.0),
+            P3::new(0.0, 0.0, 1.0),
+            P3::new(0.0, 0.0, -1.0),
+        ],
+        vec![
+            vec![0, 2, 4],
+            vec![2, 1, 4],
+            vec![1, 3, 4],
+            vec![3, 0, 4],
+            vec![2, 0, 5],
+            vec![1, 2, 5],
+            vec![3, 1, 5],
+            vec![0, 3, 5],
+        ],
+    )
+    .unwrap();
+    let once = octahedron.subdivide_loop().unwrap();
+    assert_eq!(once.positions.len(), 18);
+    assert_eq!(once.faces.len(), 32);
+    //valence 4, with the neighbors canceling out
+    assert!((once.positions[0] - P3::new(0.625, 0.0, 0.0)).norm() < 1e-5);
+    let fine = octahedron.subdivide_to(0.2, 100_000);
+    assert!(fine.max_edge_length() <= 0.2);
+    let normals = fine.normals.as_ref().unwrap();
+    for (p, n) in fine.positions.iter().zip(normals) {
+        assert!(n.dot(&p.coords) > 0.0);
+    }
+
+    //the corners of a cube move to 5 / 9 under catmull-clark's scheme
+    let corner = |i: usize| {
+        P3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        )
+    };
+    let cube = Mesh::new(
+        (0..8).map(corner).collect(),
+        vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ],
+    )
+    .unwrap();
+    let once = cube.subdivide_catmull_clark();
+    assert_eq!(once.positions.len(), 26);
+    assert_eq!(once.faces.len(), 24);
+    assert!((once.positions[7] - P3::new(5.0, 5.0, 5.0) / 9.0).norm() < 1e-5);
+    for n in once.vertex_normals().iter().zip(&once.positions) {
+        assert!(n.0.dot(&n.1.coords) > 0.0);
+    }
+}
+
+#[test]
+fn test_displacement() {
+    let plane = Mesh::new(
+        vec![
+            P3::new(0.0, 0.0, 0.0),
+            P3::new(1.0, 0.0, 0.0),
+            P3::new(1.0, 1.0, 0.0),
+            P3::new(0.0, 1.0, 0.0),
+        ],
+        vec![vec![0, 1, 2, 3]],
+    )
+    .unwrap()
+    .set_uvs(vec![
+        P2::new(0.0, 0.0),
+        P2::new(1.0, 0.0),
+        P2::new(1.0, 1.0),
+        P2::new(0.0, 1.0),
+    ])
+    .unwrap();
+    //the boundary keeps the square flat while subdividing
+    let fine = plane.subdivide_to(0.3, 100_000);
+    assert!(fine.max_edge_length() <= 0.3);
+    assert!(fine.positions.iter().all(|p| p[2] == 0.0));
+
+    let raised = fine
+        .clone()
+        .displace(&Displacement::Scalar(RGB::all(0.5).into(), 2.0))
+        .unwrap();
+    assert!(raised.positions.iter().all(|p| (p[2] - 1.0).abs() < 1e-5));
+
+    //offsets along u, v and the normal
+    let moved = fine
+        .clone()
+        .displace(&Displacement::Vector(RGB::new(1.0, 2.0, 3.0).into(), 0.1))
+        .unwrap();
+    let triangles = moved.triangles();
+    assert_eq!(triangles.len(), 2 * moved.faces.len());
+    let hit = triangles
+        .iter()
+        .find_map(|t| {
+            shape::Shape::from(t.clone()).test_hit(
+                &Ray::new(P3::new(0.5, 0.5, 5.0), -V3::z()),
+                1e-3,
+                100.0,
+            )
+        })
+        .unwrap();
+    assert!((hit.dist - 4.7).abs() < 1e-4);
+    assert!((hit.pos - P3::new(0.5, 0.5, 0.3)).norm() < 1e-4);
+
+    //invalid input is reported
+    let mut bare = fine;
+    bare.uvs = None;
+    assert!(bare
+        .displace(&Displacement::Scalar(RGB::all(0.5).into(), 1.0))
+        .is_err());
+    assert!(Mesh::new(vec![P3::origin(); 3], vec![vec![0, 1, 3]]).is_err());
+    assert!(Mesh::new(vec![P3::origin(); 3], vec![vec![0, 1]]).is_err());
+    let triangle = Mesh::new(vec![P3::origin(); 3], vec![vec![0, 1, 2]]).unwrap();
+    assert!(triangle.clone().set_normals(vec![V3::z(); 2]).is_err());
+    assert!(triangle.set_uvs(vec![P2::origin(); 4]).is_err());
+}
+
+#[test]
+fn test_degenerate_faces() {
+    let square = vec![
+        P3::new(0.0, 0.0, 0.0),
+        P3::new(1.0, 0.0, 0.0),
+        P3::new(1.0, 1.0, 0.0),
+        P3::new(0.0, 1.0, 0.0),
+    ];
+    //faces repeating a vertex are merged or dropped
+    let mesh = Mesh::new(
+        square.clone(),
+        vec![
+            vec![0, 1, 2],
+            vec![2, 3, 3],
+            vec![0, 0, 2, 3],
+            vec![0, 2, 0, 3],
+        ],
+    )
+    .unwrap();
+    assert_eq!(mesh.faces, vec![vec![0, 1, 2], vec![0, 2, 3]]);
+    assert_eq!(mesh.subdivide_loop().unwrap().faces.len(), 8);
+
+    //quadrilaterals go through catmull-clark's scheme
+    let quad = Mesh::new(square, vec![vec![0, 1, 2, 3]]).unwrap();
+    assert!(quad.subdivide_loop().is_err());
+
+    //the faces stay within the budget however short the edges asked for
+    let fine = quad.subdivide_to(0.0, 1000);
+    assert_eq!(fine.faces.len(), 256);
+}
@@ -1,4 +1,6 @@
 use crate::*;
+use animation::{Interpolate, Keyframes};
+use nalgebra::Isometry3;
 
 fn merge_options<T, F: Fn(T, T) -> T>(x: Option<T>, y: Option<T>, merge: F) -> Option<T> {
     if let Some(x) = x {
@@ -15,9 +17,16 @@ fn merge_options<T, F: Fn(T, T) -> T>(x: Option<T>, y: Option<T>, merge: F) -> O
 #[derive(Clone, Debug)]
 pub struct ObjectHit {
     pub geom: shape::Hit,
+    pub uv: P2,
+    //frame for shading, which may differ from the geometric one
+    pub snorm: V3,
+    pub tangent: V3,
     pub material: material::Material,
+    //radiance toward the origin of the ray
     pub emission: Option<RGB>,
     pub obj_ix: usize,
+    //wavelengths of the rays leaving the hit point
+    pub wavelengths: Option<spectrum::Wavelengths>,
 }
 
 impl ObjectHit {
@@ -28,23 +37,278 @@ impl ObjectHit {
     pub fn pos(&self) -> &P3 {
         &self.geom.pos
     }
+
+    //frame of the shading normal, in which the material is evaluated
+    pub fn lc(&self) -> LocalCoord {
+        LocalCoord::new_zx(&self.geom.pos, &self.snorm, &self.tangent)
+    }
+
+    //geometric term with the cosine at the hit point taken with the shading normal
+    pub fn g(&self, x: &P3, n: &V3) -> f32 {
+        let r = x - self.geom.pos;
+        let sq_dist = r.norm_squared();
+        (r.dot(n) * r.dot(&self.snorm)).abs() / (sq_dist * sq_dist)
+    }
+
+    //factor making the bsdf adjoint for light arriving from 'wfrom' and leaving to 'wto' (Veach 1997).
+    //needed whenever importance is transported with shading normals.
+    pub fn adjoint_correction(&self, wfrom: &V3, wto: &V3) -> f32 {
+        let (ng, ns) = (&self.geom.gnorm, &self.snorm);
+        let denom = (wfrom.dot(ng) * wto.dot(ns)).abs();
+        if denom == 0.0 {
+            0.0
+        } else {
+            (wfrom.dot(ns) * wto.dot(ng)).abs() / denom
+        }
+    }
+
+    pub fn at_wavelengths(mut self, wavelengths: &spectrum::Wavelengths) -> Self {
+        self.material = self.material.at_wavelengths(wavelengths);
+        if self.material.is_dispersive() {
+            //only the hero wavelength can follow the scattered direction
+            self.material = self.material.map_colors(&|c| wavelengths.hero_only(c));
+            self.wavelengths = Some(wavelengths.terminate_secondary());
+        } else {
+            self.wavelengths = Some(*wavelengths);
+        }
+        self
+    }
+}
+
+//rigid motion given by keyframes (time, object to world)
+#[derive(Clone, Debug)]
+pub struct Motion {
+    keys: Keyframes<Isometry3<f32>>,
+}
+
+impl Motion {
+    pub fn new(keys: Vec<(f32, Isometry3<f32>)>) -> Self {
+        Motion {
+            keys: Keyframes::new(keys),
+        }
+    }
+
+    pub fn linear(from: Isometry3<f32>, to: Isometry3<f32>) -> Self {
+        Self::new(vec![(0.0, from), (1.0, to)])
+    }
+
+    pub fn translate(from: &V3, to: &V3) -> Self {
+        Self::linear(
+            Isometry3::translation(from[0], from[1], from[2]),
+            Isometry3::translation(to[0], to[1], to[2]),
+        )
+    }
+
+    //object to world at the time
+    pub fn at(&self, time: f32) -> Isometry3<f32> {
+        self.keys.at(time)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.keys.is_constant()
+    }
+
+    pub fn clip(&self, begin: f32, end: f32) -> Self {
+        Motion {
+            keys: self.keys.clip(begin, end),
+        }
+    }
+
+    //bounds the object-space aabb over the whole motion
+    pub fn bound(&self, aabb: &shape::AABB) -> shape::AABB {
+        const STEPS: usize = 16;
+        let transformed = |iso: &Isometry3<f32>| {
+            let mut vs = aabb.iter_vertices().map(|v| iso * v);
+            let first = vs.next().unwrap();
+            vs.fold(shape::AABB::single_point(&first), |b, v| {
+                b.include_nomargin(&v)
+            })
+        };
+        let keys = self.keys.keys();
+        let mut bound = transformed(&keys[0].1);
+        for w in keys.windows(2) {
+            let (a, b) = (&w[0].1, &w[1].1);
+            //the vertices move along arcs; sampled positions bound the chords only
+            let angle = a.rotation.angle_to(&b.rotation) / STEPS as f32;
+            let radius = aabb.max_distance_from(&P3::origin());
+            let margin = radius * (1.0 - (angle / 2.0).cos());
+            for k in 1..=STEPS {
+                let iso = a.interpolate(b, k as f32 / STEPS as f32);
+                bound = bound.merge(&transformed(&iso));
+            }
+            let m = V3::new(margin, margin, margin);
+            bound = shape::AABB::new(&(bound.mins - m), &(bound.maxs + m));
+        }
+        bound
+    }
 }
 
+#[derive(Clone)]
 pub struct SimpleObject {
     pub shape: shape::Shape,
     pub material: material::Material,
-    pub emission: Option<RGB>,
+    pub emission: Option<emission::Emission>,
+    //the shape is given in object space when moving
+    pub motion: Option<Motion>,
+    pub normal_map: Option<texture::NormalMap>,
+    pub tangent_map: Option<texture::TangentMap>,
+    pub alpha: Option<texture::AlphaMask>,
 }
 
 impl SimpleObject {
-    pub fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32, self_ix: usize) -> Option<ObjectHit> {
-        self.shape.test_hit(ray, tnear, tfar).map(|geom| ObjectHit {
+    pub fn test_hit(
+        &self,
+        ray: &Ray,
+        mut tnear: f32,
+        tfar: f32,
+        self_ix: usize,
+    ) -> Option<ObjectHit> {
+        const MAX_MASKED_HITS: usize = 16;
+        let alpha = match &self.alpha {
+            Some(alpha) => alpha,
+            None => return self.test_hit_unmasked(ray, tnear, tfar, self_ix),
+        };
+        //hits cut out by the mask are skipped to find the ones behind
+        for _ in 0..MAX_MASKED_HITS {
+            let hit = self.test_hit_unmasked(ray, tnear, tfar, self_ix)?;
+            let dist = hit.geom.dist;
+            let (o, d) = (&ray.origin, &ray.dir);
+            let u = math::hash_unit(&[o[0], o[1], o[2], d[0], d[1], d[2], dist]);
+            if alpha.is_opaque(&hit.uv, u) {
+                return Some(hit);
+            }
+            tnear = dist + 1e-4 * dist.max(1.0);
+        }
+        None
+    }
+
+    //the fraction of hits at the point not cut out by the mask
+    pub fn opacity(&self, uv: &P2) -> f32 {
+        self.alpha.as_ref().map_or(1.0, |alpha| alpha.opacity(uv))
+    }
+
+    fn test_hit_unmasked(
+        &self,
+        ray: &Ray,
+        tnear: f32,
+        tfar: f32,
+        self_ix: usize,
+    ) -> Option<ObjectHit> {
+        let shading = |hit: &shape::Hit| {
+            let uv = self.shape.uv(&hit.pos);
+            let snorm = self.shape.shading_normal(&hit.pos).unwrap_or(hit.gnorm);
+            (uv, snorm, self.shape.tangent(&hit.pos))
+        };
+        let (geom, uv, snorm, tangent) = if let Some(motion) = &self.motion {
+            let o2w = motion.at(ray.time);
+            let ray_local = o2w.inverse() * ray.clone();
+            let hit = self.shape.test_hit(&ray_local, tnear, tfar)?;
+            let (uv, snorm, tangent) = shading(&hit);
+            let geom = shape::Hit {
+                dist: hit.dist,
+                pos: o2w * hit.pos,
+                gnorm: o2w * hit.gnorm,
+                gx: o2w * hit.gx,
+            };
+            (geom, uv, o2w * snorm, o2w * tangent)
+        } else {
+            let hit = self.shape.test_hit(ray, tnear, tfar)?;
+            let (uv, snorm, tangent) = shading(&hit);
+            (hit, uv, snorm, tangent)
+        };
+        let snorm = match &self.normal_map {
+            Some(map) => map.apply(&uv, &snorm, &tangent),
+            None => snorm,
+        };
+        let tangent = match &self.tangent_map {
+            Some(map) => map.apply(&uv, &snorm, &tangent),
+            None => tangent,
+        };
+        let emission = self.emission.as_ref().map(|e| {
+            e.color_at(&uv, ray.wavelengths.as_ref()) * e.profile_at(-ray.dir.dot(&geom.gnorm))
+        });
+        Some(ObjectHit {
             geom,
-            material: self.material.clone(),
-            emission: self.emission,
+            uv,
+            snorm,
+            tangent,
+            material: self.material.at_uv(&uv),
+            emission,
             obj_ix: self_ix,
+            wavelengths: None,
         })
     }
+
+    //texture coordinates of a point on the surface
+    pub fn uv(&self, pos: &P3, time: f32) -> P2 {
+        match &self.motion {
+            Some(motion) => self.shape.uv(&(motion.at(time).inverse() * pos)),
+            None => self.shape.uv(pos),
+        }
+    }
+
+    pub fn aabb(&self) -> shape::AABB {
+        let aabb = self.shape.aabb();
+        match &self.motion {
+            Some(motion) => motion.bound(&aabb),
+            None => aabb,
+        }
+    }
+
+    pub fn sample_surface<R>(&self, time: f32, rng: &mut R) -> pdf::PdfSample<(P3, V3)>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        let sample = self.shape.sample_surface(rng);
+        match &self.motion {
+            Some(motion) => {
+                let o2w = motion.at(time);
+                sample.map(|(pos, normal)| (o2w * pos, o2w * normal))
+            }
+            None => sample,
+        }
+    }
+
+    pub fn sample_surface_pdf(&self, pos: &P3, time: f32) -> f32 {
+        match &self.motion {
+            Some(motion) => self
+                .shape
+                .sample_surface_pdf(&(motion.at(time).inverse() * pos)),
+            None => self.shape.sample_surface_pdf(pos),
+        }
+    }
+
+    pub fn sample_from<R>(&self, point: &P3, time: f32, rng: &mut R) -> pdf::PdfSample<(P3, V3)>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        //the solid angle misses the surfaces seen through the cutouts
+        if self.alpha.is_some() {
+            return self.sample_surface(time, rng);
+        }
+        match &self.motion {
+            Some(motion) => {
+                let o2w = motion.at(time);
+                self.shape
+                    .sample_from(&(o2w.inverse() * point), rng)
+                    .map(|(pos, normal)| (o2w * pos, o2w * normal))
+            }
+            None => self.shape.sample_from(point, rng),
+        }
+    }
+
+    pub fn sample_from_pdf(&self, point: &P3, pos: &P3, time: f32) -> f32 {
+        if self.alpha.is_some() {
+            return self.sample_surface_pdf(pos, time);
+        }
+        match &self.motion {
+            Some(motion) => {
+                let w2o = motion.at(time).inverse();
+                self.shape.sample_from_pdf(&(w2o * point), &(w2o * pos))
+            }
+            None => self.shape.sample_from_pdf(point, pos),
+        }
+    }
 }
 
 pub struct ObjectList {
@@ -88,7 +352,7 @@ pub struct BVH {
 impl BVH {
     pub fn new(objects: Vec<SimpleObject>) -> Self {
         let l = objects.len();
-        let mut ixs: Vec<_> = (0..l).map(|i| (i, objects[i].shape.aabb())).collect();
+        let mut ixs: Vec<_> = (0..l).map(|i| (i, objects[i].aabb())).collect();
         let dummy_leaf = BVHNode::Leaf {
             aabb: shape::AABB::new(&P3::origin(), &P3::origin()),
             object_ix: l,
@@ -175,3 +439,56 @@ impl BVH {
         }
     }
 }
+
+#[test]
+fn test_motion_bound() {
+    use nalgebra::{Translation3, UnitQuaternion, Vector3};
+    let rot = |a: f32| {
+        Isometry3::from_parts(
+            Translation3::new(a, 0.0, 0.0),
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), a),
+        )
+    };
+    let motion = Motion::new(vec![(0.0, rot(0.0)), (1.0, rot(3.0))]);
+    let aabb = shape::AABB::new(&P3::new(1.0, -1.0, -1.0), &P3::new(2.0, 1.0, 1.0));
+    let bound = motion.bound(&aabb);
+    for i in 0..=100 {
+        let o2w = motion.at(i as f32 / 100.0);
+        for v in aabb.iter_vertices() {
+            assert!(bound.inside(&(o2w * v)));
+        }
+    }
+    assert!(bound.inside(&(rot(1.5) * P3::new(1.5, 0.0, 0.0))));
+}
+
+#[test]
+fn test_alpha_mask() {
+    use texture::{AlphaMask, Texture};
+    let quad = |alpha: AlphaMask| SimpleObject {
+        shape: shape::shapes::Parallelogram::new_rectangle(
+            &P3::origin(),
+            &V3::new(1.0, 0.0, 0.0),
+            &V3::new(0.0, 1.0, 0.0),
+        )
+        .into(),
+        material: material::materials::Lambert(RGB::all(0.5)).into(),
+        emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: Some(alpha),
+    };
+    let rays = (0..1000).map(|i| {
+        let x = i as f32 / 1000.0 - 0.5;
+        Ray::new(P3::new(x, 0.3 * x, 1.0), V3::new(0.0, 0.0, -1.0))
+    });
+    let cut = quad(AlphaMask::Threshold(Texture::from(RGB::all(0.4)), 0.5));
+    assert!(rays
+        .clone()
+        .all(|r| cut.test_hit(&r, 0.0, 10.0, 0).is_none()));
+    let half = quad(AlphaMask::Stochastic(Texture::from(RGB::all(0.5))));
+    let hits = rays
+        .filter(|r| half.test_hit(r, 0.0, 10.0, 0).is_some())
+        .count();
+    assert!(400 < hits && hits < 600);
+}
@@ -161,3 +161,54 @@ impl Distribution<PdfSample<bool>> for RandomBool {
         }
     }
 }
+
+//index chosen in proportion to the weights
+#[derive(Clone, Debug)]
+pub struct Discrete {
+    //normalized, ending at 1
+    cdf: Vec<f32>,
+}
+
+impl Discrete {
+    //None if the weights sum to zero
+    pub fn new(weights: &[f32]) -> Option<Self> {
+        let mut sum = 0.0;
+        let mut cdf: Vec<f32> = weights
+            .iter()
+            .map(|w| {
+                sum += w.max(0.0);
+                sum
+            })
+            .collect();
+        if sum <= 0.0 {
+            return None;
+        }
+        for c in cdf.iter_mut() {
+            *c /= sum;
+        }
+        Some(Discrete { cdf })
+    }
+}
+
+impl Distribution<PdfSample<usize>> for Discrete {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> PdfSample<usize> {
+        let u = rng.gen::<f32>();
+        let i = self
+            .cdf
+            .partition_point(|c| *c <= u)
+            .min(self.cdf.len() - 1);
+        PdfSample {
+            value: i,
+            pdf: self.pdf(&i),
+        }
+    }
+}
+
+impl Pdf<usize> for Discrete {
+    fn pdf(&self, i: &usize) -> f32 {
+        match *i {
+            0 => self.cdf[0],
+            i => self.cdf[i] - self.cdf[i - 1],
+        }
+    }
+}
@@ -6,18 +6,33 @@ use std::ops::Mul;
 pub struct Ray {
     pub origin: P3,
     pub dir: V3,
+    pub time: f32,
+    //set when rendering spectrally
+    pub wavelengths: Option<spectrum::Wavelengths>,
 }
 
 impl Ray {
     pub fn new(origin: P3, dir: V3) -> Self {
-        Ray { origin, dir }
+        Ray {
+            origin,
+            dir,
+            time: 0.0,
+            wavelengths: None,
+        }
+    }
+
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn with_wavelengths(mut self, wavelengths: Option<spectrum::Wavelengths>) -> Self {
+        self.wavelengths = wavelengths;
+        self
     }
 
     pub fn from_to(origin: &P3, toward: &P3) -> Self {
-        Ray {
-            origin: *origin,
-            dir: (toward - origin).normalize(),
-        }
+        Self::new(*origin, (toward - origin).normalize())
     }
 
     pub fn new_from_origin(dir: V3) -> Self {
@@ -34,7 +49,12 @@ impl Mul<Ray> for &Isometry3<f32> {
     fn mul(self, ray: Ray) -> Ray {
         let origin = self * ray.origin;
         let dir = self * ray.dir;
-        Ray { origin, dir }
+        Ray {
+            origin,
+            dir,
+            time: ray.time,
+            wavelengths: ray.wavelengths,
+        }
     }
 }
 
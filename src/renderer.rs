@@ -31,6 +31,8 @@ pub struct FilmConfig<T> {
 pub struct RenderConfig {
     pub integrator: IntegratorType,
     pub nthread: usize,
+    //samples wavelengths instead of RGB (see spectrum)
+    pub spectral: bool,
 }
 
 pub trait Integrator {
@@ -47,12 +49,14 @@ pub trait Integrator {
 pub trait OnepassIntegrator {
     type Integrator;
     fn clone_integrator(&self) -> Self::Integrator;
+    #[allow(clippy::too_many_arguments)]
     fn render_thread<T: Clone + Accumulator>(
         integrator: &Self::Integrator,
         scene: &Scene,
         camera: impl Camera,
         film: FilmArc<T>,
         accum_init: T,
+        config: RenderConfig,
         thread_id: usize,
         manager: Arc<Mutex<Manager>>,
     );
@@ -83,7 +87,16 @@ where
             let accum_init = film_config.accum_init.clone();
             let integrator = self.clone_integrator();
             let thread = thread::spawn(move || {
-                Self::render_thread(&integrator, &scene, camera, film, accum_init, i, manager)
+                Self::render_thread(
+                    &integrator,
+                    &scene,
+                    camera,
+                    film,
+                    accum_init,
+                    config,
+                    i,
+                    manager,
+                )
             });
             threads.push(thread);
         }
@@ -117,6 +130,7 @@ impl<RRI: RayRadianceIntegrator + Clone> OnepassIntegrator for RayRadianceIntegr
         camera: impl Camera,
         film: FilmArc<T>,
         accum_init: T,
+        config: RenderConfig,
         thread_id: usize,
         manager: Arc<Mutex<Manager>>,
     ) {
@@ -141,8 +155,19 @@ impl<RRI: RayRadianceIntegrator + Clone> OnepassIntegrator for RayRadianceIntegr
                 for _i in 0..spp {
                     let mut radiance = accum_init.clone();
                     let (u, v) = film.sample_uv_in_pixel(xi as i32, yi as i32, &mut rng);
-                    let ray = camera.sample_ray(u, v, &mut rng);
-                    integrator.radiance(scene, &ray, &mut radiance, &mut rng);
+                    if let Some(ray) = camera.sample_ray(u, v, &mut rng) {
+                        if config.spectral {
+                            let wavelengths = spectrum::Wavelengths::sample(&mut rng);
+                            let ray = ray.with_wavelengths(Some(wavelengths));
+                            let mut radiance = spectrum::SpectralAccumulator {
+                                accum: &mut radiance,
+                                wavelengths,
+                            };
+                            integrator.radiance(scene, &ray, &mut radiance, &mut rng);
+                        } else {
+                            integrator.radiance(scene, &ray, &mut radiance, &mut rng);
+                        }
+                    }
                     if radiance.is_finite() {
                         samples.push((xi, yi, radiance));
                     } else {
@@ -174,6 +199,7 @@ pub trait RandomPixelIntegrator {
         camera: &C,
         film: &mut FilmVec<T>,
         accum_init: &T,
+        wavelengths: Option<spectrum::Wavelengths>,
         rng: &mut R,
     ) where
         R: Rng + ?Sized,
@@ -194,6 +220,7 @@ impl<RPI: RandomPixelIntegrator + Clone> OnepassIntegrator for RandomPixelIntegr
         camera: impl Camera,
         film: FilmArc<T>,
         accum_init: T,
+        config: RenderConfig,
         thread_id: usize,
         manager: Arc<Mutex<Manager>>,
     ) {
@@ -218,7 +245,19 @@ impl<RPI: RandomPixelIntegrator + Clone> OnepassIntegrator for RandomPixelIntegr
             let _xi = task.chunk;
             let spp = task.amount;
             for _i in 0..film.h() * spp {
-                integrator.sample(scene, &camera, &mut local_film, &accum_init, &mut rng);
+                let wavelengths = if config.spectral {
+                    Some(spectrum::Wavelengths::sample(&mut rng))
+                } else {
+                    None
+                };
+                integrator.sample(
+                    scene,
+                    &camera,
+                    &mut local_film,
+                    &accum_init,
+                    wavelengths,
+                    &mut rng,
+                );
             }
             total_sample += film.h() * spp;
         }
@@ -246,8 +285,16 @@ impl Renderer {
         film_config: FilmConfig<T>,
         config: RenderConfig,
         on_cycle_complete: Box<dyn FnMut(usize, usize) -> Option<usize> + Send>,
-    ) {
+    ) -> Result<(), String> {
         match config.integrator {
+            IntegratorType::BidirectionalPathTrace | IntegratorType::LightTrace
+                if scene.has_subsurface() =>
+            {
+                return Err(format!(
+                    "{:?} does not support subsurface materials; use pt or nee",
+                    config.integrator
+                ));
+            }
             IntegratorType::PathTrace => {
                 RayRadianceIntegratorWrapper(pt::PathTracer { enable_nee: false }).integrate(
                     scene,
@@ -285,5 +332,6 @@ impl Renderer {
                 );
             }
         }
+        Ok(())
     }
 }
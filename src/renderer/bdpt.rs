@@ -32,6 +32,9 @@ pub struct Vertex {
     pub pdf_area: f32,
     pub pdf_area_ratio: f32,
     pub specular: bool,
+    pub event: lpe::Event,
+    //absorbing medium on the side the vertex is reached from
+    pub medium: Option<RGB>,
 }
 
 impl Vertex {
@@ -78,6 +81,8 @@ pub fn gen_vertices<R: ?Sized>(
     scene: &Scene,
     ray: &Ray,
     init_ray_delta: bool,
+    //bsdfs on subpaths from lights are made adjoint for the shading normals
+    from_light: bool,
     max_depth: usize,
     rng: &mut R,
 ) -> Vec<Vertex>
@@ -89,18 +94,22 @@ where
     let mut ray = ray.clone();
     let mut pdf_area = 1.0;
     let mut pdf_area_ratio = 1.0;
+    let mut medium: Option<RGB> = None;
     for depth in 0..max_depth {
         let hit = scene.test_hit(&ray, 1e-3, std::f32::MAX / 2.0);
+        //escaping subpaths gather no radiance from the environment, in a medium or not
         if hit.is_none() {
             break;
         }
         let hit = hit.unwrap();
-        let hit_lc = hit.geom.lc();
+        throughput *= material::transmittance(medium.as_ref(), hit.geom.dist);
+        let hit_lc = hit.lc();
         let wout_local = hit_lc.w2l() * -ray.dir;
         if vs.last().map(|v| !v.specular).unwrap_or(!init_ray_delta) {
             //convert the solid-angle pdf from the previous loop
-            pdf_area *= wout_local[2].abs() / hit.geom.dist / hit.geom.dist;
-            pdf_area_ratio *= wout_local[2].abs() / hit.geom.dist / hit.geom.dist;
+            let cos = ray.dir.dot(&hit.geom.gnorm).abs();
+            pdf_area *= cos / hit.geom.dist / hit.geom.dist;
+            pdf_area_ratio *= cos / hit.geom.dist / hit.geom.dist;
         }
 
         let next = hit.material.sample_win_cos(&wout_local, rng);
@@ -113,11 +122,17 @@ where
             pdf_area,
             pdf_area_ratio,
             specular: next.value.2,
+            event: lpe::Event::scatter(next.value.2, &win_local, &wout_local),
+            medium,
         });
         pdf_area_ratio = 1.0;
+        medium = hit.material.medium_toward(&win_local, &wout_local, medium);
 
         let bsdf_cos = next.value.1;
         throughput *= bsdf_cos / next.pdf;
+        if from_light {
+            throughput *= hit.adjoint_correction(&-ray.dir, &(hit_lc.l2w() * win_local));
+        }
         // will be converted into area pdf in the next loop
         pdf_area *= next.pdf;
         pdf_area_ratio *= next.pdf;
@@ -133,13 +148,17 @@ where
         throughput /= cont.pdf;
         pdf_area *= cont.pdf;
         pdf_area_ratio *= cont.pdf;
-        ray = hit_lc.l2w() * Ray::new(P3::origin(), win_local);
+        ray = hit_lc.l2w()
+            * Ray::new(P3::origin(), win_local)
+                .with_time(ray.time)
+                .with_wavelengths(hit.wavelengths);
     }
     vs
 }
 
 fn extend_path_pdf<'a>(
     ray_delta: bool,
+    from_light: bool,
     origin: Option<&P3>,
     vs_init: &'a [Vertex],
     vs_latter: &'a [Vertex],
@@ -179,17 +198,19 @@ fn extend_path_pdf<'a>(
             };
         let w = origin - pseudo_init_original.pos();
         let r = w.norm();
-        let w_local = (pseudo_init_original.hit.geom.lc().w2l() * w).normalize();
+        let w_local = (pseudo_init_original.hit.lc().w2l() * w).normalize();
         let pdf_area = if ray_delta {
             1.0
         } else {
-            w_local[2].abs() / r / r
+            w.dot(pseudo_init_original.gnorm()).abs() / r / r / r
         };
 
         let pseudo_init = Vertex {
             hit: pseudo_init_original.hit.clone(),
             throughput: RGB::all(1.0),
             specular: pseudo_init_original.specular,
+            event: pseudo_init_original.event,
+            medium: pseudo_init_original.medium,
             w_local,
             pdf_area,
             pdf_area_ratio: pdf_area,
@@ -261,7 +282,7 @@ fn extend_path_pdf<'a>(
             .scan(init_state, move |state, (v, next)| {
                 let (next_pos, next_normal, next_all_specular) = next;
                 let v_geom = &v.hit.geom;
-                let lc = v_geom.lc();
+                let lc = v.hit.lc();
                 let wout_local = lc.w2l() * state.dir;
                 let to_next = next_pos - v_geom.pos;
                 let r = to_next.norm();
@@ -286,6 +307,9 @@ fn extend_path_pdf<'a>(
 
                 let mut pdf_area_ratio = 1.0;
                 state.throughput *= bsdf_cos / dir_pdf_omega;
+                if from_light {
+                    state.throughput *= v.hit.adjoint_correction(&state.dir, &win);
+                }
                 state.pdf_area *= dir_pdf_omega;
                 pdf_area_ratio *= dir_pdf_omega;
                 if !specular_component {
@@ -340,16 +364,37 @@ fn mis_weight(
         assert!(!light_vs.last().unwrap().hit.material.all_specular())
     }
 
+    //lights other than emissive objects are never hit by eye subpaths, and the vertex on them
+    //is given the normal toward the next vertex
+    let from_light = matches!(
+        light_sample,
+        Some(scene::LightSampleResult {
+            source: scene::LightSource::Light(_),
+            ..
+        })
+    );
+    let light_vertex = light_sample.map(|ls| {
+        let normal = if from_light {
+            let next = light_vs.first().unwrap_or_else(|| eye_vs.last().unwrap());
+            (next.pos() - ls.pos).normalize()
+        } else {
+            ls.normal
+        };
+        (ls.pos, normal, false)
+    });
     let eye_extended: Vec<_> = extend_path_pdf(
         true,
+        false,
         Some(&ray.origin),
         eye_vs,
         light_vs,
-        light_sample.map(|ls| (ls.pos, ls.normal, false)),
+        light_vertex,
     )
     .collect();
     let mut light_pos_pdf = 1.0;
     let mut light_dir_pdf = 1.0;
+    //pdf of the light vertex sampled from the vertex next to it, for the strategy with s = 1
+    let mut light_connection_pdf = 1.0;
 
     let light_extended: Vec<_> = if original_s == 0 {
         assert!(light_sample.is_none());
@@ -359,24 +404,33 @@ fn mis_weight(
             let light = eye_vs.last().unwrap();
             let light_pos = light.pos();
             assert!(light.hit.emission.is_some());
-            light_pos_pdf *= scene.sample_light_pdf(&light_pos, light.hit.obj_ix);
+            let source = scene::LightSource::Object(light.hit.obj_ix);
+            light_pos_pdf *= scene.sample_light_pdf(&light_pos, source, ray.time);
             light_dir_pdf *= 1.0; // TODO direction pdf
+            let next = eye_vs[eye_vs.len() - 2].pos();
+            light_connection_pdf *= scene.sample_light_from_pdf(next, light_pos, source, ray.time);
 
-            extend_path_pdf(false, None, &[], &eye_vs, None).collect()
+            extend_path_pdf(false, true, None, &[], &eye_vs, None).collect()
         }
     } else if original_s == 1 {
         assert!(light_vs.is_empty());
         let light = light_sample.unwrap();
-        light_pos_pdf *= scene.sample_light_pdf(&light.pos, light.obj_ix);
+        light_pos_pdf *= scene.sample_light_pdf(&light.pos, light.source, ray.time);
         light_dir_pdf *= 1.0; // TODO direction pdf
+        let next = eye_vs.last().unwrap().pos();
+        light_connection_pdf *=
+            scene.sample_light_from_pdf(next, &light.pos, light.source, ray.time);
 
-        extend_path_pdf(false, Some(&light.pos), &[], &eye_vs, None).collect()
+        extend_path_pdf(false, true, Some(&light.pos), &[], &eye_vs, None).collect()
     } else {
         let light = light_sample.unwrap();
-        light_pos_pdf *= scene.sample_light_pdf(&light.pos, light.obj_ix);
+        light_pos_pdf *= scene.sample_light_pdf(&light.pos, light.source, ray.time);
         light_dir_pdf *= 1.0; // TODO direction pdf
+        let next = light_vs[0].pos();
+        light_connection_pdf *=
+            scene.sample_light_from_pdf(next, &light.pos, light.source, ray.time);
 
-        extend_path_pdf(false, Some(&light.pos), &light_vs, &eye_vs, None).collect()
+        extend_path_pdf(false, true, Some(&light.pos), &light_vs, &eye_vs, None).collect()
     };
 
     let pdfs_r: Vec<f32> = (0..=original_s + original_t - 2)
@@ -384,7 +438,11 @@ fn mis_weight(
             let t = original_s + original_t - s;
             assert!(t >= 2);
 
-            let c = strategy_weight(s, t).unwrap_or(0.0);
+            let c = if s == 0 && from_light {
+                0.0
+            } else {
+                strategy_weight(s, t).unwrap_or(0.0)
+            };
 
             if t >= eye_extended.len() + 2 {
                 return Some(0.0);
@@ -397,10 +455,11 @@ fn mis_weight(
             }
 
             *r_pdf /= eye_extended[t - 1].pdf_area_ratio;
-            if s == 1 {
-                *r_pdf *= light_pos_pdf;
-            } else if s == 2 {
-                *r_pdf *= light_dir_pdf;
+            //the light vertex is sampled from the eye vertex when s = 1,
+            //and independently as the start of the light subpath otherwise
+            let connection_pdf = if s == 1 { light_connection_pdf } else { 1.0 };
+            if s == 2 {
+                *r_pdf *= light_pos_pdf * light_dir_pdf;
             }
 
             if s >= 2 {
@@ -420,7 +479,7 @@ fn mis_weight(
             if s != 0 && (e_specular || l_specular) {
                 Some(0.0)
             } else {
-                Some(c * *r_pdf)
+                Some(c * *r_pdf * connection_pdf)
             }
         })
         .collect();
@@ -434,6 +493,20 @@ fn mis_weight(
     }
 }
 
+//events of the path made of eye_vs, the connection vertices and light_vs (reversed)
+fn path_events(
+    eye_vs: &[Vertex],
+    connection: &[lpe::Event],
+    light_vs: &[Vertex],
+) -> Vec<lpe::Event> {
+    let mut events = vec![lpe::Event::Eye];
+    events.extend(eye_vs.iter().map(|v| v.event));
+    events.extend_from_slice(connection);
+    events.extend(light_vs.iter().rev().map(|v| v.event));
+    events.push(lpe::Event::Light);
+    events
+}
+
 pub fn radiance<R: ?Sized>(
     scene: &Scene,
     ray: &Ray,
@@ -445,46 +518,33 @@ pub fn radiance<R: ?Sized>(
     //TODO: take acount of depth limits in MIS
     const LE_MAX: usize = 30;
     const LL_MAX: usize = 30;
-    let eye_vs = gen_vertices(scene, ray, true, LE_MAX, rng);
+    let eye_vs = gen_vertices(scene, ray, true, false, LE_MAX, rng);
     let len_e = eye_vs.len();
 
-    let light_sample = scene.sample_light(rng);
+    let light_sample = scene.sample_light(ray.time, ray.wavelengths.as_ref(), rng);
     if light_sample.is_none() {
         return;
     }
     let light_sample = light_sample.unwrap();
 
-    let initial_ray = light_sample.as_ref().and_then(
-        |scene::LightSampleResult {
-             pos: light_pos,
-             normal: light_normal,
-             emission: light_emission,
-             ..
-         }| {
-            pdf::CosUnitHemisphere::from_normal(light_normal)
-                .sample(rng)
-                .and_then(|v| {
-                    pdf::RandomBool { chance: 0.5 }
-                        .sample(rng)
-                        .map(|b| if b { -v } else { v })
-                })
-                .map(move |initial_outdir| {
-                    let light_emission_cos =
-                        *light_emission * initial_outdir.dot(light_normal).abs();
-                    let initial_ray = Ray::new(*light_pos, initial_outdir);
-                    (initial_ray, light_emission_cos)
-                })
-        },
-    );
-
-    let light_vs = gen_vertices(scene, &initial_ray.value.0, false, LL_MAX, rng);
+    let initial_ray = light_sample.as_ref().and_then(|light| {
+        scene
+            .sample_emission(light, ray.wavelengths.as_ref(), rng)
+            .map(|(initial_outdir, light_emission_cos)| {
+                let initial_ray = Ray::new(light.pos, initial_outdir)
+                    .with_time(ray.time)
+                    .with_wavelengths(ray.wavelengths);
+                (initial_ray, light_emission_cos)
+            })
+    });
+
+    let light_vs = gen_vertices(scene, &initial_ray.value.0, false, true, LL_MAX, rng);
     let len_l = light_vs.len();
 
     for len in 2..=len_e + len_l + 4 {
         let s_min = len - len.min(LE_MAX + 2);
         let s_max = (len - 2).min(LL_MAX + 2);
         assert!(s_min <= s_max);
-        let mut accum_len = RGB::all(0.0);
         for s in s_min..=s_max {
             let t = len - s;
             assert!(t >= 2);
@@ -497,47 +557,65 @@ pub fn radiance<R: ?Sized>(
             }
             let v_eye = &eye_vs[e_i];
 
-            let (contrib, mis_weight) = if s == 0 {
+            let (contrib, mis_weight, events) = if s == 0 {
                 if let Some(emission) = v_eye.hit.emission {
                     let mis_weight = mis_weight(scene, ray, &eye_vs[0..t - 1], &[], None);
-                    (emission * v_eye.throughput, mis_weight)
+                    let events = path_events(&eye_vs[0..t - 2], &[], &[]);
+                    (emission * v_eye.throughput, mis_weight, events)
                 } else {
-                    (RGB::all(0.0), 0.0)
+                    continue;
                 }
             } else if s == 1 {
                 let Vertex {
                     hit,
                     throughput,
                     w_local: wout_local,
+                    medium,
                     ..
                 } = v_eye;
-                let hit_lc = hit.geom.lc();
+                if v_eye.hit.material.all_specular() {
+                    continue;
+                }
+                let hit_lc = hit.lc();
+                let nee_sample = match scene.sample_light_from(
+                    hit.pos(),
+                    ray.time,
+                    ray.wavelengths.as_ref(),
+                    rng,
+                ) {
+                    Some(nee_sample) => nee_sample,
+                    None => continue,
+                };
                 let scene::LightSampleResult {
                     pos: ref light_pos,
                     normal: ref light_normal,
                     emission: light_emission,
                     ..
-                } = light_sample.value;
-                if v_eye.hit.material.all_specular() {
-                    continue;
-                } else if !scene.visible(light_pos, hit.pos()) {
+                } = nee_sample.value;
+                if !scene.visible(light_pos, hit.pos(), ray.time) {
                     continue;
                 } else {
-                    let g = hit.geom.g(light_pos, light_normal);
+                    let g = hit.g(light_pos, light_normal);
                     let light_dir = (light_pos - hit.pos()).normalize();
-                    let bsdf = hit
-                        .material
-                        .bsdf(&(hit_lc.w2l() * light_dir), &wout_local, false);
-                    let mis_weight = mis_weight(
-                        scene,
-                        ray,
-                        &eye_vs[0..t - 1],
+                    let win_local = hit_lc.w2l() * light_dir;
+                    let bsdf = hit.material.bsdf(&win_local, &wout_local, false);
+                    let tr = material::transmittance(
+                        hit.material
+                            .medium_toward(&win_local, wout_local, *medium)
+                            .as_ref(),
+                        (light_pos - hit.pos()).norm(),
+                    );
+                    let mis_weight =
+                        mis_weight(scene, ray, &eye_vs[0..t - 1], &[], Some(&nee_sample.value));
+                    let events = path_events(
+                        &eye_vs[0..t - 2],
+                        &[lpe::Event::scatter(false, &win_local, wout_local)],
                         &[],
-                        Some(&light_sample.value),
                     );
                     (
-                        light_emission * throughput * bsdf * g / light_sample.pdf,
+                        light_emission * throughput * bsdf * tr * g / nee_sample.pdf,
                         mis_weight,
+                        events,
                     )
                 }
             } else {
@@ -550,6 +628,7 @@ pub fn radiance<R: ?Sized>(
                     hit: e_hit,
                     throughput: e_throughput,
                     w_local: e_wout_local,
+                    medium: e_medium,
                     ..
                 } = v_eye;
                 let Vertex {
@@ -562,15 +641,24 @@ pub fn radiance<R: ?Sized>(
                     continue;
                 } else if v_light.hit.material.all_specular() {
                     continue;
-                } else if !scene.visible(e_hit.pos(), l_hit.pos()) {
+                } else if !scene.visible(e_hit.pos(), l_hit.pos(), ray.time) {
                     continue;
                 }
                 let e_to_l = (l_hit.pos() - e_hit.pos()).normalize();
-                let e_win_local = e_hit.geom.lc().w2l() * e_to_l;
-                let l_wout_local = l_hit.geom.lc().w2l() * -e_to_l;
-                let g = e_hit.geom.g(l_hit.pos(), &l_hit.geom.gnorm);
-                let l_bsdf = l_hit.material.bsdf(&l_win_local, &l_wout_local, false);
+                let (e_lc, l_lc) = (e_hit.lc(), l_hit.lc());
+                let e_win_local = e_lc.w2l() * e_to_l;
+                let l_wout_local = l_lc.w2l() * -e_to_l;
+                let g = e_hit.g(l_hit.pos(), &l_hit.snorm);
+                let l_bsdf = l_hit.material.bsdf(&l_win_local, &l_wout_local, false)
+                    * l_hit.adjoint_correction(&(l_lc.l2w() * l_win_local), &-e_to_l);
                 let e_bsdf = e_hit.material.bsdf(&e_win_local, &e_wout_local, false);
+                let tr = material::transmittance(
+                    e_hit
+                        .material
+                        .medium_toward(&e_win_local, e_wout_local, *e_medium)
+                        .as_ref(),
+                    (l_hit.pos() - e_hit.pos()).norm(),
+                );
                 let mis_weight = mis_weight(
                     scene,
                     ray,
@@ -578,15 +666,27 @@ pub fn radiance<R: ?Sized>(
                     &light_vs[0..s - 1],
                     Some(&light_sample.value),
                 );
-                let contrib =
-                    *l_throughput * l_bsdf * g * e_bsdf * e_throughput * initial_ray.value.1
+                let mut contrib =
+                    *l_throughput * l_bsdf * tr * g * e_bsdf * e_throughput * initial_ray.value.1
                         / initial_ray.pdf;
-                (contrib, mis_weight)
+                let single =
+                    |hit: &object::ObjectHit| matches!(hit.wavelengths, Some(w) if w.single);
+                if single(e_hit) && single(l_hit) {
+                    //both subpaths have taken over the weight of the secondary wavelengths
+                    contrib /= spectrum::N_WAVELENGTHS as f32;
+                }
+                let events = path_events(
+                    &eye_vs[0..t - 2],
+                    &[
+                        lpe::Event::scatter(false, &e_win_local, e_wout_local),
+                        lpe::Event::scatter(false, l_win_local, &l_wout_local),
+                    ],
+                    &light_vs[0..s - 2],
+                );
+                (contrib, mis_weight, events)
             };
 
-            accum_len += contrib * mis_weight;
+            radiance_accum.accum_path(&(contrib * mis_weight), &events);
         }
-
-        radiance_accum.accum(&(accum_len, len - 2));
     }
 }
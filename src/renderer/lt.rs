@@ -15,13 +15,14 @@ impl RandomPixelIntegrator for LightTracer {
         camera: &C,
         film: &mut FilmVec<T>,
         accum_init: &T,
+        wavelengths: Option<spectrum::Wavelengths>,
         rng: &mut R,
     ) where
         R: Rng + ?Sized,
         C: Camera + ?Sized,
         T: Clone + Accumulator,
     {
-        crate::renderer::lt::sample(scene, camera, film, accum_init, rng);
+        crate::renderer::lt::sample(scene, camera, film, accum_init, wavelengths, rng);
     }
 }
 
@@ -30,6 +31,7 @@ pub fn sample<R, C, T>(
     camera: &C,
     film: &mut FilmVec<T>,
     _accum_init: &T,
+    wavelengths: Option<spectrum::Wavelengths>,
     rng: &mut R,
 ) where
     R: Rng + ?Sized,
@@ -40,43 +42,37 @@ pub fn sample<R, C, T>(
     let film_area = camera.film_width() * camera.film_width() * film.h() as f32 / film.w() as f32;
     let pixel_area = film_area / n_pixels as f32;
 
+    let time = camera.sample_time(rng);
+
     //sample a light point from the scene
-    let light_sample = if let Some(l) = scene.sample_light(rng) {
+    let light_sample = if let Some(l) = scene.sample_light(time, wavelengths.as_ref(), rng) {
         l
     } else {
         return;
     };
 
     //generate path
-    let initial_ray = light_sample.as_ref().and_then(
-        |scene::LightSampleResult {
-             pos: light_pos,
-             normal: light_normal,
-             emission: light_emission,
-             ..
-         }| {
-            pdf::CosUnitHemisphere::from_normal(light_normal)
-                .sample(rng)
-                .and_then(|v| {
-                    pdf::RandomBool { chance: 0.5 }
-                        .sample(rng)
-                        .map(|b| if b { -v } else { v })
-                })
-                .map(move |initial_outdir| {
-                    let light_emission_cos =
-                        *light_emission * initial_outdir.dot(light_normal).abs();
-                    let initial_ray = Ray::new(*light_pos, initial_outdir);
-                    (initial_ray, light_emission_cos)
-                })
-        },
-    );
+    let initial_ray = light_sample.as_ref().and_then(|light| {
+        scene.sample_emission(light, wavelengths.as_ref(), rng).map(
+            |(initial_outdir, light_emission_cos)| {
+                let initial_ray = Ray::new(light.pos, initial_outdir)
+                    .with_time(time)
+                    .with_wavelengths(wavelengths);
+                (initial_ray, light_emission_cos)
+            },
+        )
+    });
     const MAX_DEPTH: usize = 25;
-    let vs = gen_vertices(scene, &initial_ray.value.0, false, MAX_DEPTH, rng);
+    let vs = gen_vertices(scene, &initial_ray.value.0, false, true, MAX_DEPTH, rng);
 
     for s in 1..=vs.len() + 1 {
         if s > 2 && vs[s - 2].specular {
             continue;
         }
+        //the camera can not see delta lights as other integrators
+        if s == 1 && light_sample.value.delta {
+            continue;
+        }
 
         //position of vertex
         let pos = if s == 1 {
@@ -85,7 +81,7 @@ pub fn sample<R, C, T>(
             vs[s - 2].pos()
         };
 
-        let film_sample = camera.sample_film_uv(pos, rng);
+        let film_sample = camera.sample_film_uv_at(pos, time, rng);
         //skip if the vertex is not visible from the camera
         if film_sample.is_none() {
             continue;
@@ -102,31 +98,54 @@ pub fn sample<R, C, T>(
             ixs.unwrap()
         };
         //skip if the vertex is occluded
-        if !scene.visible(&film_sample.lens_point, pos) {
+        if !scene.visible(&film_sample.lens_point, pos, time) {
             continue;
         }
 
-        let (radiance, cos) = if s == 1 {
-            let cos = (light_sample.value.pos - film_sample.lens_point)
-                .normalize()
-                .dot(&light_sample.value.normal)
-                .abs();
-            (light_sample.value.emission / light_sample.pdf, cos)
+        let (radiance, cos, events) = if s == 1 {
+            //the cosine at the light is included in the emission
+            let dir = (film_sample.lens_point - light_sample.value.pos).normalize();
+            let emission_cos =
+                scene.emission_cos_toward(&light_sample.value, &dir, wavelengths.as_ref());
+            (
+                emission_cos / light_sample.pdf,
+                1.0,
+                vec![lpe::Event::Eye, lpe::Event::Light],
+            )
         } else {
             let vertex = &vs[s - 2];
 
             let win_local = vertex.w_local;
             let wout = (film_sample.lens_point - vertex.pos()).normalize();
-            let wout_local = vertex.hit.geom.lc().w2l() * wout;
-            let bsdf = vertex.hit.material.bsdf(&win_local, &wout_local, false);
+            let lc = vertex.hit.lc();
+            let wout_local = lc.w2l() * wout;
+            let bsdf = vertex.hit.material.bsdf(&win_local, &wout_local, false)
+                * vertex
+                    .hit
+                    .adjoint_correction(&(lc.l2w() * win_local), &wout);
+            let tr = material::transmittance(
+                vertex
+                    .hit
+                    .material
+                    .medium_toward(&wout_local, &win_local, vertex.medium)
+                    .as_ref(),
+                (film_sample.lens_point - vertex.pos()).norm(),
+            );
 
-            let radiance = initial_ray.value.1 / initial_ray.pdf * vertex.throughput * bsdf;
-            (radiance, wout_local[2].abs())
+            let radiance = initial_ray.value.1 / initial_ray.pdf * vertex.throughput * bsdf * tr;
+            let mut events = vec![
+                lpe::Event::Eye,
+                lpe::Event::scatter(false, &win_local, &wout_local),
+            ];
+            events.extend(vs[0..s - 2].iter().rev().map(|v| v.event));
+            events.push(lpe::Event::Light);
+            (radiance, wout_local[2].abs(), events)
         };
 
         //dA(x_film) = measure_conv * cos * dA(x)
         let contrib = radiance * film_sample.measure_conv * cos / pixel_area;
+        let contrib = wavelengths.map_or(contrib, |w| w.to_rgb(&contrib));
         film.at_mut(ix_x as usize, ix_y as usize)
-            .accum(&(contrib, s - 1));
+            .accum_path(&contrib, &events);
     }
 }
@@ -31,46 +31,68 @@ pub fn radiance<R: ?Sized>(
     let mut throughput = RGB::all(1.0);
     let mut prev_specular = true;
     let mut last_ray_pdf = 1.0;
+    let mut events = vec![lpe::Event::Eye];
+    //absorbing medium the ray travels in
+    let mut medium: Option<RGB> = None;
 
     const DEPTH_MAX: usize = 100;
     const MIS_PDF_WEIGHT_PT: f32 = 1.0;
     const MIS_PDF_WEIGHT_NEE: f32 = 1.0;
-    for depth in 0..DEPTH_MAX {
+    for _ in 0..DEPTH_MAX {
         let hit = scene.test_hit(&ray, 1e-3, std::f32::MAX / 2.0);
         if let Some(hit) = hit {
-            let hit_lc = hit.geom.lc();
+            throughput *= material::transmittance(medium.as_ref(), hit.geom.dist);
+            let hit_lc = hit.lc();
             let wout_local = hit_lc.w2l() * -ray.dir;
 
             if let Some(emission) = hit.emission {
+                events.push(lpe::Event::Light);
                 if prev_specular || !enable_nee {
-                    radiance_accum.accum(&(throughput * emission, depth));
+                    radiance_accum.accum_path(&(throughput * emission), &events);
                 } else {
                     let pt_pdf_omega = last_ray_pdf;
-                    let pt_pdf_area =
-                        pt_pdf_omega * wout_local[2].abs() / hit.geom.dist / hit.geom.dist;
-                    let nee_pdf_area = scene.sample_light_pdf(hit.pos(), hit.obj_ix);
+                    let pt_pdf_area = pt_pdf_omega * ray.dir.dot(&hit.geom.gnorm).abs()
+                        / hit.geom.dist
+                        / hit.geom.dist;
+                    let nee_pdf_area = scene.sample_light_from_pdf(
+                        &ray.origin,
+                        hit.pos(),
+                        scene::LightSource::Object(hit.obj_ix),
+                        ray.time,
+                    );
                     let mis_weight = MIS_PDF_WEIGHT_PT * pt_pdf_area
                         / (MIS_PDF_WEIGHT_PT * pt_pdf_area + MIS_PDF_WEIGHT_NEE * nee_pdf_area);
-                    radiance_accum.accum(&(throughput * emission * mis_weight, depth));
+                    radiance_accum.accum_path(&(throughput * emission * mis_weight), &events);
                 }
+                events.pop();
             }
 
             if enable_nee && !hit.material.all_specular() {
-                if let Some(light_sample) = scene.sample_light(rng) {
+                if let Some(light_sample) =
+                    scene.sample_light_from(hit.pos(), ray.time, ray.wavelengths.as_ref(), rng)
+                {
                     let scene::LightSampleResult {
                         pos: ref light_pos,
                         normal: ref light_normal,
                         emission: light_emission,
+                        delta: light_delta,
                         ..
                     } = light_sample.value;
                     //dbg!(light_sample.pdf);
                     //dbg!(scene.sample_light_pdf(&light_pos, obj_ix));
-                    if scene.visible(light_pos, hit.pos()) {
-                        let g = hit.geom.g(&light_pos, light_normal);
+                    if scene.visible(light_pos, hit.pos(), ray.time) {
+                        let g = hit.g(&light_pos, light_normal);
                         let light_dir = (light_pos - hit.pos()).normalize();
                         let win_local = hit_lc.w2l() * light_dir;
                         let bsdf = hit.material.bsdf(&win_local, &wout_local, false);
-                        let nee_contrib = throughput * light_emission * bsdf * g / light_sample.pdf;
+                        let nee_medium =
+                            hit.material.medium_toward(&win_local, &wout_local, medium);
+                        let tr = material::transmittance(
+                            nee_medium.as_ref(),
+                            (light_pos - hit.pos()).norm(),
+                        );
+                        let nee_contrib =
+                            throughput * light_emission * bsdf * tr * g / light_sample.pdf;
                         if !nee_contrib.is_finite() {
                             warn!("nee_radiance is not finite {:?}", nee_contrib);
                             warn!("> throughput {:?}", throughput);
@@ -89,10 +111,18 @@ pub fn radiance<R: ?Sized>(
                                 rr_chance * pt_pdf_omega * (light_normal.dot(&light_dir)).abs()
                                     / sq_distance;
                             let nee_pdf_area = light_sample.pdf;
-                            let mis_weight = MIS_PDF_WEIGHT_NEE * nee_pdf_area
-                                / (MIS_PDF_WEIGHT_PT * pt_pdf_area
-                                    + MIS_PDF_WEIGHT_NEE * nee_pdf_area);
-                            radiance_accum.accum(&(nee_contrib * mis_weight, depth + 1));
+                            //paths can not reach delta lights
+                            let mis_weight = if light_delta {
+                                1.0
+                            } else {
+                                MIS_PDF_WEIGHT_NEE * nee_pdf_area
+                                    / (MIS_PDF_WEIGHT_PT * pt_pdf_area
+                                        + MIS_PDF_WEIGHT_NEE * nee_pdf_area)
+                            };
+                            events.push(lpe::Event::scatter(false, &win_local, &wout_local));
+                            events.push(lpe::Event::Light);
+                            radiance_accum.accum_path(&(nee_contrib * mis_weight), &events);
+                            events.truncate(events.len() - 2);
                         }
                     }
                 }
@@ -108,6 +138,8 @@ pub fn radiance<R: ?Sized>(
             //    //dbg!((win_local[2].abs() / std::f32::consts::PI, bsdf_cos.r, next.pdf));
             //}
             prev_specular = next.value.2;
+            events.push(lpe::Event::scatter(prev_specular, &win_local, &wout_local));
+            medium = hit.material.medium_toward(&win_local, &wout_local, medium);
             throughput *= bsdf_cos;
             throughput /= next.pdf;
             last_ray_pdf *= next.pdf;
@@ -135,10 +167,230 @@ pub fn radiance<R: ?Sized>(
                 break;
             }
 
-            ray = hit_lc.l2w() * Ray::new(P3::origin(), win_local);
+            ray = hit_lc.l2w()
+                * Ray::new(P3::origin(), win_local)
+                    .with_time(ray.time)
+                    .with_wavelengths(hit.wavelengths);
+
+            //the paths entering a subsurface material continue from where they leave it
+            if let Some(medium) = hit.material.subsurface() {
+                if wout_local[2] > 0.0 && win_local[2] < 0.0 {
+                    match random_walk(scene, medium, ray, &mut throughput, &mut events, rng) {
+                        Some(exit_ray) => ray = exit_ray,
+                        None => break,
+                    }
+                    prev_specular = true;
+                }
+            }
         } else {
-            radiance_accum.accum(&(scene.envmap_dir(&ray.dir) * throughput, depth));
+            //through an open boundary, the medium extends to the infinity
+            throughput *= material::transmittance(medium.as_ref(), f32::INFINITY);
+            events.push(lpe::Event::Light);
+            let (light_radiance, nee_pdf_omega) =
+                scene.escaped_radiance(&ray.dir, ray.wavelengths.as_ref());
+            let mis_weight = if prev_specular || !enable_nee || nee_pdf_omega == 0.0 {
+                1.0
+            } else {
+                MIS_PDF_WEIGHT_PT * last_ray_pdf
+                    / (MIS_PDF_WEIGHT_PT * last_ray_pdf + MIS_PDF_WEIGHT_NEE * nee_pdf_omega)
+            };
+            let radiance =
+                scene.envmap_dir(&ray.dir, ray.wavelengths.as_ref()) + light_radiance * mis_weight;
+            radiance_accum.accum_path(&(radiance * throughput), &events);
             break;
         }
     }
 }
+
+//walks the ray inside the medium until it leaves through a subsurface boundary, which
+//reflects or refracts it by the Fresnel reflectance. the walk ends at other objects too.
+//returns the ray leaving the medium, or None when the path is terminated.
+fn random_walk<R: Rng + ?Sized>(
+    scene: &Scene,
+    medium: &material::materials::Subsurface,
+    mut ray: Ray,
+    throughput: &mut RGB,
+    events: &mut Vec<lpe::Event>,
+    rng: &mut R,
+) -> Option<Ray> {
+    use material::materials::{fresnel_dielectric, reflect, refract};
+    let sigma_t = medium.sigma_t();
+    let sigma_s = medium.albedo * sigma_t;
+    let transmittance = |dist: f32| {
+        RGB::new(
+            (-sigma_t.r * dist).exp(),
+            (-sigma_t.g * dist).exp(),
+            (-sigma_t.b * dist).exp(),
+        )
+    };
+    let mean = |c: RGB| (c.r + c.g + c.b) / 3.0;
+    //the distances are sampled for a channel chosen uniformly for the walk, so its pdf is the
+    //mean of the ones of the channels. both are kept relative to the mean to avoid underflow.
+    let sigma = [sigma_t.r, sigma_t.g, sigma_t.b][rng.gen_range(0, 3)];
+    let start = *throughput;
+    let (mut f, mut p) = (RGB::all(1.0), RGB::all(1.0));
+    let mut rr_pdf = 1.0;
+    //only the rays starting on the boundary skip it
+    let mut tnear = 1e-3;
+    loop {
+        let dist = -(1.0 - rng.gen::<f32>()).ln() / sigma;
+        //the walk can not leave an open surface
+        let hit = scene.test_hit(&ray, tnear, f32::MAX / 2.0)?;
+        let (f_step, p_step) = if hit.geom.dist < dist {
+            let tr = transmittance(hit.geom.dist);
+            (tr, tr)
+        } else {
+            let tr = transmittance(dist);
+            (sigma_s * tr, sigma_t * tr)
+        };
+        f *= f_step;
+        p *= p_step;
+        let m = mean(p);
+        f /= m;
+        p /= m;
+        *throughput = start * f / rr_pdf;
+
+        if hit.geom.dist < dist {
+            if hit.material.subsurface().is_none() {
+                return Some(ray);
+            }
+            //the reflectance is the probability of reflecting, so the weights cancel
+            let (w, n) = (-ray.dir, -hit.geom.gnorm);
+            let refracted = refract(&w, &n, medium.index);
+            let reflectance = fresnel_dielectric(w.dot(&n), medium.index, 1.0);
+            let (dir, transmission) = match refracted {
+                Some(dir) if rng.gen::<f32>() >= reflectance => (dir, true),
+                _ => (reflect(&w, &n), false),
+            };
+            events.push(lpe::Event::Scatter {
+                specular: true,
+                transmission,
+            });
+            ray = Ray::new(*hit.pos(), dir)
+                .with_time(ray.time)
+                .with_wavelengths(ray.wavelengths);
+            if transmission {
+                return Some(ray);
+            }
+            tnear = 1e-3;
+        } else {
+            //scattering in the volume counts as diffuse
+            events.push(lpe::Event::Scatter {
+                specular: false,
+                transmission: false,
+            });
+            ray = Ray::new(ray.at(dist), medium.sample_phase(&ray.dir, rng))
+                .with_time(ray.time)
+                .with_wavelengths(ray.wavelengths);
+            tnear = 0.0;
+        }
+
+        let cont = pdf::RandomBool {
+            chance: (throughput.max() * 0.8).clamp(0.1, 1.0),
+        }
+        .sample(rng);
+        if !cont.value || !throughput.is_finite() {
+            return None;
+        }
+        rr_pdf *= cont.pdf;
+        *throughput /= cont.pdf;
+    }
+}
+
+#[cfg(test)]
+fn uniform_envmap() -> image::Image {
+    let mut envmap = image::Image::new(4, 2);
+    for x in 0..4 {
+        for y in 0..2 {
+            *envmap.at_mut(x, y) = RGB::all(1.0);
+        }
+    }
+    envmap
+}
+
+#[test]
+fn test_subsurface_furnace() {
+    //a medium which does not absorb keeps the radiance of a uniform environment
+    let mut rng = SmallRng::seed_from_u64(0);
+    let medium = material::materials::Subsurface::new(RGB::all(1.0), RGB::new(0.1, 0.2, 0.4), 1.4);
+    //and the share of the paths reflected at the surface is bounded
+    let materials = vec![
+        (medium.clone().into(), 0.2),
+        (
+            material::Material::mix(
+                0.5,
+                medium.into(),
+                material::Material::new_mirror(RGB::all(1.0)),
+            ),
+            0.6,
+        ),
+    ];
+    for (material, max_short) in materials {
+        let object = object::SimpleObject {
+            shape: shape::shapes::Sphere {
+                center: P3::origin(),
+                radius: 1.0,
+            }
+            .into(),
+            material,
+            emission: None,
+            motion: None,
+            normal_map: None,
+            tangent_map: None,
+            alpha: None,
+        };
+        let scene = Scene::new(vec![object]).set_sphere_envmap(uniform_envmap());
+        for &x in [0.0, 0.5, 0.9].iter() {
+            let ray = Ray::new(P3::new(x, 0.0, 5.0), -V3::z());
+            let n = 4000;
+            //the paths through the medium have events for the vertices inside
+            let mut sum = (RGB::all(0.0), vec![RGB::all(0.0); 3]);
+            for _ in 0..n {
+                radiance(false, &scene, &ray, &mut sum, &mut rng);
+            }
+            let mean = sum.0 / n as f32;
+            assert!(
+                (mean.r - 1.0).abs() < 0.05 && (mean.b - 1.0).abs() < 0.05,
+                "{:?}",
+                mean
+            );
+            let short = sum.1.iter().fold(RGB::all(0.0), |a, b| a + *b) / n as f32;
+            assert!(short.r < max_short, "{:?}", short);
+        }
+    }
+}
+
+#[test]
+fn test_escape_in_medium() {
+    //the path entering an open surface never leaves the medium
+    let mut rng = SmallRng::seed_from_u64(0);
+    let object = object::SimpleObject {
+        shape: shape::shapes::Parallelogram::new_rectangle(
+            &P3::new(-1.0, -1.0, 0.0),
+            &V3::new(2.0, 0.0, 0.0),
+            &V3::new(0.0, 2.0, 0.0),
+        )
+        .into(),
+        material: material::materials::Transparent {
+            color: RGB::all(1.0),
+            index: 1.0,
+            dispersion: None,
+            film: None,
+            absorption: RGB::new(1.0, 0.0, 0.0),
+        }
+        .into(),
+        emission: None,
+        motion: None,
+        normal_map: None,
+        tangent_map: None,
+        alpha: None,
+    };
+    let scene = Scene::new(vec![object]).set_sphere_envmap(uniform_envmap());
+    let mut sum = RGB::all(0.0);
+    for _ in 0..1000 {
+        let ray = Ray::new(P3::new(0.0, 0.0, 1.0), -V3::z());
+        radiance(false, &scene, &ray, &mut sum, &mut rng);
+    }
+    assert_eq!(sum.r, 0.0);
+    assert!((sum.g / 1000.0 - 1.0).abs() < 0.1);
+}
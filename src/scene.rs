@@ -9,30 +9,57 @@ enum EnvMap {
 
 pub struct Scene {
     bvh: object::BVH,
-    lights: Vec<usize>,
+    lights: Vec<light::Light>,
+    //emissive objects and lights, from which light samples are chosen uniformly
+    light_sources: Vec<LightSource>,
+    bounds: light::BoundingSphere,
     envmap: Option<EnvMap>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightSource {
+    Object(usize),
+    Light(usize),
+}
+
 pub struct LightSampleResult {
     pub pos: P3,
     pub normal: V3,
+    //radiance or intensity toward the point by 'sample_light_from'.
+    //by 'sample_light', the color of emissive objects before their profile.
     pub emission: RGB,
-    pub obj_ix: usize,
+    pub source: LightSource,
+    //true if rays can not hit the vertex
+    pub delta: bool,
 }
+
 impl Scene {
     pub fn new(objects: Vec<object::SimpleObject>) -> Self {
         let bvh = object::BVH::new(objects);
         let objects = bvh.objects();
-        let lights = (0..objects.len())
+        let light_sources = (0..objects.len())
             .filter(|i| objects[*i].emission.is_some())
+            .map(LightSource::Object)
             .collect();
+        let aabb = objects[1..]
+            .iter()
+            .fold(objects[0].aabb(), |aabb, o| aabb.merge(&o.aabb()));
         Scene {
             bvh,
-            lights,
+            lights: vec![],
+            light_sources,
+            bounds: light::BoundingSphere::around(&aabb),
             envmap: None,
         }
     }
 
+    pub fn add_light(mut self, light: impl Into<light::Light>) -> Self {
+        self.light_sources
+            .push(LightSource::Light(self.lights.len()));
+        self.lights.push(light.into());
+        self
+    }
+
     pub fn set_sphere_envmap(mut self, envmap: image::Image) -> Self {
         self.envmap = Some(EnvMap::Sphere(envmap));
         self
@@ -43,10 +70,10 @@ impl Scene {
         self
     }
 
-    pub fn envmap_dir(&self, dir: &V3) -> RGB {
+    pub fn envmap_dir(&self, dir: &V3, wavelengths: Option<&spectrum::Wavelengths>) -> RGB {
         use EnvMap::*;
         let dir = dir.normalize();
-        match &self.envmap {
+        let rgb = match &self.envmap {
             Some(Sphere(image)) => {
                 let r = std::f32::consts::FRAC_1_PI * dir[2].acos()
                     / (dir[0] * dir[0] + dir[1] * dir[1]).sqrt();
@@ -60,44 +87,218 @@ impl Scene {
                 *image.at_uv(u, v)
             }
             _ => RGB::all(0.0),
-        }
+        };
+        wavelengths.map_or(rgb, |w| w.uplift(&rgb))
     }
 
-    pub fn sample_light<R: ?Sized>(&self, rng: &mut R) -> Option<pdf::PdfSample<LightSampleResult>>
+    fn object_emission(&self, obj_ix: usize) -> &emission::Emission {
+        self.bvh.objects()[obj_ix].emission.as_ref().unwrap()
+    }
+
+    //chooses a light source and samples a vertex on it by 'sample_object' or 'sample_light'.
+    //the emission of objects is the radiance toward 'toward', or before the profile without it.
+    fn sample_light_by<R, F, G>(
+        &self,
+        toward: Option<&P3>,
+        time: f32,
+        wavelengths: Option<&spectrum::Wavelengths>,
+        rng: &mut R,
+        sample_object: F,
+        sample_light: G,
+    ) -> Option<pdf::PdfSample<LightSampleResult>>
     where
-        R: Rng,
+        R: Rng + ?Sized,
+        F: FnOnce(&object::SimpleObject, &mut R) -> pdf::PdfSample<(P3, V3)>,
+        G: FnOnce(&light::Light, &mut R) -> pdf::PdfSample<(P3, V3, RGB)>,
     {
         use pdf::*;
 
-        self.lights.choose_pdf(rng).map(|ix| {
-            ix.and_then(|obj_ix| {
-                let obj = &self.bvh.objects()[*obj_ix];
-                let emission = obj.emission.unwrap();
-                obj.shape
-                    .sample_surface(rng)
-                    .map(|(pos, normal)| LightSampleResult {
+        let source = self.light_sources.choose_pdf(rng)?;
+        Some(source.and_then(|source| match *source {
+            LightSource::Object(obj_ix) => {
+                let obj = &self.bvh.objects()[obj_ix];
+                let e = self.object_emission(obj_ix);
+                sample_object(obj, rng).map(|(pos, normal)| {
+                    let uv = obj.uv(&pos, time);
+                    let mut emission = e.color_at(&uv, wavelengths) * obj.opacity(&uv);
+                    if let Some(point) = toward {
+                        emission *= e.profile_at((point - pos).normalize().dot(&normal));
+                    }
+                    LightSampleResult {
                         pos,
                         normal,
                         emission,
-                        obj_ix: *obj_ix,
-                    })
-            })
-        })
+                        source: *source,
+                        delta: false,
+                    }
+                })
+            }
+            LightSource::Light(light_ix) => {
+                let light = &self.lights[light_ix];
+                sample_light(light, rng).map(|(pos, normal, emission)| LightSampleResult {
+                    pos,
+                    normal,
+                    emission: wavelengths.map_or(emission, |w| w.uplift(&emission)),
+                    source: *source,
+                    delta: light.is_delta(),
+                })
+            }
+        }))
+    }
+
+    pub fn sample_light<R: ?Sized>(
+        &self,
+        time: f32,
+        wavelengths: Option<&spectrum::Wavelengths>,
+        rng: &mut R,
+    ) -> Option<pdf::PdfSample<LightSampleResult>>
+    where
+        R: Rng,
+    {
+        self.sample_light_by(
+            None,
+            time,
+            wavelengths,
+            rng,
+            |obj, rng| obj.sample_surface(time, rng),
+            |light, rng| {
+                light
+                    .sample_vertex(&self.bounds, rng)
+                    .map(|(pos, normal)| (pos, normal, light.color()))
+            },
+        )
     }
 
-    pub fn sample_light_pdf(&self, pos: &P3, obj_ix: usize) -> f32 {
-        let obj = &self.bvh.objects()[obj_ix];
-        obj.shape.sample_surface_pdf(pos) / self.lights.len() as f32
+    pub fn sample_light_pdf(&self, pos: &P3, source: LightSource, time: f32) -> f32 {
+        let pdf = match source {
+            LightSource::Object(obj_ix) => self.bvh.objects()[obj_ix].sample_surface_pdf(pos, time),
+            LightSource::Light(light_ix) => {
+                self.lights[light_ix].sample_vertex_pdf(pos, &self.bounds)
+            }
+        };
+        pdf / self.light_sources.len() as f32
+    }
+
+    //samples a point on a light to be connected to 'point'
+    pub fn sample_light_from<R>(
+        &self,
+        point: &P3,
+        time: f32,
+        wavelengths: Option<&spectrum::Wavelengths>,
+        rng: &mut R,
+    ) -> Option<pdf::PdfSample<LightSampleResult>>
+    where
+        R: Rng + ?Sized,
+    {
+        self.sample_light_by(
+            Some(point),
+            time,
+            wavelengths,
+            rng,
+            |obj, rng| obj.sample_from(point, time, rng),
+            |light, rng| light.sample_from(point, &self.bounds, rng),
+        )
+    }
+
+    pub fn sample_light_from_pdf(
+        &self,
+        point: &P3,
+        pos: &P3,
+        source: LightSource,
+        time: f32,
+    ) -> f32 {
+        let pdf = match source {
+            LightSource::Object(obj_ix) => {
+                self.bvh.objects()[obj_ix].sample_from_pdf(point, pos, time)
+            }
+            LightSource::Light(light_ix) => {
+                self.lights[light_ix].sample_from_pdf(point, pos, &self.bounds)
+            }
+        };
+        pdf / self.light_sources.len() as f32
+    }
+
+    //samples the direction of light emitted from a vertex by 'sample_light',
+    //with the emission multiplied by the cosine
+    pub fn sample_emission<R: Rng + ?Sized>(
+        &self,
+        light: &LightSampleResult,
+        wavelengths: Option<&spectrum::Wavelengths>,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(V3, RGB)> {
+        match light.source {
+            LightSource::Object(obj_ix) => self
+                .object_emission(obj_ix)
+                .sample_dir(&light.normal, rng)
+                .map(|(dir, profile_cos)| (dir, light.emission * profile_cos)),
+            LightSource::Light(light_ix) => self.lights[light_ix]
+                .sample_dir(rng)
+                .map(|(dir, e)| (dir, wavelengths.map_or(e, |w| w.uplift(&e)))),
+        }
+    }
+
+    //the emission multiplied by the cosine from a vertex by 'sample_light' toward 'dir'
+    pub fn emission_cos_toward(
+        &self,
+        light: &LightSampleResult,
+        dir: &V3,
+        wavelengths: Option<&spectrum::Wavelengths>,
+    ) -> RGB {
+        match light.source {
+            LightSource::Object(obj_ix) => {
+                let cos = dir.dot(&light.normal);
+                light.emission * self.object_emission(obj_ix).profile_at(cos) * cos.abs()
+            }
+            LightSource::Light(light_ix) => {
+                let e = self.lights[light_ix].emission_cos(dir);
+                wavelengths.map_or(e, |w| w.uplift(&e))
+            }
+        }
+    }
+
+    //radiance of the lights seen by a ray hitting nothing, and the solid-angle pdf of
+    //sampling its direction by 'sample_light_from'
+    pub fn escaped_radiance(
+        &self,
+        dir: &V3,
+        wavelengths: Option<&spectrum::Wavelengths>,
+    ) -> (RGB, f32) {
+        let (radiance, pdf) = self.lights.iter().filter(|light| !light.is_delta()).fold(
+            (RGB::all(0.0), 0.0),
+            |(radiance, pdf), light| {
+                //the lights sample directions from points as they emit
+                (
+                    radiance + light.emission_toward(&-dir),
+                    pdf + light.sample_dir_pdf(&-dir),
+                )
+            },
+        );
+        (
+            wavelengths.map_or(radiance, |w| w.uplift(&radiance)),
+            pdf / self.light_sources.len() as f32,
+        )
+    }
+
+    //whether some object scatters light inside, which only the path tracers support
+    pub fn has_subsurface(&self) -> bool {
+        self.bvh
+            .objects()
+            .iter()
+            .any(|o| o.material.subsurface().is_some())
     }
 
     pub fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<object::ObjectHit> {
-        self.bvh.test_hit(ray, tnear, tfar)
+        let hit = self.bvh.test_hit(ray, tnear, tfar);
+        match &ray.wavelengths {
+            Some(w) => hit.map(|hit| hit.at_wavelengths(w)),
+            None => hit,
+        }
     }
 
-    pub fn visible(&self, x: &P3, y: &P3) -> bool {
+    pub fn visible(&self, x: &P3, y: &P3, time: f32) -> bool {
         let r = y - x;
         let dist = r.norm();
-        let ray = Ray::new(*x, r / dist);
+        let ray = Ray::new(*x, r / dist).with_time(time);
         self.test_hit(&ray, 1e-3, dist - 1e-3).is_none()
     }
 }
@@ -1,4 +1,5 @@
 use crate::*;
+use nalgebra::{Isometry3, Vector3};
 use rand::prelude::Rng;
 
 #[derive(Debug, Clone)]
@@ -110,9 +111,9 @@ impl AABB {
     pub fn iter_vertices<'a>(&'a self) -> impl Iterator<Item = P3> + 'a {
         let diag_vertices = [self.mins, self.maxs];
         (0..8).map(move |i| {
-            let x = i ^ 2;
-            let y = (i >> 1) ^ 1;
-            let z = (i >> 2) ^ 1;
+            let x = i & 1;
+            let y = (i >> 1) & 1;
+            let z = (i >> 2) & 1;
             let mut v = P3::origin();
             v[0] = diag_vertices[x][0];
             v[1] = diag_vertices[y][1];
@@ -121,6 +122,13 @@ impl AABB {
         })
     }
 
+    //bounds the box transformed by the isometry
+    pub fn transformed(&self, iso: &Isometry3<f32>) -> Self {
+        let mut vs = self.iter_vertices().map(|v| iso * v);
+        let first = vs.next().unwrap();
+        vs.fold(AABB::single_point(&first), |b, v| b.include_nomargin(&v))
+    }
+
     pub fn ray_intersect(&self, ray: &Ray, mut tnear: f32, mut tfar: f32) -> Option<(f32, f32)> {
         let mut axis = [0, 1, 2];
         axis.sort_by(|i, j| ray.dir[*j].abs().partial_cmp(&ray.dir[*i].abs()).unwrap());
@@ -160,6 +168,20 @@ impl AABB {
     }
 }
 
+#[test]
+fn test_aabb_vertices() {
+    let aabb = AABB::new(&P3::new(0.0, 1.0, 2.0), &P3::new(3.0, 4.0, 5.0));
+    let mut vertices: Vec<[f32; 3]> = aabb.iter_vertices().map(|v| [v[0], v[1], v[2]]).collect();
+    vertices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    vertices.dedup();
+    assert_eq!(vertices.len(), 8);
+    for v in vertices {
+        assert!(v[0] == 0.0 || v[0] == 3.0);
+        assert!(v[1] == 1.0 || v[1] == 4.0);
+        assert!(v[2] == 2.0 || v[2] == 5.0);
+    }
+}
+
 trait ShapeImpl {
     fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit>;
     fn sample_surface<R: ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)>
@@ -168,12 +190,153 @@ trait ShapeImpl {
     fn sample_surface_pdf(&self, pos: &P3) -> f32;
     fn aabb(&self) -> AABB;
     fn area(&self) -> f32;
+    //texture coordinates in [0, 1]^2 of a point on the surface
+    fn uv(&self, pos: &P3) -> P2;
+    //unit direction on the surface in which u increases
+    fn tangent(&self, pos: &P3) -> V3;
+
+    //normal interpolated for shading, if any
+    fn shading_normal(&self, _pos: &P3) -> Option<V3> {
+        None
+    }
+
+    //samples a point to be seen from 'point'. the pdf is with respect to the area.
+    fn sample_from<R: Rng + ?Sized>(&self, _point: &P3, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        self.sample_surface(rng)
+    }
+
+    fn sample_from_pdf(&self, _point: &P3, pos: &P3) -> f32 {
+        self.sample_surface_pdf(pos)
+    }
+}
+
+//solid angles smaller than this are sampled by the area, as the spherical sampling is unstable
+const MIN_SAMPLED_SOLID_ANGLE: f32 = 1e-4;
+
+//converts a pdf with respect to the solid angle seen from 'point' into one with respect to the area
+fn solid_angle_pdf_to_area(pdf_omega: f32, point: &P3, pos: &P3, normal: &V3) -> f32 {
+    let r = pos - point;
+    let sq_dist = r.norm_squared();
+    pdf_omega * normal.dot(&r).abs() / sq_dist.sqrt() / sq_dist
 }
 
 pub mod shapes {
     use super::*;
     use rand::prelude::*;
 
+    //roots of a t^2 + b t + c in ascending order
+    fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+        let (a, b, c) = (a as f64, b as f64, c as f64);
+        if a == 0.0 {
+            if b == 0.0 {
+                return None;
+            }
+            let t = (-c / b) as f32;
+            return Some((t, t));
+        }
+        let d = b * b - 4.0 * a * c;
+        if d < 0.0 {
+            return None;
+        }
+        //avoids cancellation
+        let q = -0.5 * (b + b.signum() * d.sqrt());
+        let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+        Some(((t0.min(t1)) as f32, (t0.max(t1)) as f32))
+    }
+
+    //frame whose z axis is 'axis'
+    fn axis_frame(origin: &P3, axis: &V3) -> LocalCoord {
+        LocalCoord::new_zx(origin, axis, &pick_orthogonal(axis))
+    }
+
+    //longitude and latitude of the direction around the z axis
+    fn spherical_uv(dir: &V3) -> P2 {
+        let dir = dir.normalize();
+        P2::new(
+            0.5 + dir[1].atan2(dir[0]) * std::f32::consts::FRAC_1_PI / 2.0,
+            1.0 - dir[2].clamp(-1.0, 1.0).acos() * std::f32::consts::FRAC_1_PI,
+        )
+    }
+
+    //direction in which the longitude of spherical_uv increases
+    fn spherical_tangent(dir: &V3) -> V3 {
+        let t = V3::new(-dir[1], dir[0], 0.0);
+        if t.norm_squared() > 0.0 {
+            t.normalize()
+        } else {
+            V3::x()
+        }
+    }
+
+    //projection onto the xy plane of the square of the radius around the z axis
+    fn planar_uv(p: &P3, radius: f32) -> P2 {
+        P2::new(0.5 + p[0] / radius / 2.0, 0.5 + p[1] / radius / 2.0)
+    }
+
+    //a line uniformly distributed among the lines through the bounding sphere of the box,
+    //by a point on the plane through the center and the direction
+    fn random_line<R: Rng + ?Sized>(bounds: &AABB, rng: &mut R) -> (P3, V3) {
+        use rand::distributions::Uniform;
+        let upi = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI);
+        let z = Uniform::new(-1.0f32, 1.0).sample(rng);
+        let theta = upi.sample(rng);
+        let r = (1.0 - z * z).sqrt();
+        let dir = V3::new(r * theta.cos(), r * theta.sin(), z);
+
+        let lc = LocalCoord::new_zx(&bounds.center(), &dir, &pick_orthogonal(&dir));
+        let rho = bounds.diag().norm() / 2.0 * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
+        let phi = upi.sample(rng);
+        let o = lc.l2w() * P3::new(rho * phi.cos(), rho * phi.sin(), 0.0);
+        (o, dir)
+    }
+
+    //checks that samples lie on the surface, facing their normals, and returns the
+    //fraction of them in 'region'
+    #[cfg(test)]
+    fn sample_fraction<S: ShapeImpl>(shape: &S, region: impl Fn(&P3) -> bool) -> f32 {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let n = 20000;
+        let mut count = 0;
+        for _ in 0..n {
+            let s = shape.sample_surface(&mut rng);
+            let (p, normal) = s.value;
+            assert!((s.pdf * shape.area() - 1.0).abs() < 1e-4);
+            let hit = shape
+                .test_hit(&Ray::new(p + normal * 0.01, -normal), 0.0, 1.0)
+                .unwrap();
+            assert!((hit.dist - 0.01).abs() < 1e-3);
+            assert!(hit.gnorm.dot(&normal) > 0.999);
+            if region(&p) {
+                count += 1;
+            }
+        }
+        count as f32 / n as f32
+    }
+
+    mod disk;
+    pub use disk::*;
+
+    mod cylinder;
+    pub use cylinder::*;
+
+    mod cone;
+    pub use cone::*;
+
+    mod torus;
+    pub use torus::*;
+
+    mod quadric;
+    pub use quadric::*;
+
+    mod curve;
+    pub use curve::*;
+
+    mod implicit;
+    pub use implicit::*;
+
+    mod csg;
+    pub use csg::*;
+
     #[derive(Clone)]
     pub struct Sphere {
         pub center: P3,
@@ -193,6 +356,24 @@ pub mod shapes {
                 gx,
             }
         }
+
+        //1 - cos of the half angle of the cone subtended by the sphere.
+        //None if 'point' is inside, on the surface or the cone is too narrow to be sampled.
+        fn cone_one_minus_cos(&self, point: &P3) -> Option<f32> {
+            let sq_dist = (self.center - point).norm_squared();
+            let sq_sin_max = self.radius * self.radius / sq_dist;
+            //from a point on the surface, the nearer intersection is the point itself
+            if sq_sin_max >= 0.999 {
+                return None;
+            }
+            //avoids cancellation for narrow cones
+            let one_minus_cos = sq_sin_max / (1.0 + (1.0 - sq_sin_max).sqrt());
+            if 2.0 * std::f32::consts::PI * one_minus_cos < MIN_SAMPLED_SOLID_ANGLE {
+                None
+            } else {
+                Some(one_minus_cos)
+            }
+        }
     }
 
     impl ShapeImpl for Sphere {
@@ -253,6 +434,58 @@ pub mod shapes {
         fn area(&self) -> f32 {
             4.0 * std::f32::consts::PI * self.radius * self.radius
         }
+
+        fn uv(&self, pos: &P3) -> P2 {
+            spherical_uv(&(pos - self.center))
+        }
+
+        fn tangent(&self, pos: &P3) -> V3 {
+            spherical_tangent(&(pos - self.center))
+        }
+
+        //samples the cone of directions toward the sphere
+        fn sample_from<R: Rng + ?Sized>(
+            &self,
+            point: &P3,
+            rng: &mut R,
+        ) -> pdf::PdfSample<(P3, V3)> {
+            use rand::distributions::Uniform;
+            let one_minus_cos_max = match self.cone_one_minus_cos(point) {
+                Some(c) => c,
+                None => return self.sample_surface(rng),
+            };
+            let to_center = self.center - point;
+            let dist_center = to_center.norm();
+            let lc = LocalCoord::new_zx(point, &to_center, &pick_orthogonal(&to_center));
+
+            let one_minus_cos = Uniform::new_inclusive(0.0, one_minus_cos_max).sample(rng);
+            let phi = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+            let cos_theta = 1.0 - one_minus_cos;
+            let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).sqrt();
+            let dir = lc.l2w() * V3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+
+            //the nearer intersection of the direction with the sphere
+            let sq_half_chord =
+                self.radius * self.radius - dist_center * dist_center * sin_theta * sin_theta;
+            let dist = dist_center * cos_theta - sq_half_chord.max(0.0).sqrt();
+            let n = (point + dir * dist - self.center).normalize();
+            let pos = self.center + n * self.radius;
+            pdf::PdfSample {
+                value: (pos, n),
+                pdf: self.sample_from_pdf(point, &pos),
+            }
+        }
+
+        fn sample_from_pdf(&self, point: &P3, pos: &P3) -> f32 {
+            match self.cone_one_minus_cos(point) {
+                Some(one_minus_cos_max) => {
+                    let pdf_omega = std::f32::consts::FRAC_1_PI / 2.0 / one_minus_cos_max;
+                    let n = (pos - self.center) / self.radius;
+                    solid_angle_pdf_to_area(pdf_omega, point, pos, &n)
+                }
+                None => self.sample_surface_pdf(pos),
+            }
+        }
     }
 
     #[derive(Clone)]
@@ -261,6 +494,10 @@ pub mod shapes {
         lc: LocalCoord,
         aabb: AABB,
         area: f32,
+        //vertex normals for shading
+        normals: Option<[V3; 3]>,
+        //vertex texture coordinates and the direction of u they give
+        uvs: Option<([P2; 3], V3)>,
     }
 
     impl Triangle {
@@ -283,12 +520,108 @@ pub mod shapes {
                 lc,
                 aabb,
                 area: n.norm() / 2.0,
+                normals: None,
+                uvs: None,
             }
         }
 
+        //the tangent follows u over the triangle, so frames agree across triangles sharing uvs
+        pub fn set_uvs(mut self, uvs: [P2; 3]) -> Self {
+            let [a, b, c] = self.vertices();
+            let (e1, e2) = (b - a, c - a);
+            let (d1, d2) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+            let det = d1[0] * d2[1] - d2[0] * d1[1];
+            let dpdu = (e1 * d2[1] - e2 * d1[1]) / det;
+            //degenerate uvs keep the edge
+            let tangent = if det.abs() > 1e-12 && dpdu.norm_squared() > 0.0 {
+                dpdu.normalize()
+            } else {
+                e1.normalize()
+            };
+            self.uvs = Some((uvs, tangent));
+            self
+        }
+
+        pub fn set_normals(mut self, normals: [V3; 3]) -> Self {
+            self.normals = Some([
+                normals[0].normalize(),
+                normals[1].normalize(),
+                normals[2].normalize(),
+            ]);
+            self
+        }
+
         pub fn normal(&self) -> V3 {
             self.lc.w()
         }
+
+        //(s, t) of the point a + s (b - a) + t (c - a) nearest to 'pos'
+        fn barycentric(&self, pos: &P3) -> (f32, f32) {
+            let p = (self.lc.w2l() * pos).coords - self.abc[0];
+            let ab = self.abc[1] - self.abc[0];
+            let ac = self.abc[2] - self.abc[0];
+            let det = ab.cross(&ac)[2];
+            (p.cross(&ac)[2] / det, ab.cross(&p)[2] / det)
+        }
+
+        fn vertices(&self) -> [P3; 3] {
+            let v = |i: usize| self.lc.l2w() * (P3::origin() + self.abc[i]);
+            [v(0), v(1), v(2)]
+        }
+
+        //unit vectors from 'point' to the vertices
+        fn spherical_vertices(&self, point: &P3) -> [Vector3<f64>; 3] {
+            let v = self.vertices();
+            let u = |i: usize| (v[i] - point).map(|x| x as f64).normalize();
+            [u(0), u(1), u(2)]
+        }
+
+        //solid angle seen from 'point' (van Oosterom and Strackee)
+        fn solid_angle(&self, point: &P3) -> f32 {
+            //points on the plane, including ones on the triangle itself, see nothing
+            if self.normal().dot(&(point - self.lc.origin())).abs() < 1e-4 * self.area.sqrt() {
+                return 0.0;
+            }
+            let [a, b, c] = self.spherical_vertices(point);
+            let numer = a.dot(&b.cross(&c)).abs();
+            let denom = 1.0 + a.dot(&b) + b.dot(&c) + c.dot(&a);
+            (2.0 * numer.atan2(denom)) as f32
+        }
+
+        //a point whose direction from 'point' is uniformly distributed in the solid angle (Arvo 1995)
+        fn sample_spherical<R: Rng + ?Sized>(&self, point: &P3, rng: &mut R) -> P3 {
+            let [a, b, c] = self.spherical_vertices(point);
+            let angle = |x: &Vector3<f64>, y: &Vector3<f64>, z: &Vector3<f64>| {
+                let n1 = x.cross(y).normalize();
+                let n2 = x.cross(z).normalize();
+                n1.dot(&n2).clamp(-1.0, 1.0).acos()
+            };
+            let alpha = angle(&a, &b, &c);
+            let beta = angle(&b, &c, &a);
+            let gamma = angle(&c, &a, &b);
+            let area = alpha + beta + gamma - std::f64::consts::PI;
+
+            let u1: f64 = rng.gen();
+            let u2: f64 = rng.gen();
+            //the vertex c' of the sub-triangle a b c' whose area is u1 * area
+            let sub_area = u1 * area;
+            let (s, t) = (sub_area - alpha).sin_cos();
+            let u = t - alpha.cos();
+            let v = s + alpha.sin() * a.dot(&b);
+            let q = ((v * t - u * s) * alpha.cos() - v) / ((v * s + u * t) * alpha.sin());
+            let q = q.clamp(-1.0, 1.0);
+            let c_sub = a * q + (c - a * c.dot(&a)).normalize() * (1.0 - q * q).sqrt();
+            //a direction on the arc from b to c'
+            let z = 1.0 - u2 * (1.0 - c_sub.dot(&b));
+            let z = z.clamp(-1.0, 1.0);
+            let dir = b * z + (c_sub - b * c_sub.dot(&b)).normalize() * (1.0 - z * z).sqrt();
+            let dir = dir.map(|x| x as f32);
+
+            //intersection with the plane of the triangle
+            let n = self.normal();
+            let dist = n.dot(&(self.lc.origin() - point)) / n.dot(&dir);
+            point + dir * dist
+        }
     }
 
     impl ShapeImpl for Triangle {
@@ -352,6 +685,142 @@ pub mod shapes {
         fn area(&self) -> f32 {
             self.area
         }
+
+        fn uv(&self, pos: &P3) -> P2 {
+            let (s, t) = self.barycentric(pos);
+            match &self.uvs {
+                Some((uvs, _)) => P2 {
+                    coords: uvs[0].coords * (1.0 - s - t) + uvs[1].coords * s + uvs[2].coords * t,
+                },
+                None => P2::new(s, t),
+            }
+        }
+
+        fn tangent(&self, _pos: &P3) -> V3 {
+            match &self.uvs {
+                Some((_, tangent)) => *tangent,
+                None => (self.lc.l2w() * (self.abc[1] - self.abc[0])).normalize(),
+            }
+        }
+
+        fn shading_normal(&self, pos: &P3) -> Option<V3> {
+            let ns = self.normals.as_ref()?;
+            let (s, t) = self.barycentric(pos);
+            Some((ns[0] * (1.0 - s - t) + ns[1] * s + ns[2] * t).normalize())
+        }
+
+        fn sample_from<R: Rng + ?Sized>(
+            &self,
+            point: &P3,
+            rng: &mut R,
+        ) -> pdf::PdfSample<(P3, V3)> {
+            let solid_angle = self.solid_angle(point);
+            if solid_angle < MIN_SAMPLED_SOLID_ANGLE {
+                return self.sample_surface(rng);
+            }
+            let pos = self.sample_spherical(point, rng);
+            pdf::PdfSample {
+                value: (pos, self.normal()),
+                pdf: solid_angle_pdf_to_area(1.0 / solid_angle, point, &pos, &self.normal()),
+            }
+        }
+
+        fn sample_from_pdf(&self, point: &P3, pos: &P3) -> f32 {
+            let solid_angle = self.solid_angle(point);
+            if solid_angle < MIN_SAMPLED_SOLID_ANGLE {
+                self.sample_surface_pdf(pos)
+            } else {
+                solid_angle_pdf_to_area(1.0 / solid_angle, point, pos, &self.normal())
+            }
+        }
+    }
+
+    //a rectangle seen from a point, in the frame of its edges whose z axis points away from
+    //the point (Urena et al. 2013)
+    struct SphericalRectangle {
+        point: Vector3<f64>,
+        frame: [Vector3<f64>; 3],
+        x: (f64, f64),
+        y: (f64, f64),
+        z0: f64,
+        //z of the normals of the planes through the point and the edges at y0 and y1
+        b: (f64, f64),
+        //interior angles of the spherical rectangle
+        g: [f64; 4],
+        solid_angle: f64,
+    }
+
+    impl SphericalRectangle {
+        fn new(point: &P3, corner: &P3, ex: &V3, ey: &V3) -> Self {
+            let f64s = |v: &V3| v.map(|x| x as f64);
+            let (ex, ey) = (f64s(ex), f64s(ey));
+            let (x, y) = (ex.normalize(), ey.normalize());
+            let mut z = x.cross(&y);
+            let d = f64s(&(corner - point));
+            let mut z0 = d.dot(&z);
+            if z0 > 0.0 {
+                z0 = -z0;
+                z = -z;
+            }
+            let (x0, y0) = (d.dot(&x), d.dot(&y));
+            let (x1, y1) = (x0 + ex.norm(), y0 + ey.norm());
+            let v00 = Vector3::new(x0, y0, z0);
+            let v01 = Vector3::new(x0, y1, z0);
+            let v10 = Vector3::new(x1, y0, z0);
+            let v11 = Vector3::new(x1, y1, z0);
+            let n0 = v00.cross(&v10).normalize();
+            let n1 = v10.cross(&v11).normalize();
+            let n2 = v11.cross(&v01).normalize();
+            let n3 = v01.cross(&v00).normalize();
+            let angle = |a: &Vector3<f64>, b: &Vector3<f64>| (-a).dot(b).clamp(-1.0, 1.0).acos();
+            let g = [
+                angle(&n0, &n1),
+                angle(&n1, &n2),
+                angle(&n2, &n3),
+                angle(&n3, &n0),
+            ];
+            //points on the plane give nans, which max turns into 0
+            let solid_angle = (g.iter().sum::<f64>() - 2.0 * std::f64::consts::PI).max(0.0);
+            SphericalRectangle {
+                point: point.coords.map(|x| x as f64),
+                frame: [x, y, z],
+                x: (x0, x1),
+                y: (y0, y1),
+                z0,
+                b: (n0[2], n2[2]),
+                g,
+                solid_angle,
+            }
+        }
+
+        //a point whose direction is uniformly distributed in the solid angle
+        fn sample(&self, u1: f64, u2: f64) -> P3 {
+            let [g0, g1, g2, g3] = self.g;
+            let (b0, b1) = self.b;
+            let ((x0, x1), (y0, y1), z0) = (self.x, self.y, self.z0);
+            //the x whose part of the solid angle is u1
+            let au = u1 * (g0 + g1 - 2.0 * std::f64::consts::PI) + (u1 - 1.0) * (g2 + g3);
+            let fu = (au.cos() * b0 - b1) / au.sin();
+            let cu = (1.0 / (fu * fu + b0 * b0).sqrt())
+                .copysign(fu)
+                .clamp(-1.0, 1.0);
+            let xu = (-(cu * z0) / (1.0 - cu * cu).max(0.0).sqrt())
+                .max(x0)
+                .min(x1);
+            //the y uniform in the solid angle along x = xu
+            let dist = (xu * xu + z0 * z0).sqrt();
+            let h0 = y0 / (dist * dist + y0 * y0).sqrt();
+            let h1 = y1 / (dist * dist + y1 * y1).sqrt();
+            let hv = h0 + u2 * (h1 - h0);
+            let yv = if hv * hv < 1.0 - 1e-6 {
+                hv * dist / (1.0 - hv * hv).sqrt()
+            } else {
+                y1
+            };
+            let [x, y, z] = &self.frame;
+            let p = self.point + x * xu + y * yv + z * z0;
+            P3::from(p.map(|x| x as f32))
+        }
     }
 
     #[derive(Clone)]
@@ -370,6 +839,17 @@ pub mod shapes {
             let d = center + half_edge_1 - half_edge_2;
             Self::new(&a, &b, &d)
         }
+
+        //the corner 'a' and the edges to 'b' and 'd', if they are orthogonal
+        fn rectangle(&self) -> Option<(P3, V3, V3)> {
+            let [a, b, d] = self.0.vertices();
+            let (ex, ey) = (b - a, d - a);
+            if ex.dot(&ey).abs() <= 1e-4 * ex.norm() * ey.norm() {
+                Some((a, ex, ey))
+            } else {
+                None
+            }
+        }
     }
 
     impl ShapeImpl for Parallelogram {
@@ -401,6 +881,66 @@ pub mod shapes {
         fn area(&self) -> f32 {
             self.0.area * 2.0
         }
+
+        //(0, 0) at 'a', (1, 0) at 'b' and (0, 1) at 'd'
+        fn uv(&self, pos: &P3) -> P2 {
+            self.0.uv(pos)
+        }
+
+        fn tangent(&self, pos: &P3) -> V3 {
+            self.0.tangent(pos)
+        }
+
+        //rectangles are sampled by the solid angle as a whole. other parallelograms are split
+        //into the triangles, one of which is sampled in proportion to its solid angle.
+        fn sample_from<R: Rng + ?Sized>(
+            &self,
+            point: &P3,
+            rng: &mut R,
+        ) -> pdf::PdfSample<(P3, V3)> {
+            if let Some((corner, ex, ey)) = self.rectangle() {
+                let rect = SphericalRectangle::new(point, &corner, &ex, &ey);
+                let solid_angle = rect.solid_angle as f32;
+                if solid_angle < MIN_SAMPLED_SOLID_ANGLE {
+                    return self.sample_surface(rng);
+                }
+                let pos = rect.sample(rng.gen(), rng.gen());
+                let normal = self.0.normal();
+                return pdf::PdfSample {
+                    value: (pos, normal),
+                    pdf: solid_angle_pdf_to_area(1.0 / solid_angle, point, &pos, &normal),
+                };
+            }
+            let solid_angle_0 = self.0.solid_angle(point);
+            let solid_angle = solid_angle_0 + self.1.solid_angle(point);
+            if solid_angle < MIN_SAMPLED_SOLID_ANGLE {
+                return self.sample_surface(rng);
+            }
+            let tri = if rng.gen::<f32>() * solid_angle < solid_angle_0 {
+                &self.0
+            } else {
+                &self.1
+            };
+            let pos = tri.sample_spherical(point, rng);
+            pdf::PdfSample {
+                value: (pos, tri.normal()),
+                pdf: solid_angle_pdf_to_area(1.0 / solid_angle, point, &pos, &tri.normal()),
+            }
+        }
+
+        fn sample_from_pdf(&self, point: &P3, pos: &P3) -> f32 {
+            let solid_angle = match self.rectangle() {
+                Some((corner, ex, ey)) => {
+                    SphericalRectangle::new(point, &corner, &ex, &ey).solid_angle as f32
+                }
+                None => self.0.solid_angle(point) + self.1.solid_angle(point),
+            };
+            if solid_angle < MIN_SAMPLED_SOLID_ANGLE {
+                self.sample_surface_pdf(pos)
+            } else {
+                solid_angle_pdf_to_area(1.0 / solid_angle, point, pos, &self.0.normal())
+            }
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -451,41 +991,35 @@ pub mod shapes {
             Some(hit)
         }
 
+        //samples a face in proportion to its area, so the pdf is uniform
         fn sample_surface<R: ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)>
         where
             R: Rng,
         {
-            use crate::pdf::SliceRandomPdf;
             use rand::distributions::Uniform;
-            [0 as usize, 1, 2, 3, 4, 5]
-                .choose_pdf(rng)
-                .unwrap()
-                .and_then(|s| {
-                    let axis = s / 2;
-                    let side = s % 2;
-                    let (p0, p1) = if side == 0 {
-                        (self.0, self.1)
-                    } else {
-                        (self.1, self.0)
-                    };
-                    let mut dx = V3::zeros();
-                    dx[axis] = p1[axis] - p0[axis];
-                    let mut dy = V3::zeros();
-                    dy[(axis + 1) % 3] = p1[(axis + 1) % 3] - p0[(axis + 1) % 3];
-
-                    let u = Uniform::new(0.0, 1.0).sample(rng);
-                    let v = Uniform::new(0.0, 1.0).sample(rng);
-                    let p = p0 + dx * u + dy * v;
-
-                    pdf::PdfSample {
-                        value: (p, self.normal_at(p)),
-                        pdf: 1.0 / (dx.norm() * dy.norm()),
-                    }
-                })
+            let diag = self.1 - self.0;
+            let face_area = |axis: usize| (diag[(axis + 1) % 3] * diag[(axis + 2) % 3]).abs();
+            let mut u = Uniform::new(0.0, self.area()).sample(rng);
+            let mut axis = 0;
+            while axis < 2 && u >= 2.0 * face_area(axis) {
+                u -= 2.0 * face_area(axis);
+                axis += 1;
+            }
+            let side = if u < face_area(axis) { self.0 } else { self.1 };
+
+            let mut p = self.0;
+            p[axis] = side[axis];
+            for i in [(axis + 1) % 3, (axis + 2) % 3].iter() {
+                p[*i] += diag[*i] * Uniform::new(0.0, 1.0).sample(rng);
+            }
+            pdf::PdfSample {
+                value: (p, self.normal_at(p)),
+                pdf: 1.0 / self.area(),
+            }
         }
 
         fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
-            unimplemented!()
+            1.0 / self.area()
         }
 
         fn aabb(&self) -> AABB {
@@ -498,17 +1032,41 @@ pub mod shapes {
             let dz = (self.0[2] - self.1[2]).abs();
             2.0 * (dx * dy + dy * dz + dz * dx)
         }
+
+        //the face is parametrized by the other two axes in cyclic order
+        fn uv(&self, pos: &P3) -> P2 {
+            let axis = self.normal_at(*pos).iamax();
+            let (mins, maxs) = (self.0.inf(&self.1), self.0.sup(&self.1));
+            let at = |i: usize| (pos[i] - mins[i]) / (maxs[i] - mins[i]);
+            P2::new(at((axis + 1) % 3), at((axis + 2) % 3))
+        }
+
+        fn tangent(&self, pos: &P3) -> V3 {
+            let axis = self.normal_at(*pos).iamax();
+            let mut t = V3::zeros();
+            t[(axis + 1) % 3] = 1.0;
+            t
+        }
     }
 }
 
+#[derive(Clone)]
 pub enum Shape {
     Sphere(shapes::Sphere),
     Triangle(shapes::Triangle),
     Parallelogram(shapes::Parallelogram),
     AARectangular(shapes::AARectangular),
+    Disk(shapes::Disk),
+    Cylinder(shapes::Cylinder),
+    Cone(shapes::Cone),
+    Torus(shapes::Torus),
+    Quadric(shapes::Quadric),
+    Curve(shapes::Curve),
+    Implicit(shapes::Implicit),
+    Csg(shapes::Csg),
 }
 
-impl_wrap_from_many! {Shape, shapes, [Sphere, Triangle, Parallelogram, AARectangular]}
+impl_wrap_from_many! {Shape, shapes, [Sphere, Triangle, Parallelogram, AARectangular, Disk, Cylinder, Cone, Torus, Quadric, Curve, Implicit, Csg]}
 
 impl Shape {
     pub fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
@@ -517,6 +1075,14 @@ impl Shape {
             Shape::Triangle(s) => s.test_hit(ray, tnear, tfar),
             Shape::Parallelogram(s) => s.test_hit(ray, tnear, tfar),
             Shape::AARectangular(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Disk(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Cylinder(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Cone(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Torus(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Quadric(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Curve(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Implicit(s) => s.test_hit(ray, tnear, tfar),
+            Shape::Csg(s) => s.test_hit(ray, tnear, tfar),
         }
     }
 
@@ -529,6 +1095,14 @@ impl Shape {
             Shape::Triangle(s) => s.sample_surface(rng),
             Shape::Parallelogram(s) => s.sample_surface(rng),
             Shape::AARectangular(s) => s.sample_surface(rng),
+            Shape::Disk(s) => s.sample_surface(rng),
+            Shape::Cylinder(s) => s.sample_surface(rng),
+            Shape::Cone(s) => s.sample_surface(rng),
+            Shape::Torus(s) => s.sample_surface(rng),
+            Shape::Quadric(s) => s.sample_surface(rng),
+            Shape::Curve(s) => s.sample_surface(rng),
+            Shape::Implicit(s) => s.sample_surface(rng),
+            Shape::Csg(s) => s.sample_surface(rng),
         }
     }
 
@@ -538,6 +1112,103 @@ impl Shape {
             Shape::Triangle(s) => s.sample_surface_pdf(pos),
             Shape::Parallelogram(s) => s.sample_surface_pdf(pos),
             Shape::AARectangular(s) => s.sample_surface_pdf(pos),
+            Shape::Disk(s) => s.sample_surface_pdf(pos),
+            Shape::Cylinder(s) => s.sample_surface_pdf(pos),
+            Shape::Cone(s) => s.sample_surface_pdf(pos),
+            Shape::Torus(s) => s.sample_surface_pdf(pos),
+            Shape::Quadric(s) => s.sample_surface_pdf(pos),
+            Shape::Curve(s) => s.sample_surface_pdf(pos),
+            Shape::Implicit(s) => s.sample_surface_pdf(pos),
+            Shape::Csg(s) => s.sample_surface_pdf(pos),
+        }
+    }
+
+    pub fn sample_from<R: Rng + ?Sized>(
+        &self,
+        point: &P3,
+        rng: &mut R,
+    ) -> pdf::PdfSample<(P3, V3)> {
+        match self {
+            Shape::Sphere(s) => s.sample_from(point, rng),
+            Shape::Triangle(s) => s.sample_from(point, rng),
+            Shape::Parallelogram(s) => s.sample_from(point, rng),
+            Shape::AARectangular(s) => s.sample_from(point, rng),
+            Shape::Disk(s) => s.sample_from(point, rng),
+            Shape::Cylinder(s) => s.sample_from(point, rng),
+            Shape::Cone(s) => s.sample_from(point, rng),
+            Shape::Torus(s) => s.sample_from(point, rng),
+            Shape::Quadric(s) => s.sample_from(point, rng),
+            Shape::Curve(s) => s.sample_from(point, rng),
+            Shape::Implicit(s) => s.sample_from(point, rng),
+            Shape::Csg(s) => s.sample_from(point, rng),
+        }
+    }
+
+    pub fn sample_from_pdf(&self, point: &P3, pos: &P3) -> f32 {
+        match self {
+            Shape::Sphere(s) => s.sample_from_pdf(point, pos),
+            Shape::Triangle(s) => s.sample_from_pdf(point, pos),
+            Shape::Parallelogram(s) => s.sample_from_pdf(point, pos),
+            Shape::AARectangular(s) => s.sample_from_pdf(point, pos),
+            Shape::Disk(s) => s.sample_from_pdf(point, pos),
+            Shape::Cylinder(s) => s.sample_from_pdf(point, pos),
+            Shape::Cone(s) => s.sample_from_pdf(point, pos),
+            Shape::Torus(s) => s.sample_from_pdf(point, pos),
+            Shape::Quadric(s) => s.sample_from_pdf(point, pos),
+            Shape::Curve(s) => s.sample_from_pdf(point, pos),
+            Shape::Implicit(s) => s.sample_from_pdf(point, pos),
+            Shape::Csg(s) => s.sample_from_pdf(point, pos),
+        }
+    }
+
+    pub fn uv(&self, pos: &P3) -> P2 {
+        match self {
+            Shape::Sphere(s) => s.uv(pos),
+            Shape::Triangle(s) => s.uv(pos),
+            Shape::Parallelogram(s) => s.uv(pos),
+            Shape::AARectangular(s) => s.uv(pos),
+            Shape::Disk(s) => s.uv(pos),
+            Shape::Cylinder(s) => s.uv(pos),
+            Shape::Cone(s) => s.uv(pos),
+            Shape::Torus(s) => s.uv(pos),
+            Shape::Quadric(s) => s.uv(pos),
+            Shape::Curve(s) => s.uv(pos),
+            Shape::Implicit(s) => s.uv(pos),
+            Shape::Csg(s) => s.uv(pos),
+        }
+    }
+
+    pub fn tangent(&self, pos: &P3) -> V3 {
+        match self {
+            Shape::Sphere(s) => s.tangent(pos),
+            Shape::Triangle(s) => s.tangent(pos),
+            Shape::Parallelogram(s) => s.tangent(pos),
+            Shape::AARectangular(s) => s.tangent(pos),
+            Shape::Disk(s) => s.tangent(pos),
+            Shape::Cylinder(s) => s.tangent(pos),
+            Shape::Cone(s) => s.tangent(pos),
+            Shape::Torus(s) => s.tangent(pos),
+            Shape::Quadric(s) => s.tangent(pos),
+            Shape::Curve(s) => s.tangent(pos),
+            Shape::Implicit(s) => s.tangent(pos),
+            Shape::Csg(s) => s.tangent(pos),
+        }
+    }
+
+    pub fn shading_normal(&self, pos: &P3) -> Option<V3> {
+        match self {
+            Shape::Sphere(s) => s.shading_normal(pos),
+            Shape::Triangle(s) => s.shading_normal(pos),
+            Shape::Parallelogram(s) => s.shading_normal(pos),
+            Shape::AARectangular(s) => s.shading_normal(pos),
+            Shape::Disk(s) => s.shading_normal(pos),
+            Shape::Cylinder(s) => s.shading_normal(pos),
+            Shape::Cone(s) => s.shading_normal(pos),
+            Shape::Torus(s) => s.shading_normal(pos),
+            Shape::Quadric(s) => s.shading_normal(pos),
+            Shape::Curve(s) => s.shading_normal(pos),
+            Shape::Implicit(s) => s.shading_normal(pos),
+            Shape::Csg(s) => s.shading_normal(pos),
         }
     }
 
@@ -547,6 +1218,14 @@ impl Shape {
             Shape::Triangle(s) => s.aabb(),
             Shape::Parallelogram(s) => s.aabb(),
             Shape::AARectangular(s) => s.aabb(),
+            Shape::Disk(s) => s.aabb(),
+            Shape::Cylinder(s) => s.aabb(),
+            Shape::Cone(s) => s.aabb(),
+            Shape::Torus(s) => s.aabb(),
+            Shape::Quadric(s) => s.aabb(),
+            Shape::Curve(s) => s.aabb(),
+            Shape::Implicit(s) => s.aabb(),
+            Shape::Csg(s) => s.aabb(),
         }
     }
 
@@ -556,6 +1235,97 @@ impl Shape {
             Shape::Triangle(s) => s.area(),
             Shape::Parallelogram(s) => s.area(),
             Shape::AARectangular(s) => s.area(),
+            Shape::Disk(s) => s.area(),
+            Shape::Cylinder(s) => s.area(),
+            Shape::Cone(s) => s.area(),
+            Shape::Torus(s) => s.area(),
+            Shape::Quadric(s) => s.area(),
+            Shape::Curve(s) => s.area(),
+            Shape::Implicit(s) => s.area(),
+            Shape::Csg(s) => s.area(),
         }
     }
 }
+
+#[test]
+fn test_sample_from() {
+    use rand::prelude::*;
+    let mut rng = SmallRng::seed_from_u64(0);
+    let point = P3::new(1.0, 2.0, 10.0);
+    let cos = |pos: &P3, normal: &V3| normal.dot(&(point - pos).normalize()).abs();
+    //estimates the integral of cos over the part visible from the point,
+    //both by the area and by sample_from
+    let mut check = |shape: Shape, visible: &dyn Fn(&P3) -> bool| {
+        let n = 100000;
+        let mut by_area = 0.0;
+        let mut by_point = 0.0;
+        for _ in 0..n {
+            let s = shape.sample_surface(&mut rng);
+            let (pos, normal) = s.value;
+            if visible(&pos) {
+                by_area += cos(&pos, &normal) / s.pdf;
+            }
+
+            let s = shape.sample_from(&point, &mut rng);
+            let (pos, normal) = s.value;
+            assert!((s.pdf - shape.sample_from_pdf(&point, &pos)).abs() <= s.pdf * 1e-3);
+            by_point += cos(&pos, &normal) / s.pdf;
+        }
+        assert!((by_area - by_point).abs() < by_area * 0.01);
+    };
+    let tri = shapes::Triangle::new([
+        P3::new(0.0, 0.0, 0.0),
+        P3::new(4.0, 0.0, 1.0),
+        P3::new(0.0, 3.0, 0.0),
+    ]);
+    check(tri.into(), &|_| true);
+    let para = shapes::Parallelogram::new(
+        &P3::new(-2.0, 0.0, 0.0),
+        &P3::new(3.0, 0.0, 0.0),
+        &P3::new(-1.0, 4.0, 2.0),
+    );
+    check(para.into(), &|_| true);
+    let rect = shapes::Parallelogram::new_rectangle(
+        &P3::new(3.0, -1.0, 1.0),
+        &V3::new(2.0, 0.0, 1.0),
+        &V3::new(0.0, 1.5, 0.0),
+    );
+    check(rect.clone().into(), &|_| true);
+    let center = P3::new(1.0, 2.0, 0.0);
+    let sphere = shapes::Sphere {
+        center,
+        radius: 2.0,
+    };
+    check(sphere.into(), &|pos| {
+        (pos - center).dot(&(point - center)) >= 4.0
+    });
+    //the samples lie on the rectangle, seen from either side
+    for point in [P3::new(0.0, 0.0, 5.0), P3::new(4.0, -1.0, -2.0)].iter() {
+        for _ in 0..1000 {
+            let (pos, normal) = rect.sample_from(point, &mut rng).value;
+            let hit = rect
+                .test_hit(&Ray::new(pos + normal * 0.01, -normal), 0.0, 1.0)
+                .unwrap();
+            assert!((hit.dist - 0.01).abs() < 1e-3);
+        }
+    }
+}
+
+#[test]
+fn test_triangle_uvs() {
+    //the two halves of a square whose u runs along y
+    let (a, b, c, d) = (
+        P3::new(0.0, 0.0, 0.0),
+        P3::new(1.0, 0.0, 0.0),
+        P3::new(1.0, 1.0, 0.0),
+        P3::new(0.0, 1.0, 0.0),
+    );
+    let uv = |x: f32, y: f32| P2::new(y, 1.0 - x);
+    let t1 = shapes::Triangle::new([a, b, c]).set_uvs([uv(0.0, 0.0), uv(1.0, 0.0), uv(1.0, 1.0)]);
+    let t2 = shapes::Triangle::new([a, c, d]).set_uvs([uv(0.0, 0.0), uv(1.0, 1.0), uv(0.0, 1.0)]);
+    for t in [t1.clone(), t2].iter() {
+        assert!((t.tangent(&P3::origin()) - V3::y()).norm() < 1e-5);
+    }
+    let p = P3::new(0.7, 0.2, 0.0);
+    assert!((t1.uv(&p) - uv(0.7, 0.2)).norm() < 1e-5);
+}
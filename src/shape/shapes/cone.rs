@@ -0,0 +1,176 @@
+use super::*;
+
+//cone whose base circle is centered at 'base' and whose apex is 'base + axis'.
+//a closed cone has a cap on the base.
+#[derive(Clone)]
+pub struct Cone {
+    lc: LocalCoord,
+    radius: f32,
+    height: f32,
+    closed: bool,
+}
+
+impl Cone {
+    pub fn new(base: &P3, axis: &V3, radius: f32, closed: bool) -> Self {
+        Cone {
+            lc: axis_frame(base, axis),
+            radius,
+            height: axis.norm(),
+            closed,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn side_area(&self) -> f32 {
+        let slant = (self.radius * self.radius + self.height * self.height).sqrt();
+        std::f32::consts::PI * self.radius * slant
+    }
+
+    fn cap_area(&self) -> f32 {
+        std::f32::consts::PI * self.radius * self.radius
+    }
+
+    fn side_normal(&self, p: &P3) -> V3 {
+        let k = self.radius / self.height;
+        V3::new(p[0], p[1], k * k * (self.height - p[2])).normalize()
+    }
+
+    //(dist, position, normal, tangent) in local coordinates
+    fn hit_local(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<(f32, P3, V3, V3)> {
+        let (o, d) = (ray.origin, ray.dir);
+        let mut nearest = None::<(f32, P3, V3, V3)>;
+        let mut update = |t: f32, p: P3, n: V3, x: V3| {
+            if tnear < t && t < nearest.as_ref().map_or(tfar, |h| h.0) {
+                nearest = Some((t, p, n, x));
+            }
+        };
+
+        let k2 = (self.radius / self.height).powi(2);
+        let w = self.height - o[2];
+        let a = d[0] * d[0] + d[1] * d[1] - k2 * d[2] * d[2];
+        let b = 2.0 * (o[0] * d[0] + o[1] * d[1] + k2 * w * d[2]);
+        let c = o[0] * o[0] + o[1] * o[1] - k2 * w * w;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for &t in [t0, t1].iter() {
+                let p = o + d * t;
+                if 0.0 <= p[2] && p[2] <= self.height {
+                    let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+                    //the tangent is undefined at the apex
+                    let x = if rho > 0.0 {
+                        V3::new(-p[1] / rho, p[0] / rho, 0.0)
+                    } else {
+                        V3::x()
+                    };
+                    update(t, p, self.side_normal(&p), x);
+                }
+            }
+        }
+
+        if self.closed && d[2] != 0.0 {
+            let t = -o[2] / d[2];
+            let p = o + d * t;
+            if p[0] * p[0] + p[1] * p[1] <= self.radius * self.radius {
+                update(t, p, -V3::z(), V3::x());
+            }
+        }
+        nearest
+    }
+}
+
+impl ShapeImpl for Cone {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let ray_local = self.lc.w2l() * ray.clone();
+        self.hit_local(&ray_local, tnear, tfar)
+            .map(|(dist, pos, n, x)| Hit {
+                dist,
+                pos: self.lc.l2w() * pos,
+                gnorm: self.lc.l2w() * n,
+                gx: self.lc.l2w() * x,
+            })
+    }
+
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        use rand::distributions::Uniform;
+        let u = Uniform::new(0.0, self.area()).sample(rng);
+        let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+        //the circumference is proportional to the distance from the apex
+        let s = Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
+        let (p, n) = if u < self.side_area() {
+            let r = self.radius * s;
+            let p = P3::new(r * theta.cos(), r * theta.sin(), self.height * (1.0 - s));
+            (p, self.side_normal(&p))
+        } else {
+            let r = self.radius * s;
+            (P3::new(r * theta.cos(), r * theta.sin(), 0.0), -V3::z())
+        };
+        pdf::PdfSample {
+            value: (self.lc.l2w() * p, self.lc.l2w() * n),
+            pdf: 1.0 / self.area(),
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area()
+    }
+
+    fn aabb(&self) -> AABB {
+        let r = self.radius;
+        AABB::new(&P3::new(-r, -r, 0.0), &P3::new(r, r, self.height)).transformed(self.lc.l2w())
+    }
+
+    //the side is parametrized by the angle and the height, and the cap is projected
+    fn uv(&self, pos: &P3) -> P2 {
+        let p = self.lc.w2l() * pos;
+        if self.closed && p[2].abs() < self.height * 1e-4 {
+            planar_uv(&p, self.radius)
+        } else {
+            P2::new(spherical_uv(&p.coords)[0], p[2] / self.height)
+        }
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        let p = self.lc.w2l() * pos;
+        if self.closed && p[2].abs() < self.height * 1e-4 {
+            self.lc.u()
+        } else {
+            self.lc.l2w() * spherical_tangent(&p.coords)
+        }
+    }
+
+    fn area(&self) -> f32 {
+        if self.closed {
+            self.side_area() + self.cap_area()
+        } else {
+            self.side_area()
+        }
+    }
+}
+
+#[test]
+fn test_cone() {
+    let cone = Cone::new(&P3::origin(), &V3::new(0.0, 0.0, 2.0), 1.0, true);
+    let hit = cone
+        .test_hit(&Ray::new(P3::new(5.0, 0.0, 1.0), -V3::x()), 0.0, 10.0)
+        .unwrap();
+    assert!((hit.dist - 4.5).abs() < 1e-4);
+    assert!((hit.gnorm - V3::new(2.0, 0.0, 1.0).normalize()).norm() < 1e-4);
+    let up = Ray::new(P3::new(0.2, 0.0, -3.0), V3::z());
+    let hit = cone.test_hit(&up, 0.0, 10.0).unwrap();
+    assert!((hit.dist - 3.0).abs() < 1e-4);
+    assert!((hit.gnorm + V3::z()).norm() < 1e-4);
+    assert!(cone
+        .test_hit(&Ray::new(P3::new(5.0, 0.0, 2.5), -V3::x()), 0.0, 10.0)
+        .is_none());
+
+    let pi = std::f32::consts::PI;
+    let (side, cap) = (pi * 5.0f32.sqrt(), pi);
+    assert!((cone.area() - side - cap).abs() < 1e-4);
+    let caps = sample_fraction(&cone, |p| p[2] < 1e-4);
+    assert!((caps - cap / (side + cap)).abs() < 0.02);
+    //the part above half the height is similar to the whole side, scaled by 1/2
+    let upper = sample_fraction(&cone, |p| p[2] > 1.0);
+    assert!((upper - side / 4.0 / (side + cap)).abs() < 0.02);
+}
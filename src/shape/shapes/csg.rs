@@ -0,0 +1,313 @@
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    //the first minus the second
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+//solid combined from two closed shapes whose normals face outward.
+//the crossings of the ray with the boundaries of both are merged by the inside-outside states.
+#[derive(Clone)]
+pub struct Csg {
+    op: CsgOp,
+    a: Box<Shape>,
+    b: Box<Shape>,
+    aabb: AABB,
+    area: f32,
+}
+
+const MAX_CROSSINGS: usize = 64;
+const AREA_SAMPLES: usize = 4096;
+const MAX_REJECTIONS: usize = 1024;
+
+//crossings of the ray with the boundary after 'tnear' in order, and whether the ray enters there
+fn crossings(shape: &Shape, ray: &Ray, tnear: f32) -> Vec<(Hit, bool)> {
+    if let Shape::Csg(csg) = shape {
+        return csg.crossings(ray, tnear);
+    }
+    let tfar = f32::MAX / 2.0;
+    let mut result = vec![];
+    let mut t = tnear;
+    while result.len() < MAX_CROSSINGS {
+        let hit = match shape.test_hit(ray, t, tfar) {
+            Some(hit) => hit,
+            None => break,
+        };
+        t = hit.dist + 1e-5 * hit.dist.max(1.0);
+        let entering = hit.gnorm.dot(&ray.dir) < 0.0;
+        result.push((hit, entering));
+    }
+    result
+}
+
+//the ray leaves the solid first if it starts inside
+fn contains(shape: &Shape, p: &P3) -> bool {
+    let ray = Ray::new(*p, V3::new(0.5773, 0.5774, 0.5775).normalize());
+    crossings(shape, &ray, 0.0)
+        .first()
+        .is_some_and(|(_, entering)| !entering)
+}
+
+//whether the shape bounds a solid, so that its inside is known from the crossings
+fn is_closed(shape: &Shape) -> bool {
+    match shape {
+        Shape::Sphere(_)
+        | Shape::AARectangular(_)
+        | Shape::Torus(_)
+        | Shape::Implicit(_)
+        | Shape::Csg(_) => true,
+        Shape::Cylinder(s) => s.is_closed(),
+        Shape::Cone(s) => s.is_closed(),
+        Shape::Triangle(_)
+        | Shape::Parallelogram(_)
+        | Shape::Disk(_)
+        | Shape::Quadric(_)
+        | Shape::Curve(_) => false,
+    }
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Shape, b: Shape) -> Result<Self, String> {
+        if !is_closed(&a) || !is_closed(&b) {
+            return Err("the operands of csg have to be closed".into());
+        }
+        let (bound_a, bound_b) = (a.aabb(), b.aabb());
+        let aabb = match op {
+            CsgOp::Union => bound_a.merge(&bound_b),
+            CsgOp::Intersection => {
+                let mins = bound_a.mins.sup(&bound_b.mins);
+                let maxs = bound_a.maxs.inf(&bound_b.maxs);
+                AABB::new(&mins, &mins.sup(&maxs))
+            }
+            CsgOp::Difference => bound_a,
+        };
+        let mut csg = Csg {
+            op,
+            a: Box::new(a),
+            b: Box::new(b),
+            aabb,
+            area: 0.0,
+        };
+        //the fraction of the surfaces of the operands left on the boundary
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+        let accepted = (0..AREA_SAMPLES)
+            .filter(|_| csg.sample_operands(&mut rng).is_some())
+            .count();
+        if accepted == 0 {
+            return Err("the csg surface is empty".into());
+        }
+        csg.area = (csg.a.area() + csg.b.area()) * accepted as f32 / AREA_SAMPLES as f32;
+        Ok(csg)
+    }
+
+    pub fn union(a: Shape, b: Shape) -> Result<Self, String> {
+        Self::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: Shape, b: Shape) -> Result<Self, String> {
+        Self::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(a: Shape, b: Shape) -> Result<Self, String> {
+        Self::new(CsgOp::Difference, a, b)
+    }
+
+    fn crossings(&self, ray: &Ray, tnear: f32) -> Vec<(Hit, bool)> {
+        let xa = crossings(&self.a, ray, tnear);
+        let xb = crossings(&self.b, ray, tnear);
+        let starts_inside = |xs: &Vec<(Hit, bool)>| xs.first().is_some_and(|x| !x.1);
+        let (mut in_a, mut in_b) = (starts_inside(&xa), starts_inside(&xb));
+        let mut inside = self.op.inside(in_a, in_b);
+
+        let mut events: Vec<_> = xa
+            .into_iter()
+            .map(|x| (x, true))
+            .chain(xb.into_iter().map(|x| (x, false)))
+            .collect();
+        events.sort_by(|x, y| x.0 .0.dist.total_cmp(&y.0 .0.dist));
+
+        let mut result = vec![];
+        for ((mut hit, entering), from_a) in events {
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let now_inside = self.op.inside(in_a, in_b);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+            //the surface of the subtracted solid faces the other way
+            if entering != now_inside {
+                hit.gnorm = -hit.gnorm;
+            }
+            result.push((hit, now_inside));
+        }
+        result
+    }
+
+    //a point on the surface of an operand chosen by its area, if it is on the boundary
+    fn sample_operands<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(P3, V3)> {
+        let (area_a, area_b) = (self.a.area(), self.b.area());
+        let from_a = rng.gen::<f32>() * (area_a + area_b) < area_a;
+        let (this, other) = if from_a {
+            (&self.a, &self.b)
+        } else {
+            (&self.b, &self.a)
+        };
+        let (pos, normal) = this.sample_surface(rng).value;
+        let in_other = contains(other, &pos);
+        let on_boundary = match self.op {
+            CsgOp::Union => !in_other,
+            CsgOp::Intersection => in_other,
+            CsgOp::Difference => in_other != from_a,
+        };
+        if !on_boundary {
+            None
+        } else if self.op == CsgOp::Difference && !from_a {
+            Some((pos, -normal))
+        } else {
+            Some((pos, normal))
+        }
+    }
+}
+
+impl ShapeImpl for Csg {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        self.aabb.ray_intersect(ray, tnear, tfar)?;
+        self.crossings(ray, tnear)
+            .into_iter()
+            .map(|(hit, _)| hit)
+            .find(|hit| tnear < hit.dist && hit.dist < tfar)
+    }
+
+    //samples on the operands are rejected until one is on the boundary
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        let mut value = None;
+        for _ in 0..MAX_REJECTIONS {
+            value = self.sample_operands(rng);
+            if value.is_some() {
+                break;
+            }
+        }
+        pdf::PdfSample {
+            value: value.unwrap_or_else(|| self.a.sample_surface(rng).value),
+            pdf: 1.0 / self.area,
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area
+    }
+
+    fn aabb(&self) -> AABB {
+        self.aabb.clone()
+    }
+
+    //estimated from the fraction of the samples on the boundary
+    fn area(&self) -> f32 {
+        self.area
+    }
+
+    fn uv(&self, pos: &P3) -> P2 {
+        spherical_uv(&(pos - self.aabb.center()))
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        spherical_tangent(&(pos - self.aabb.center()))
+    }
+}
+
+#[test]
+fn test_csg() {
+    let ball = |z: f32, radius: f32| -> Shape {
+        Sphere {
+            center: P3::new(0.0, 0.0, z),
+            radius,
+        }
+        .into()
+    };
+    let cube =
+        || -> Shape { AARectangular(P3::new(-1.0, -1.0, -1.0), P3::new(1.0, 1.0, 1.0)).into() };
+    let down = V3::new(0.0, 0.0, -1.0);
+
+    //a cube with a spherical cavity
+    let hollow = Csg::difference(cube(), ball(0.0, 0.5)).unwrap();
+    let hit = hollow
+        .test_hit(&Ray::new(P3::new(0.0, 0.0, 5.0), down), 1e-3, 100.0)
+        .unwrap();
+    assert!((hit.dist - 4.0).abs() < 1e-4);
+    assert!((hit.gnorm - V3::z()).norm() < 1e-4);
+    let hit = hollow
+        .test_hit(&Ray::new(P3::new(0.0, 0.0, 0.9), down), 1e-3, 100.0)
+        .unwrap();
+    assert!((hit.dist - 0.4).abs() < 1e-4);
+    assert!((hit.gnorm + V3::z()).norm() < 1e-4);
+
+    //a lens of two overlapping balls
+    let lens = Csg::intersection(ball(-0.5, 1.0), ball(0.5, 1.0)).unwrap();
+    let hit = lens
+        .test_hit(&Ray::new(P3::new(0.0, 0.0, 5.0), down), 1e-3, 100.0)
+        .unwrap();
+    assert!((hit.pos - P3::new(0.0, 0.0, 0.5)).norm() < 1e-4);
+    assert!((hit.gnorm - V3::z()).norm() < 1e-4);
+    let sideways = Ray::new(P3::new(-5.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0));
+    let hit = lens.test_hit(&sideways, 1e-3, 100.0).unwrap();
+    assert!((hit.pos[0] + 0.75f32.sqrt()).abs() < 1e-4);
+    assert!(lens.test_hit(&sideways, 1e-3, 4.0).is_none());
+
+    //the surfaces inside the union are not hit
+    let pair = Csg::union(ball(-0.5, 1.0), ball(0.5, 1.0)).unwrap();
+    let hit = pair
+        .test_hit(&Ray::new(P3::new(0.0, 0.0, 1.0), down), 1e-3, 100.0)
+        .unwrap();
+    assert!((hit.pos - P3::new(0.0, 0.0, -1.5)).norm() < 1e-4);
+    let nested: Shape = pair.into();
+    let carved = Csg::difference(nested, cube()).unwrap();
+    assert!(carved
+        .test_hit(&Ray::new(P3::new(0.0, 0.0, 5.0), down), 1e-3, 100.0)
+        .map(|hit| (hit.pos[2] - 1.5).abs() < 1e-4)
+        .unwrap());
+
+    //each ball keeps the part of its sphere outside the other
+    let pair = Csg::union(ball(-0.5, 1.0), ball(0.5, 1.0)).unwrap();
+    let expected = 2.0 * 3.0 * std::f32::consts::PI;
+    assert!((pair.area() - expected).abs() < expected * 0.05);
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(1);
+    for _ in 0..100 {
+        let (pos, normal) = pair.sample_surface(&mut rng).value;
+        let center = P3::new(0.0, 0.0, if pos[2] < 0.0 { -0.5 } else { 0.5 });
+        assert!(((pos - center).norm() - 1.0).abs() < 1e-4);
+        assert!((normal - (pos - center)).norm() < 1e-4);
+    }
+
+    //open or empty results have no inside or no surface to sample
+    let square: Shape = Parallelogram::new(
+        &P3::new(0.0, 0.0, 0.0),
+        &P3::new(1.0, 0.0, 0.0),
+        &P3::new(0.0, 1.0, 0.0),
+    )
+    .into();
+    assert!(Csg::union(ball(0.0, 1.0), square).is_err());
+    assert!(Csg::intersection(ball(-2.0, 1.0), ball(2.0, 1.0)).is_err());
+    assert!(Csg::difference(ball(0.0, 0.5), cube()).is_err());
+    assert!(Csg::union(
+        ball(0.0, 1.0),
+        Cylinder::new(&P3::origin(), &V3::z(), 1.0, false).into()
+    )
+    .is_err());
+}
@@ -0,0 +1,427 @@
+use super::*;
+
+//how the width of a curve makes a surface
+#[derive(Clone, Debug)]
+pub enum CurveKind {
+    //ribbon always facing the ray, for fibers too thin to be seen as tubes
+    Flat,
+    //tube whose hits are moved from the ray-facing ribbon onto its surface
+    Cylinder,
+    //ribbon turning from the first normal to the second, like a blade of grass
+    Ribbon([V3; 2]),
+}
+
+//cubic bezier curve swept with the width changing linearly from the first point to the last.
+//hits are found by subdividing the curve in the coordinates of the ray (Nakamaru and Ohno 2002).
+#[derive(Clone)]
+pub struct Curve {
+    cps: [P3; 4],
+    widths: [f32; 2],
+    kind: CurveKind,
+    //chord lengths of the pieces by which the surface is sampled
+    lengths: [f32; PIECES],
+    area: f32,
+}
+
+const PIECES: usize = 16;
+const MAX_DEPTH: i32 = 10;
+
+fn eval_bezier(cps: &[P3; 4], u: f32) -> P3 {
+    let lerp = |a: &P3, b: &P3| P3::from(a.coords * (1.0 - u) + b.coords * u);
+    let (a, b, c) = (
+        lerp(&cps[0], &cps[1]),
+        lerp(&cps[1], &cps[2]),
+        lerp(&cps[2], &cps[3]),
+    );
+    let (d, e) = (lerp(&a, &b), lerp(&b, &c));
+    lerp(&d, &e)
+}
+
+fn bezier_derivative(cps: &[P3; 4], u: f32) -> V3 {
+    let (d0, d1, d2) = (cps[1] - cps[0], cps[2] - cps[1], cps[3] - cps[2]);
+    3.0 * ((1.0 - u) * (1.0 - u) * d0 + 2.0 * u * (1.0 - u) * d1 + u * u * d2)
+}
+
+fn bezier_second_derivative(cps: &[P3; 4], u: f32) -> V3 {
+    let a = cps[2].coords - 2.0 * cps[1].coords + cps[0].coords;
+    let b = cps[3].coords - 2.0 * cps[2].coords + cps[1].coords;
+    6.0 * ((1.0 - u) * a + u * b)
+}
+
+//halves at u = 0.5 by de casteljau's algorithm
+fn split_bezier(cps: &[P3; 4]) -> ([P3; 4], [P3; 4]) {
+    let mid = |a: &P3, b: &P3| P3::from((a.coords + b.coords) / 2.0);
+    let (a, b, c) = (
+        mid(&cps[0], &cps[1]),
+        mid(&cps[1], &cps[2]),
+        mid(&cps[2], &cps[3]),
+    );
+    let (d, e) = (mid(&a, &b), mid(&b, &c));
+    let m = mid(&d, &e);
+    ([cps[0], a, d, m], [m, e, c, cps[3]])
+}
+
+impl Curve {
+    pub fn new(cps: [P3; 4], widths: [f32; 2], kind: CurveKind) -> Self {
+        assert!(widths[0] > 0.0 && widths[1] > 0.0);
+        let mut lengths = [0.0; PIECES];
+        for (i, l) in lengths.iter_mut().enumerate() {
+            let (u0, u1) = (i as f32 / PIECES as f32, (i + 1) as f32 / PIECES as f32);
+            *l = (eval_bezier(&cps, u1) - eval_bezier(&cps, u0)).norm();
+        }
+        let mut curve = Curve {
+            cps,
+            widths,
+            kind,
+            lengths,
+            area: 0.0,
+        };
+        //the width is linear in u, so its mean over a piece is the one at the middle
+        let around = match curve.kind {
+            CurveKind::Ribbon(_) => 1.0,
+            _ => std::f32::consts::PI,
+        };
+        curve.area = (0..PIECES)
+            .map(|i| curve.lengths[i] * curve.width_at((i as f32 + 0.5) / PIECES as f32))
+            .sum::<f32>()
+            * around;
+        curve
+    }
+
+    //catmull-rom spline through the points as a chain of curves, tapering from the first point
+    pub fn new_strand(
+        points: &[P3],
+        root_width: f32,
+        tip_width: f32,
+        kind: CurveKind,
+    ) -> Vec<Curve> {
+        assert!(points.len() >= 2);
+        let n = points.len() - 1;
+        let at = |i: isize| points[i.clamp(0, n as isize) as usize];
+        let width = |i: usize| root_width + (tip_width - root_width) * i as f32 / n as f32;
+        (0..n)
+            .map(|i| {
+                let i_ = i as isize;
+                let (p0, p1, p2, p3) = (at(i_ - 1), at(i_), at(i_ + 1), at(i_ + 2));
+                let cps = [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2];
+                Curve::new(cps, [width(i), width(i + 1)], kind.clone())
+            })
+            .collect()
+    }
+
+    fn width_at(&self, u: f32) -> f32 {
+        self.widths[0] * (1.0 - u) + self.widths[1] * u
+    }
+
+    fn axis_tangent(&self, u: f32) -> V3 {
+        let d = bezier_derivative(&self.cps, u);
+        if d.norm_squared() > 0.0 {
+            d.normalize()
+        } else {
+            (self.cps[3] - self.cps[0]).normalize()
+        }
+    }
+
+    //normal of the ribbon perpendicular to the axis
+    fn ribbon_normal(normals: &[V3; 2], u: f32, tangent: &V3) -> V3 {
+        let n = normals[0] * (1.0 - u) + normals[1] * u;
+        let n = n - tangent * n.dot(tangent);
+        if n.norm_squared() > 0.0 {
+            n.normalize()
+        } else {
+            pick_orthogonal(tangent)
+        }
+    }
+
+    //u of the point on the axis nearest to 'pos'
+    fn closest_u(&self, pos: &P3) -> f32 {
+        let sq_dist = |u: f32| (eval_bezier(&self.cps, u) - pos).norm_squared();
+        let mut u = (0..=PIECES)
+            .map(|i| i as f32 / PIECES as f32)
+            .min_by(|a, b| sq_dist(*a).partial_cmp(&sq_dist(*b)).unwrap())
+            .unwrap();
+        //refined by newton's method on the derivative of the squared distance
+        for _ in 0..4 {
+            let r = eval_bezier(&self.cps, u) - pos;
+            let d1 = bezier_derivative(&self.cps, u);
+            let d2 = bezier_second_derivative(&self.cps, u);
+            let df = d1.norm_squared() + r.dot(&d2);
+            if df <= 0.0 {
+                break;
+            }
+            u = (u - r.dot(&d1) / df).clamp(0.0, 1.0);
+        }
+        u
+    }
+
+    //subdivisions needed for the pieces to be flat within a fraction of the width
+    fn max_depth(&self, cps: &[P3; 4]) -> i32 {
+        let l0 = (0..2)
+            .map(|i| (cps[i].coords - 2.0 * cps[i + 1].coords + cps[i + 2].coords).amax())
+            .fold(0.0, f32::max);
+        let eps = 0.05 * self.widths[0].max(self.widths[1]);
+        if l0 <= 0.0 {
+            return 0;
+        }
+        let r0 = (std::f32::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps))
+            .log2()
+            .floor() as i32
+            / 2;
+        r0.clamp(0, MAX_DEPTH)
+    }
+
+    //finds the nearest (z, u) of the piece in the coordinates of the ray, whose direction is 'dir'.
+    //the fibers at the ends of the ray are skipped, as the paths through them are in their bsdf.
+    fn hit_piece(
+        &self,
+        cps: &[P3; 4],
+        (u0, u1): (f32, f32),
+        depth: i32,
+        dir: &V3,
+        (znear, zfar): (f32, f32),
+        nearest: &mut Option<(f32, f32)>,
+    ) {
+        let zmax = nearest.map_or(zfar, |h| h.0);
+        let half_width = 0.5 * self.width_at(u0).max(self.width_at(u1));
+        let (mut lo, mut hi) = (cps[0], cps[0]);
+        for p in cps[1..].iter() {
+            lo = lo.inf(p);
+            hi = hi.sup(p);
+        }
+        if lo[0] - half_width > 0.0
+            || hi[0] + half_width < 0.0
+            || lo[1] - half_width > 0.0
+            || hi[1] + half_width < 0.0
+            || hi[2] + half_width < znear
+            || lo[2] - half_width > zmax
+        {
+            return;
+        }
+
+        if depth > 0 {
+            let (a, b) = split_bezier(cps);
+            let um = 0.5 * (u0 + u1);
+            self.hit_piece(&a, (u0, um), depth - 1, dir, (znear, zfar), nearest);
+            self.hit_piece(&b, (um, u1), depth - 1, dir, (znear, zfar), nearest);
+            return;
+        }
+
+        //the ray must pass between the planes perpendicular to the piece at its ends
+        let edge = (cps[1][1] - cps[0][1]) * -cps[0][1] + cps[0][0] * (cps[0][0] - cps[1][0]);
+        if edge < 0.0 {
+            return;
+        }
+        let edge = (cps[2][1] - cps[3][1]) * -cps[3][1] + cps[3][0] * (cps[3][0] - cps[2][0]);
+        if edge < 0.0 {
+            return;
+        }
+        let segment = V2::new(cps[3][0] - cps[0][0], cps[3][1] - cps[0][1]);
+        let denom = segment.norm_squared();
+        if denom == 0.0 {
+            return;
+        }
+        let w = -V2::new(cps[0][0], cps[0][1]).dot(&segment) / denom;
+        let u = (u0 + (u1 - u0) * w).clamp(u0, u1);
+        let width = self.width_at(u);
+        let hit_width = match &self.kind {
+            CurveKind::Ribbon(normals) => {
+                let tangent = self.axis_tangent(u);
+                width * Self::ribbon_normal(normals, u, &tangent).dot(dir).abs()
+            }
+            _ => width,
+        };
+        let pc = eval_bezier(cps, w.clamp(0.0, 1.0));
+        if pc[0] * pc[0] + pc[1] * pc[1] > hit_width * hit_width / 4.0 {
+            return;
+        }
+        if pc[2] < znear.max(width) || pc[2] > zmax.min(zfar - width) {
+            return;
+        }
+        *nearest = Some((pc[2], u));
+    }
+
+    fn make_hit(&self, ray: &Ray, dist: f32, u: f32) -> Hit {
+        let tangent = self.axis_tangent(u);
+        let dir = ray.dir.normalize();
+        let across = dir - tangent * dir.dot(&tangent);
+        let across = if across.norm_squared() > 1e-12 {
+            across.normalize()
+        } else {
+            pick_orthogonal(&tangent)
+        };
+        let pos = ray.at(dist);
+        let (dist, pos, gnorm) = match &self.kind {
+            CurveKind::Flat => (dist, pos, -across),
+            CurveKind::Cylinder => {
+                let center = eval_bezier(&self.cps, u);
+                let radius = 0.5 * self.width_at(u);
+                let side = tangent.cross(&across);
+                let h = ((pos - center).dot(&side) / radius).clamp(-1.0, 1.0);
+                let n = side * h - across * (1.0 - h * h).sqrt();
+                let pos = center + n * radius;
+                let dist = (pos - ray.origin).dot(&ray.dir) / ray.dir.norm_squared();
+                (dist, pos, n)
+            }
+            CurveKind::Ribbon(normals) => (dist, pos, Self::ribbon_normal(normals, u, &tangent)),
+        };
+        Hit {
+            dist,
+            pos,
+            gnorm,
+            gx: tangent,
+        }
+    }
+}
+
+impl ShapeImpl for Curve {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let len = ray.dir.norm();
+        let dir = ray.dir / len;
+        let lc = LocalCoord::new_zx(&ray.origin, &dir, &pick_orthogonal(&dir));
+        let cps = [
+            lc.w2l() * self.cps[0],
+            lc.w2l() * self.cps[1],
+            lc.w2l() * self.cps[2],
+            lc.w2l() * self.cps[3],
+        ];
+        let mut nearest = None;
+        let range = (tnear * len, tfar * len);
+        self.hit_piece(
+            &cps,
+            (0.0, 1.0),
+            self.max_depth(&cps),
+            &dir,
+            range,
+            &mut nearest,
+        );
+        let (z, u) = nearest?;
+        Some(self.make_hit(ray, z / len, u))
+    }
+
+    //pieces are chosen by their lengths and the points along them by the width
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        let total: f32 = self.lengths.iter().sum();
+        let max_width = self.widths[0].max(self.widths[1]);
+        let u = loop {
+            let mut x = rng.gen::<f32>() * total;
+            let mut i = 0;
+            while i + 1 < PIECES && x >= self.lengths[i] {
+                x -= self.lengths[i];
+                i += 1;
+            }
+            let u = (i as f32 + rng.gen::<f32>()) / PIECES as f32;
+            if rng.gen::<f32>() * max_width < self.width_at(u) {
+                break u;
+            }
+        };
+        let center = eval_bezier(&self.cps, u);
+        let tangent = self.axis_tangent(u);
+        let (pos, normal) = match &self.kind {
+            CurveKind::Ribbon(normals) => {
+                let side = tangent.cross(&Self::ribbon_normal(normals, u, &tangent));
+                let offset = (rng.gen::<f32>() - 0.5) * self.width_at(u);
+                (
+                    center + side * offset,
+                    Self::ribbon_normal(normals, u, &tangent),
+                )
+            }
+            //flat curves are sampled as the tubes they stand for
+            _ => {
+                let a = pick_orthogonal(&tangent);
+                let b = tangent.cross(&a);
+                let phi = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
+                let n = a * phi.cos() + b * phi.sin();
+                (center + n * (0.5 * self.width_at(u)), n)
+            }
+        };
+        pdf::PdfSample {
+            value: (pos, normal),
+            pdf: 1.0 / self.area,
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area
+    }
+
+    fn aabb(&self) -> AABB {
+        let half_width = 0.5 * self.widths[0].max(self.widths[1]);
+        let m = V3::new(half_width, half_width, half_width);
+        let mut aabb = AABB::single_point(&self.cps[0]);
+        for p in self.cps[1..].iter() {
+            aabb = aabb.include_nomargin(p);
+        }
+        AABB::new(&(aabb.mins - m), &(aabb.maxs + m))
+    }
+
+    fn area(&self) -> f32 {
+        self.area
+    }
+
+    //u along the curve and v across the ribbon or around the tube
+    fn uv(&self, pos: &P3) -> P2 {
+        let u = self.closest_u(pos);
+        let tangent = self.axis_tangent(u);
+        let offset = pos - eval_bezier(&self.cps, u);
+        let v = match &self.kind {
+            CurveKind::Ribbon(normals) => {
+                let side = tangent.cross(&Self::ribbon_normal(normals, u, &tangent));
+                0.5 + offset.dot(&side) / self.width_at(u)
+            }
+            _ => {
+                let a = pick_orthogonal(&tangent);
+                let b = tangent.cross(&a);
+                0.5 + offset.dot(&b).atan2(offset.dot(&a)) * std::f32::consts::FRAC_1_PI / 2.0
+            }
+        };
+        P2::new(u, v)
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        self.axis_tangent(self.closest_u(pos))
+    }
+}
+
+#[test]
+fn test_curve_hit() {
+    //a straight tube along z of radius 0.1
+    let cps = [
+        P3::new(0.0, 0.0, -1.0),
+        P3::new(0.0, 0.0, -1.0 / 3.0),
+        P3::new(0.0, 0.0, 1.0 / 3.0),
+        P3::new(0.0, 0.0, 1.0),
+    ];
+    let tube = Curve::new(cps, [0.2, 0.2], CurveKind::Cylinder);
+    let ray = Ray::new(P3::new(0.05, -5.0, 0.2), V3::new(0.0, 1.0, 0.0));
+    let hit = tube.test_hit(&ray, 1e-3, 100.0).unwrap();
+    assert!((hit.pos - P3::new(0.05, -0.0866, 0.2)).norm() < 1e-3);
+    assert!((hit.gnorm - V3::new(0.5, -0.866, 0.0)).norm() < 1e-3);
+    assert!((hit.dist - (5.0 - 0.0866)).abs() < 1e-3);
+    assert!((tube.tangent(&hit.pos) - V3::z()).norm() < 1e-4);
+    assert!((tube.uv(&hit.pos)[0] - 0.6).abs() < 1e-3);
+
+    let miss = Ray::new(P3::new(0.15, -5.0, 0.2), V3::new(0.0, 1.0, 0.0));
+    assert!(tube.test_hit(&miss, 1e-3, 100.0).is_none());
+    //rays leaving the fiber pass through it
+    let leaving = Ray::new(hit.pos, V3::new(0.0, 1.0, 0.0));
+    assert!(tube.test_hit(&leaving, 1e-3, 100.0).is_none());
+
+    let strand = Curve::new_strand(
+        &[
+            P3::new(0.0, 0.0, 0.0),
+            P3::new(1.0, 0.0, 0.0),
+            P3::new(2.0, 0.0, 0.0),
+        ],
+        0.1,
+        0.05,
+        CurveKind::Flat,
+    );
+    assert_eq!(strand.len(), 2);
+    let down = V3::new(0.0, 0.0, -1.0);
+    assert!(strand[0]
+        .test_hit(&Ray::new(P3::new(0.5, 0.0, 5.0), down), 1e-3, 100.0)
+        .is_some());
+    assert!(strand[1]
+        .test_hit(&Ray::new(P3::new(0.5, 0.0, 5.0), down), 1e-3, 100.0)
+        .is_none());
+}
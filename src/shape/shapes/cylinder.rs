@@ -0,0 +1,169 @@
+use super::*;
+
+//cylinder from 'base' to 'base + axis'. a closed cylinder has caps on both ends.
+#[derive(Clone)]
+pub struct Cylinder {
+    lc: LocalCoord,
+    radius: f32,
+    height: f32,
+    closed: bool,
+}
+
+impl Cylinder {
+    pub fn new(base: &P3, axis: &V3, radius: f32, closed: bool) -> Self {
+        Cylinder {
+            lc: axis_frame(base, axis),
+            radius,
+            height: axis.norm(),
+            closed,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn side_area(&self) -> f32 {
+        2.0 * std::f32::consts::PI * self.radius * self.height
+    }
+
+    fn cap_area(&self) -> f32 {
+        std::f32::consts::PI * self.radius * self.radius
+    }
+
+    //(dist, position, normal, tangent) in local coordinates
+    fn hit_local(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<(f32, P3, V3, V3)> {
+        let (o, d) = (ray.origin, ray.dir);
+        let mut nearest = None::<(f32, P3, V3, V3)>;
+        let mut update = |t: f32, p: P3, n: V3, x: V3| {
+            if tnear < t && t < nearest.as_ref().map_or(tfar, |h| h.0) {
+                nearest = Some((t, p, n, x));
+            }
+        };
+
+        let a = d[0] * d[0] + d[1] * d[1];
+        let b = 2.0 * (o[0] * d[0] + o[1] * d[1]);
+        let c = o[0] * o[0] + o[1] * o[1] - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for &t in [t0, t1].iter() {
+                let p = o + d * t;
+                if 0.0 <= p[2] && p[2] <= self.height {
+                    let n = V3::new(p[0], p[1], 0.0).normalize();
+                    update(t, p, n, V3::new(-n[1], n[0], 0.0));
+                }
+            }
+        }
+
+        if self.closed && d[2] != 0.0 {
+            for &(z, nz) in [(0.0, -1.0), (self.height, 1.0)].iter() {
+                let t = (z - o[2]) / d[2];
+                let p = o + d * t;
+                if p[0] * p[0] + p[1] * p[1] <= self.radius * self.radius {
+                    update(t, p, V3::new(0.0, 0.0, nz), V3::x());
+                }
+            }
+        }
+        nearest
+    }
+}
+
+impl ShapeImpl for Cylinder {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let ray_local = self.lc.w2l() * ray.clone();
+        self.hit_local(&ray_local, tnear, tfar)
+            .map(|(dist, pos, n, x)| Hit {
+                dist,
+                pos: self.lc.l2w() * pos,
+                gnorm: self.lc.l2w() * n,
+                gx: self.lc.l2w() * x,
+            })
+    }
+
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        use rand::distributions::Uniform;
+        let u = Uniform::new(0.0, self.area()).sample(rng);
+        let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+        let (p, n) = if u < self.side_area() {
+            let z = Uniform::new(0.0, self.height).sample(rng);
+            let n = V3::new(theta.cos(), theta.sin(), 0.0);
+            (P3::new(0.0, 0.0, z) + n * self.radius, n)
+        } else {
+            let r = self.radius * Uniform::new(0.0f32, 1.0).sample(rng).sqrt();
+            let top = u > self.side_area() + self.cap_area();
+            let z = if top { self.height } else { 0.0 };
+            let nz = if top { 1.0 } else { -1.0 };
+            (
+                P3::new(r * theta.cos(), r * theta.sin(), z),
+                V3::new(0.0, 0.0, nz),
+            )
+        };
+        pdf::PdfSample {
+            value: (self.lc.l2w() * p, self.lc.l2w() * n),
+            pdf: 1.0 / self.area(),
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area()
+    }
+
+    fn aabb(&self) -> AABB {
+        let r = self.radius;
+        AABB::new(&P3::new(-r, -r, 0.0), &P3::new(r, r, self.height)).transformed(self.lc.l2w())
+    }
+
+    //the side is parametrized by the angle and the height, and the caps are projected
+    fn uv(&self, pos: &P3) -> P2 {
+        let p = self.lc.w2l() * pos;
+        let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        if self.closed && rho < self.radius * (1.0 - 1e-4) {
+            planar_uv(&p, self.radius)
+        } else {
+            P2::new(spherical_uv(&p.coords)[0], p[2] / self.height)
+        }
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        let p = self.lc.w2l() * pos;
+        let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        if self.closed && rho < self.radius * (1.0 - 1e-4) {
+            self.lc.u()
+        } else {
+            self.lc.l2w() * spherical_tangent(&p.coords)
+        }
+    }
+
+    fn area(&self) -> f32 {
+        if self.closed {
+            self.side_area() + 2.0 * self.cap_area()
+        } else {
+            self.side_area()
+        }
+    }
+}
+
+#[test]
+fn test_cylinder() {
+    let cylinder = Cylinder::new(&P3::origin(), &V3::new(0.0, 0.0, 2.0), 1.0, true);
+    let hit = cylinder
+        .test_hit(&Ray::new(P3::new(5.0, 0.0, 1.0), -V3::x()), 0.0, 10.0)
+        .unwrap();
+    assert!((hit.dist - 4.0).abs() < 1e-4);
+    assert!((hit.gnorm - V3::x()).norm() < 1e-4);
+    let down = Ray::new(P3::new(0.5, 0.0, 5.0), -V3::z());
+    let hit = cylinder.test_hit(&down, 0.0, 10.0).unwrap();
+    assert!((hit.dist - 3.0).abs() < 1e-4);
+    assert!((hit.gnorm - V3::z()).norm() < 1e-4);
+    let open = Cylinder::new(&P3::origin(), &V3::new(0.0, 0.0, 2.0), 1.0, false);
+    assert!(open.test_hit(&down, 0.0, 10.0).is_none());
+
+    let pi = std::f32::consts::PI;
+    assert!((cylinder.area() - 6.0 * pi).abs() < 1e-4);
+    assert!((open.area() - 4.0 * pi).abs() < 1e-4);
+    //each cap and each half of the side holds a third of the area
+    let rho = |p: &P3| (p[0] * p[0] + p[1] * p[1]).sqrt();
+    let caps = sample_fraction(&cylinder, |p| rho(p) < 0.999);
+    assert!((caps - 1.0 / 3.0).abs() < 0.02);
+    let upper = sample_fraction(&cylinder, |p| rho(p) >= 0.999 && p[2] > 1.0);
+    assert!((upper - 1.0 / 3.0).abs() < 0.02);
+}
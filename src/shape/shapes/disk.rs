@@ -0,0 +1,118 @@
+use super::*;
+
+//disk facing 'normal', or an annulus if inner_radius > 0
+#[derive(Clone)]
+pub struct Disk {
+    lc: LocalCoord,
+    radius: f32,
+    inner_radius: f32,
+}
+
+impl Disk {
+    pub fn new(center: &P3, normal: &V3, radius: f32) -> Self {
+        Self::new_annulus(center, normal, radius, 0.0)
+    }
+
+    pub fn new_annulus(center: &P3, normal: &V3, radius: f32, inner_radius: f32) -> Self {
+        assert!(0.0 <= inner_radius && inner_radius < radius);
+        Disk {
+            lc: axis_frame(center, normal),
+            radius,
+            inner_radius,
+        }
+    }
+
+    pub fn normal(&self) -> V3 {
+        self.lc.w()
+    }
+}
+
+impl ShapeImpl for Disk {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let ray_local = self.lc.w2l() * ray.clone();
+        if ray_local.dir[2] == 0.0 {
+            return None;
+        }
+        let dist = -ray_local.origin[2] / ray_local.dir[2];
+        if !(tnear < dist && dist < tfar) {
+            return None;
+        }
+        let pos_local = ray_local.at(dist);
+        let sq_r = pos_local[0] * pos_local[0] + pos_local[1] * pos_local[1];
+        if sq_r > self.radius * self.radius || sq_r < self.inner_radius * self.inner_radius {
+            return None;
+        }
+        Some(Hit {
+            dist,
+            pos: self.lc.l2w() * P3::new(pos_local[0], pos_local[1], 0.0),
+            gnorm: self.normal(),
+            gx: self.lc.u(),
+        })
+    }
+
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        use rand::distributions::Uniform;
+        let sq_inner = self.inner_radius * self.inner_radius;
+        let sq_r = Uniform::new(sq_inner, self.radius * self.radius).sample(rng);
+        let theta = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI).sample(rng);
+        let r = sq_r.sqrt();
+        let p_local = P3::new(r * theta.cos(), r * theta.sin(), 0.0);
+        pdf::PdfSample {
+            value: (self.lc.l2w() * p_local, self.normal()),
+            pdf: 1.0 / self.area(),
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area()
+    }
+
+    fn aabb(&self) -> AABB {
+        let r = self.radius;
+        //flat boxes are padded in proportion to the size
+        let eps = r * 1e-4;
+        AABB::new(&P3::new(-r, -r, -eps), &P3::new(r, r, eps)).transformed(self.lc.l2w())
+    }
+
+    fn uv(&self, pos: &P3) -> P2 {
+        planar_uv(&(self.lc.w2l() * pos), self.radius)
+    }
+
+    fn tangent(&self, _pos: &P3) -> V3 {
+        self.lc.u()
+    }
+
+    fn area(&self) -> f32 {
+        std::f32::consts::PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+}
+
+#[test]
+fn test_disk() {
+    let center = P3::new(1.0, 2.0, 3.0);
+    let normal = V3::new(1.0, 1.0, 0.0).normalize();
+    let side = V3::new(1.0, -1.0, 0.0).normalize();
+    let disk = Disk::new_annulus(&center, &normal, 2.0, 1.0);
+    let at = |r: f32| {
+        disk.test_hit(
+            &Ray::new(center + side * r + normal * 5.0, -normal),
+            0.0,
+            10.0,
+        )
+    };
+    let hit = at(1.5).unwrap();
+    assert!((hit.dist - 5.0).abs() < 1e-4);
+    assert!((hit.gnorm - normal).norm() < 1e-4);
+    assert!(at(0.5).is_none());
+    assert!(at(2.5).is_none());
+
+    //the bounds of small disks stay thin
+    let small = Disk::new(&P3::origin(), &V3::z(), 1e-3).aabb();
+    assert!(small.diag()[2] > 0.0 && small.diag()[2] < 1e-5);
+
+    let area = 3.0 * std::f32::consts::PI;
+    assert!((disk.area() - area).abs() < 1e-4);
+    //the inner part of the annulus holds (1.5^2 - 1) / (2^2 - 1) of the area
+    let inner = sample_fraction(&disk, |p| (p - center).norm() < 1.5);
+    assert!((inner - 1.25 / 3.0).abs() < 0.02);
+}
@@ -0,0 +1,389 @@
+use super::*;
+
+//signed distance to a surface, negative inside, built as a tree of primitives and operations
+#[derive(Clone, Debug)]
+pub enum Sdf {
+    Sphere(f32),
+    //half extents
+    Cuboid(V3),
+    //major and minor radius around the z axis
+    Torus(f32, f32),
+    //radius and half height along the z axis
+    Cylinder(f32, f32),
+    //segment and radius
+    Capsule(P3, P3, f32),
+    //power and iterations of the fractal, whose distance is a lower bound
+    Mandelbulb(f32, usize),
+    Union(Box<Sdf>, Box<Sdf>),
+    //union blending the surfaces within the distance
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f32),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    //the first minus the second
+    Subtraction(Box<Sdf>, Box<Sdf>),
+    //local to world
+    Transformed(Isometry3<f32>, Box<Sdf>),
+    Scaled(f32, Box<Sdf>),
+}
+
+impl Sdf {
+    pub fn union(self, other: Sdf) -> Self {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, blend: f32) -> Self {
+        assert!(blend > 0.0);
+        Sdf::SmoothUnion(Box::new(self), Box::new(other), blend)
+    }
+
+    pub fn intersection(self, other: Sdf) -> Self {
+        Sdf::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn subtraction(self, other: Sdf) -> Self {
+        Sdf::Subtraction(Box::new(self), Box::new(other))
+    }
+
+    pub fn transformed(self, l2w: Isometry3<f32>) -> Self {
+        Sdf::Transformed(l2w, Box::new(self))
+    }
+
+    pub fn translated(self, v: &V3) -> Self {
+        self.transformed(Isometry3::translation(v[0], v[1], v[2]))
+    }
+
+    pub fn scaled(self, scale: f32) -> Self {
+        assert!(scale > 0.0);
+        Sdf::Scaled(scale, Box::new(self))
+    }
+
+    pub fn distance(&self, p: &P3) -> f32 {
+        use Sdf::*;
+        match self {
+            Sphere(r) => p.coords.norm() - r,
+            Cuboid(half) => {
+                let q = p.coords.abs() - half;
+                q.sup(&V3::zeros()).norm() + q.max().min(0.0)
+            }
+            Torus(major, minor) => {
+                let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+                V2::new(rho - major, p[2]).norm() - minor
+            }
+            Cylinder(radius, half_height) => {
+                let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+                let d = V2::new(rho - radius, p[2].abs() - half_height);
+                d.max().min(0.0) + d.sup(&V2::zeros()).norm()
+            }
+            Capsule(a, b, radius) => {
+                let (pa, ba) = (p - a, b - a);
+                let h = (pa.dot(&ba) / ba.norm_squared()).clamp(0.0, 1.0);
+                (pa - ba * h).norm() - radius
+            }
+            Mandelbulb(power, iterations) => {
+                let mut z = p.coords;
+                let (mut dr, mut r) = (1.0, 0.0);
+                for _ in 0..*iterations {
+                    r = z.norm();
+                    if r > 2.0 {
+                        break;
+                    }
+                    let theta = (z[2] / r.max(1e-12)).clamp(-1.0, 1.0).acos() * power;
+                    let phi = z[1].atan2(z[0]) * power;
+                    dr = r.powf(power - 1.0) * power * dr + 1.0;
+                    let zr = r.powf(*power);
+                    z =
+                        zr * V3::new(
+                            theta.sin() * phi.cos(),
+                            theta.sin() * phi.sin(),
+                            theta.cos(),
+                        ) + p.coords;
+                }
+                0.5 * r.max(1e-12).ln() * r / dr
+            }
+            Union(a, b) => a.distance(p).min(b.distance(p)),
+            //polynomial smooth minimum
+            SmoothUnion(a, b, k) => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = (k - (da - db).abs()).max(0.0) / k;
+                da.min(db) - h * h * k / 4.0
+            }
+            Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Subtraction(a, b) => a.distance(p).max(-b.distance(p)),
+            Transformed(l2w, s) => s.distance(&l2w.inverse_transform_point(p)),
+            Scaled(scale, s) => s.distance(&(p / *scale)) * scale,
+        }
+    }
+
+    //box containing the surface
+    pub fn bound(&self) -> AABB {
+        use Sdf::*;
+        let centered = |half: V3| AABB::new(&P3::from(-half), &P3::from(half));
+        match self {
+            Sphere(r) => centered(V3::new(*r, *r, *r)),
+            Cuboid(half) => centered(*half),
+            Torus(major, minor) => centered(V3::new(major + minor, major + minor, *minor)),
+            Cylinder(radius, half_height) => centered(V3::new(*radius, *radius, *half_height)),
+            Capsule(a, b, radius) => {
+                let r = V3::new(*radius, *radius, *radius);
+                AABB::new(&(a.inf(b) - r), &(a.sup(b) + r))
+            }
+            Mandelbulb(..) => centered(V3::new(1.25, 1.25, 1.25)),
+            Union(a, b) => a.bound().merge(&b.bound()),
+            //the blend lowers the distance by at most a quarter of its width
+            SmoothUnion(a, b, k) => {
+                let aabb = a.bound().merge(&b.bound());
+                let m = V3::new(*k, *k, *k) / 4.0;
+                AABB::new(&(aabb.mins - m), &(aabb.maxs + m))
+            }
+            Intersection(a, b) => {
+                let (a, b) = (a.bound(), b.bound());
+                let (mins, maxs) = (a.mins.sup(&b.mins), a.maxs.inf(&b.maxs));
+                AABB::new(&mins, &mins.sup(&maxs))
+            }
+            Subtraction(a, _) => a.bound(),
+            Transformed(l2w, s) => s.bound().transformed(l2w),
+            Scaled(scale, s) => {
+                let aabb = s.bound();
+                AABB::new(&(aabb.mins * *scale), &(aabb.maxs * *scale))
+            }
+        }
+    }
+}
+
+//surface of the signed distance field, intersected by sphere tracing in its bound
+#[derive(Clone)]
+pub struct Implicit {
+    sdf: Sdf,
+    aabb: AABB,
+    //distance within which points are taken to be on the surface
+    eps: f32,
+    area: f32,
+}
+
+const GRID: usize = 64;
+const MAX_STEPS: usize = 1024;
+//lines crossing the surface more often are sampled as if they crossed it this many times
+const MAX_CROSSINGS: usize = 16;
+const AREA_LINES: usize = 1 << 14;
+const MAX_REJECTIONS: usize = 4096;
+
+impl Implicit {
+    //fails if no cell of a grid over the bound contains the surface
+    pub fn new(sdf: Sdf) -> Result<Self, String> {
+        let aabb = sdf.bound();
+        let diag = aabb.diag();
+        let cell = diag.max() / GRID as f32;
+        let dims = diag.map(|x| ((x / cell).ceil() as usize).max(1));
+        let empty = (0..dims[0] * dims[1] * dims[2]).all(|i| {
+            let ix = V3::new(
+                (i / (dims[1] * dims[2])) as f32,
+                (i / dims[2] % dims[1]) as f32,
+                (i % dims[2]) as f32,
+            );
+            let p = aabb.mins + (ix + V3::new(0.5, 0.5, 0.5)) * cell;
+            sdf.distance(&p).abs() >= cell
+        });
+        if empty {
+            return Err("the implicit surface is empty".into());
+        }
+        let mut implicit = Implicit {
+            sdf,
+            eps: 1e-5 * diag.norm(),
+            aabb,
+            area: 0.0,
+        };
+        //crofton's formula: the expected number of crossings with a random line through
+        //the bounding sphere is 2 area / (4 pi r^2)
+        let mut rng = SmallRng::seed_from_u64(0);
+        let count: usize = (0..AREA_LINES)
+            .map(|_| {
+                let (o, d) = random_line(&implicit.aabb, &mut rng);
+                implicit.crossings(&o, &d, MAX_STEPS).len()
+            })
+            .sum();
+        let r = diag.norm() / 2.0;
+        implicit.area = 2.0 * std::f32::consts::PI * r * r * count as f32 / AREA_LINES as f32;
+        if implicit.area == 0.0 {
+            return Err("the implicit surface is too small to be sampled".into());
+        }
+        Ok(implicit)
+    }
+
+    //the first 'max' points where the line through o along d crosses the surface in the bound
+    fn crossings(&self, o: &P3, d: &V3, max: usize) -> Vec<P3> {
+        let r = self.aabb.diag().norm() / 2.0;
+        let ray = Ray::new(o - d * r, *d);
+        let mut result = vec![];
+        let mut t = 0.0;
+        while result.len() < max {
+            match self.test_hit(&ray, t, 2.0 * r) {
+                Some(hit) => {
+                    t = hit.dist + self.eps;
+                    result.push(hit.pos);
+                }
+                None => break,
+            }
+        }
+        result
+    }
+
+    //outward normal by the central differences
+    fn normal(&self, p: &P3) -> V3 {
+        let h = 10.0 * self.eps;
+        let d = |axis: usize| {
+            let mut e = V3::zeros();
+            e[axis] = h;
+            self.sdf.distance(&(p + e)) - self.sdf.distance(&(p - e))
+        };
+        let g = V3::new(d(0), d(1), d(2));
+        if g.norm_squared() > 0.0 {
+            g.normalize()
+        } else {
+            V3::z()
+        }
+    }
+}
+
+impl ShapeImpl for Implicit {
+    //steps by the distance on the side of the start, at least by 'eps',
+    //until the side changes and then bisects the last step
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let (t0, t1) = self.aabb.ray_intersect(ray, tnear, tfar)?;
+        if t0 > t1 {
+            return None;
+        }
+        let len = ray.dir.norm();
+        let side_distance = |t: f32| self.sdf.distance(&ray.at(t));
+        let side = if side_distance(t0) < 0.0 { -1.0 } else { 1.0 };
+        let (mut prev, mut t) = (t0, t0);
+        for _ in 0..MAX_STEPS {
+            let d = side * side_distance(t);
+            if d <= 0.0 {
+                break;
+            }
+            if t >= t1 {
+                return None;
+            }
+            prev = t;
+            t = (t + d.max(self.eps) / len).min(t1);
+        }
+        if side * side_distance(t) > 0.0 {
+            return None;
+        }
+
+        let (mut lo, mut hi) = (prev, t);
+        for _ in 0..32 {
+            let mid = 0.5 * (lo + hi);
+            if side * side_distance(mid) > 0.0 {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        let dist = hi;
+        if dist <= tnear {
+            return None;
+        }
+        let pos = ray.at(dist);
+        let gnorm = self.normal(&pos);
+        Some(Hit {
+            dist,
+            pos,
+            gnorm,
+            gx: pick_orthogonal(&gnorm),
+        })
+    }
+
+    //points where random lines cross the surface are distributed uniformly over the area.
+    //a line is accepted with probability n / MAX_CROSSINGS for n crossings, then one of them
+    //is chosen.
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        let (mut pos, mut fallback) = (None, None);
+        for _ in 0..MAX_REJECTIONS {
+            let (o, d) = random_line(&self.aabb, rng);
+            let i = rng.gen_range(0, MAX_CROSSINGS);
+            let crossings = self.crossings(&o, &d, i + 1);
+            fallback = fallback.or_else(|| crossings.first().copied());
+            pos = crossings.get(i).copied();
+            if pos.is_some() {
+                break;
+            }
+        }
+        //some of the lines of the area estimate crossed the surface, so this is rare
+        let pos = pos.or(fallback).unwrap_or_else(|| self.aabb.center());
+        pdf::PdfSample {
+            value: (pos, self.normal(&pos)),
+            pdf: 1.0 / self.area,
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area
+    }
+
+    fn aabb(&self) -> AABB {
+        self.aabb.clone()
+    }
+
+    //estimated by crofton's formula
+    fn area(&self) -> f32 {
+        self.area
+    }
+
+    fn uv(&self, pos: &P3) -> P2 {
+        spherical_uv(&(pos - self.aabb.center()))
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        spherical_tangent(&(pos - self.aabb.center()))
+    }
+}
+
+#[test]
+fn test_implicit() {
+    use rand::prelude::*;
+    let center = V3::new(1.0, 2.0, 3.0);
+    let ball = Implicit::new(Sdf::Sphere(1.0).translated(&center)).unwrap();
+    let ray = Ray::new(P3::new(1.5, 2.0, -5.0), V3::new(0.0, 0.0, 1.0));
+    let hit = ball.test_hit(&ray, 1e-3, 100.0).unwrap();
+    let z = 3.0 - 0.75f32.sqrt();
+    assert!((hit.pos - P3::new(1.5, 2.0, z)).norm() < 1e-3);
+    assert!((hit.gnorm - (hit.pos - center).coords).norm() < 1e-3);
+    //leaves from the inside
+    let inside = Ray::new(hit.pos, ray.dir);
+    let exit = ball.test_hit(&inside, 1e-3, 100.0).unwrap();
+    assert!((exit.pos - P3::new(1.5, 2.0, 6.0 - z)).norm() < 1e-3);
+    assert!(ball
+        .test_hit(&Ray::new(exit.pos, ray.dir), 1e-3, 100.0)
+        .is_none());
+    let pi = std::f32::consts::PI;
+    assert!((ball.area() - 4.0 * pi).abs() < 4.0 * pi * 0.03);
+    //the integral of z^2 over the unit sphere is 4 pi / 3
+    let mut rng = SmallRng::seed_from_u64(0);
+    let n = 20000;
+    let mut integral = 0.0;
+    for _ in 0..n {
+        let s = ball.sample_surface(&mut rng);
+        let (pos, normal) = s.value;
+        assert!(((pos - center).coords.norm() - 1.0).abs() < 1e-3);
+        assert!((normal - (pos - center).coords).norm() < 1e-2);
+        integral += (pos[2] - center[2]).powi(2) / s.pdf / n as f32;
+    }
+    assert!((integral - 4.0 * pi / 3.0).abs() < 4.0 * pi / 3.0 * 0.05);
+
+    //a box with a hole through it
+    let holed =
+        Implicit::new(Sdf::Cuboid(V3::new(1.0, 1.0, 1.0)).subtraction(Sdf::Cylinder(0.5, 2.0)))
+            .unwrap();
+    let down = V3::new(0.0, 0.0, -1.0);
+    assert!(holed
+        .test_hit(&Ray::new(P3::new(0.2, 0.0, 5.0), down), 1e-3, 100.0)
+        .is_none());
+    let hit = holed
+        .test_hit(&Ray::new(P3::new(0.7, 0.0, 5.0), down), 1e-3, 100.0)
+        .unwrap();
+    assert!((hit.dist - 4.0).abs() < 1e-3);
+
+    //disjoint solids have no intersection
+    let apart = Sdf::Sphere(1.0).intersection(Sdf::Sphere(1.0).translated(&V3::new(3.0, 0.0, 0.0)));
+    assert!(Implicit::new(apart).is_err());
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+//the part of the surface p^T a p + b^T p + c = 0 inside 'bounds'
+#[derive(Clone)]
+pub struct Quadric {
+    a: M3,
+    b: V3,
+    c: f32,
+    bounds: AABB,
+    area: f32,
+}
+
+//lines of the grid along each axis over which the area is integrated
+const AREA_GRID: usize = 512;
+
+impl Quadric {
+    pub fn new(a: M3, b: V3, c: f32, bounds: AABB) -> Result<Self, String> {
+        if a.iter().chain(b.iter()).any(|x| !x.is_finite()) || !c.is_finite() {
+            return Err("coefficients of a quadric have to be finite".into());
+        }
+        if a.iter().chain(b.iter()).all(|x| *x == 0.0) {
+            return Err("a quadric needs a or b nonzero".into());
+        }
+        if !(bounds.maxs - bounds.mins).iter().all(|x| *x > 0.0) {
+            return Err("empty bounds of a quadric".into());
+        }
+        //only the symmetric part matters
+        let a = (a + a.transpose()) * 0.5;
+        let mut quadric = Quadric {
+            a,
+            b,
+            c,
+            bounds,
+            area: 0.0,
+        };
+        quadric.area = quadric.integrate_area();
+        if quadric.area > 0.0 {
+            Ok(quadric)
+        } else {
+            Err("no surface of the quadric in the bounds".into())
+        }
+    }
+
+    pub fn sphere(center: &P3, radius: f32) -> Result<Self, String> {
+        if radius <= 0.0 || !radius.is_finite() {
+            return Err("radius of a sphere has to be positive".into());
+        }
+        let r = V3::new(radius, radius, radius);
+        Ok(Quadric {
+            a: M3::identity(),
+            b: -2.0 * center.coords,
+            c: center.coords.norm_squared() - radius * radius,
+            bounds: AABB::new(&(center - r * 1.01), &(center + r * 1.01)),
+            area: 4.0 * std::f32::consts::PI * radius * radius,
+        })
+    }
+
+    //distances along the line o + t d to the points on the surface in the bounds
+    fn intersections(&self, o: &P3, d: &V3) -> Vec<f32> {
+        let ad = self.a * d;
+        let qa = d.dot(&ad);
+        let qb = 2.0 * o.coords.dot(&ad) + self.b.dot(d);
+        let qc = o.coords.dot(&(self.a * o.coords)) + self.b.dot(&o.coords) + self.c;
+        let (t0, t1) = match solve_quadratic(qa, qb, qc) {
+            Some(ts) => ts,
+            None => return vec![],
+        };
+        let ts = if t0 == t1 { vec![t0] } else { vec![t0, t1] };
+        ts.into_iter()
+            .filter(|t| self.bounds.inside(&(o + d * *t)))
+            .collect()
+    }
+
+    //the area is the sum over the axes k of the integrals of |n_k| over the surface, each of
+    //which is the integral over the lines along k of |n_k| at their crossings.
+    //|n_k| vanishes where the lines graze the surface, so the midpoint rule converges quickly.
+    fn integrate_area(&self) -> f32 {
+        let size = self.bounds.maxs - self.bounds.mins;
+        (0..3)
+            .map(|k| {
+                let (i, j) = ((k + 1) % 3, (k + 2) % 3);
+                let (di, dj) = (size[i] / AREA_GRID as f32, size[j] / AREA_GRID as f32);
+                let mut d = V3::zeros();
+                d[k] = 1.0;
+                let mut sum = 0.0f64;
+                for x in 0..AREA_GRID {
+                    for y in 0..AREA_GRID {
+                        let mut o = self.bounds.mins;
+                        o[i] += (x as f32 + 0.5) * di;
+                        o[j] += (y as f32 + 0.5) * dj;
+                        for t in self.intersections(&o, &d) {
+                            sum += self.normal_at(&(o + d * t))[k].abs() as f64;
+                        }
+                    }
+                }
+                sum as f32 * di * dj
+            })
+            .sum()
+    }
+
+    fn normal_at(&self, p: &P3) -> V3 {
+        (2.0 * self.a * p.coords + self.b).normalize()
+    }
+}
+
+impl ShapeImpl for Quadric {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let dist = self
+            .intersections(&ray.origin, &ray.dir)
+            .into_iter()
+            .find(|t| tnear < *t && *t < tfar)?;
+        let pos = ray.at(dist);
+        let gnorm = self.normal_at(&pos);
+        Some(Hit {
+            dist,
+            pos,
+            gnorm,
+            gx: pick_orthogonal(&gnorm),
+        })
+    }
+
+    //points where random lines cross the surface are distributed uniformly over the area.
+    //a line is accepted with probability n / 2 for n intersections, then one of them is chosen.
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        let p = loop {
+            let (o, d) = random_line(&self.bounds, rng);
+            let ts = self.intersections(&o, &d);
+            let i = rng.gen_range(0, 2);
+            if let Some(t) = ts.get(i) {
+                break o + d * *t;
+            }
+        };
+        pdf::PdfSample {
+            value: (p, self.normal_at(&p)),
+            pdf: 1.0 / self.area,
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area
+    }
+
+    fn aabb(&self) -> AABB {
+        self.bounds.clone()
+    }
+
+    //no natural parametrization, so the direction from the center of the bounds is used
+    fn uv(&self, pos: &P3) -> P2 {
+        spherical_uv(&(pos - self.bounds.center()))
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        spherical_tangent(&(pos - self.bounds.center()))
+    }
+
+    fn area(&self) -> f32 {
+        self.area
+    }
+}
+
+#[test]
+fn test_quadric_area() {
+    let q = Quadric::sphere(&P3::new(1.0, 2.0, 3.0), 2.0).unwrap();
+    let expected = 4.0 * std::f32::consts::PI * 4.0;
+    assert!((q.area() - expected).abs() < expected * 1e-5);
+
+    //the same sphere by the coefficients
+    let bounds = AABB::new(&P3::new(-2.5, -2.5, -2.5), &P3::new(2.5, 2.5, 2.5));
+    let q = Quadric::new(M3::identity(), V3::zeros(), -4.0, bounds).unwrap();
+    let expected = 4.0 * std::f32::consts::PI * 4.0;
+    assert!((q.area() - expected).abs() < expected * 2e-3);
+
+    //a cylinder of radius 1 cut to the height 2
+    let q = Quadric::new(
+        M3::from_diagonal(&V3::new(1.0, 1.0, 0.0)),
+        V3::zeros(),
+        -1.0,
+        AABB::new(&P3::new(-1.5, -1.5, 0.0), &P3::new(1.5, 1.5, 2.0)),
+    )
+    .unwrap();
+    let expected = 4.0 * std::f32::consts::PI;
+    assert!((q.area() - expected).abs() < expected * 2e-3);
+    assert!((super::sample_fraction(&q, |p| p.z > 1.0) - 0.5).abs() < 0.02);
+
+    assert!(Quadric::sphere(&P3::origin(), 0.0).is_err());
+    assert!(Quadric::new(M3::zeros(), V3::zeros(), 1.0, q.aabb()).is_err());
+    assert!(Quadric::new(M3::identity(), V3::zeros(), f32::NAN, q.aabb()).is_err());
+    //the sphere lies outside the bounds
+    let bounds = AABB::new(&P3::new(5.0, 5.0, 5.0), &P3::new(6.0, 6.0, 6.0));
+    assert!(Quadric::new(M3::identity(), V3::zeros(), -1.0, bounds).is_err());
+}
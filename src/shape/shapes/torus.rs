@@ -0,0 +1,182 @@
+use super::*;
+
+//torus around 'axis' through 'center'. 'major_radius' is the distance from the center to the
+//center of the tube and 'minor_radius' is the radius of the tube.
+#[derive(Clone)]
+pub struct Torus {
+    lc: LocalCoord,
+    major_radius: f32,
+    minor_radius: f32,
+}
+
+//evaluates the polynomial with coefficients in descending order
+fn eval_poly(coeffs: &[f64], t: f64) -> f64 {
+    coeffs.iter().fold(0.0, |acc, c| acc * t + c)
+}
+
+//real roots in [lo, hi] in ascending order.
+//the roots of the derivative split the interval into monotonic pieces, which are bisected.
+fn poly_roots(coeffs: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = coeffs.len() - 1;
+    if degree == 0 {
+        return vec![];
+    }
+    if degree == 1 {
+        let t = -coeffs[1] / coeffs[0];
+        return if lo <= t && t <= hi { vec![t] } else { vec![] };
+    }
+    let derivative: Vec<f64> = coeffs[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect();
+    let mut ends = vec![lo];
+    ends.extend(poly_roots(&derivative, lo, hi));
+    ends.push(hi);
+
+    let mut roots = vec![];
+    for w in ends.windows(2) {
+        let (mut a, mut b) = (w[0], w[1]);
+        let (fa, fb) = (eval_poly(coeffs, a), eval_poly(coeffs, b));
+        if fa == 0.0 {
+            roots.push(a);
+            continue;
+        }
+        if fa.signum() == fb.signum() {
+            continue;
+        }
+        for _ in 0..64 {
+            let m = 0.5 * (a + b);
+            if eval_poly(coeffs, m).signum() == fa.signum() {
+                a = m;
+            } else {
+                b = m;
+            }
+        }
+        roots.push(0.5 * (a + b));
+    }
+    roots
+}
+
+impl Torus {
+    pub fn new(center: &P3, axis: &V3, major_radius: f32, minor_radius: f32) -> Self {
+        assert!(0.0 < minor_radius && minor_radius <= major_radius);
+        Torus {
+            lc: axis_frame(center, axis),
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    //normal and tangent in local coordinates
+    fn frame_at(&self, p: &P3) -> (V3, V3) {
+        let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        let (c, s) = (p[0] / rho, p[1] / rho);
+        let center = V3::new(c, s, 0.0) * self.major_radius;
+        ((p.coords - center).normalize(), V3::new(-s, c, 0.0))
+    }
+
+    fn local_aabb(&self) -> AABB {
+        let xy = self.major_radius + self.minor_radius;
+        let z = self.minor_radius;
+        AABB::new(&P3::new(-xy, -xy, -z), &P3::new(xy, xy, z))
+    }
+}
+
+impl ShapeImpl for Torus {
+    fn test_hit(&self, ray: &Ray, tnear: f32, tfar: f32) -> Option<Hit> {
+        let ray_local = self.lc.w2l() * ray.clone();
+        //the bounding box also makes the interval finite
+        let (lo, hi) = self.local_aabb().ray_intersect(&ray_local, tnear, tfar)?;
+        let margin = 1e-3 * self.minor_radius as f64;
+
+        let o = ray_local.origin.coords.map(|x| x as f64);
+        let d = ray_local.dir.map(|x| x as f64);
+        let sq_major = (self.major_radius as f64).powi(2);
+        let sq_minor = (self.minor_radius as f64).powi(2);
+        let g = d.norm_squared();
+        let f = o.dot(&d);
+        let e = o.norm_squared() + sq_major - sq_minor;
+        let coeffs = [
+            g * g,
+            4.0 * g * f,
+            4.0 * f * f + 2.0 * g * e - 4.0 * sq_major * (d[0] * d[0] + d[1] * d[1]),
+            4.0 * f * e - 8.0 * sq_major * (o[0] * d[0] + o[1] * d[1]),
+            e * e - 4.0 * sq_major * (o[0] * o[0] + o[1] * o[1]),
+        ];
+        let dist = poly_roots(&coeffs, lo as f64 - margin, hi as f64 + margin)
+            .into_iter()
+            .map(|t| t as f32)
+            .find(|t| tnear < *t && *t < tfar)?;
+
+        let pos = ray_local.at(dist);
+        let (n, x) = self.frame_at(&pos);
+        Some(Hit {
+            dist,
+            pos: self.lc.l2w() * pos,
+            gnorm: self.lc.l2w() * n,
+            gx: self.lc.l2w() * x,
+        })
+    }
+
+    fn sample_surface<R: Rng + ?Sized>(&self, rng: &mut R) -> pdf::PdfSample<(P3, V3)> {
+        use rand::distributions::Uniform;
+        let angle = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI);
+        let (big_r, r) = (self.major_radius, self.minor_radius);
+        //the area element is proportional to the distance from the axis
+        let phi = loop {
+            let phi = angle.sample(rng);
+            if Uniform::new(0.0, big_r + r).sample(rng) < big_r + r * phi.cos() {
+                break phi;
+            }
+        };
+        let theta = angle.sample(rng);
+        let rho = big_r + r * phi.cos();
+        let p = P3::new(rho * theta.cos(), rho * theta.sin(), r * phi.sin());
+        let (n, _) = self.frame_at(&p);
+        pdf::PdfSample {
+            value: (self.lc.l2w() * p, self.lc.l2w() * n),
+            pdf: 1.0 / self.area(),
+        }
+    }
+
+    fn sample_surface_pdf(&self, _pos: &P3) -> f32 {
+        1.0 / self.area()
+    }
+
+    fn aabb(&self) -> AABB {
+        self.local_aabb().transformed(self.lc.l2w())
+    }
+
+    //the angles around the axis and around the tube
+    fn uv(&self, pos: &P3) -> P2 {
+        let p = self.lc.w2l() * pos;
+        let (n, _) = self.frame_at(&p);
+        let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        let theta = n[2].atan2(V3::new(p[0], p[1], 0.0).dot(&n) / rho.max(1e-12));
+        P2::new(
+            spherical_uv(&p.coords)[0],
+            0.5 + theta * std::f32::consts::FRAC_1_PI / 2.0,
+        )
+    }
+
+    fn tangent(&self, pos: &P3) -> V3 {
+        self.lc.l2w() * spherical_tangent(&(self.lc.w2l() * pos).coords)
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * std::f32::consts::PI * std::f32::consts::PI * self.major_radius * self.minor_radius
+    }
+}
+
+#[test]
+fn test_poly_roots() {
+    //(t - 1)(t - 2)(t - 3)(t - 4)
+    let coeffs = [1.0, -10.0, 35.0, -50.0, 24.0];
+    let roots = poly_roots(&coeffs, 0.0, 10.0);
+    assert_eq!(roots.len(), 4);
+    for (r, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0].iter()) {
+        assert!((r - expected).abs() < 1e-9);
+    }
+    assert_eq!(poly_roots(&coeffs, 1.5, 2.5).len(), 1);
+}
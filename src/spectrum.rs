@@ -0,0 +1,250 @@
+//spectral rendering with hero wavelength sampling.
+//
+//a sample carries N_WAVELENGTHS wavelengths, and the r, g, b slots of every RGB value along the
+//path (throughput, bsdf, emission) hold the values at these wavelengths instead of colors.
+//RGB parameters of the scene are uplifted into spectra when they are hit, and the radiance is
+//converted back into sRGB through XYZ when it reaches the film.
+use crate::accum::*;
+use crate::*;
+use rand::prelude::*;
+
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+pub const N_WAVELENGTHS: usize = 3;
+
+//integral of ybar over [LAMBDA_MIN, LAMBDA_MAX]
+const CIE_Y_INTEGRAL: f32 = 106.912;
+//sRGB of the constant spectrum 1, which is scaled to white
+const WHITE: [f32; 3] = [1.2006, 0.9497, 0.9078];
+
+fn gaussian(lambda: f32, mu: f32, sigma_lower: f32, sigma_upper: f32) -> f32 {
+    let sigma = if lambda < mu {
+        sigma_lower
+    } else {
+        sigma_upper
+    };
+    let t = (lambda - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+//analytic fit of the CIE 1931 color matching functions (Wyman et al. 2013)
+pub fn xyz_bar(lambda: f32) -> [f32; 3] {
+    let g = |mu, s1, s2| gaussian(lambda, mu, s1, s2);
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+pub fn xyz_to_srgb(xyz: &[f32; 3]) -> RGB {
+    let [x, y, z] = *xyz;
+    RGB::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+fn smoothstep(edge: f32, width: f32, lambda: f32) -> f32 {
+    let t = ((lambda - edge) / width * 0.5 + 0.5).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+//spectrum of an RGB value. the basis spectra for r, g and b sum up to 1,
+//so white is the constant spectrum and reflectances in [0, 1] stay in [0, 1].
+pub fn uplift(rgb: &RGB, lambda: f32) -> f32 {
+    let b = 1.0 - smoothstep(490.0, 30.0, lambda);
+    let r = smoothstep(590.0, 30.0, lambda);
+    let g = 1.0 - r - b;
+    rgb.r * r + rgb.g * g + rgb.b * b
+}
+
+//spectral radiance of a blackbody by Planck's law in W / (sr m^2 nm)
+pub fn planck(lambda: f32, temperature: f32) -> f32 {
+    const C: f64 = 2.997_924_58e8;
+    const H: f64 = 6.626_070_15e-34;
+    const KB: f64 = 1.380_649e-23;
+    let l = lambda as f64 * 1e-9;
+    let radiance =
+        2.0 * H * C * C / l.powi(5) / ((H * C / (l * KB * temperature as f64)).exp() - 1.0);
+    (radiance * 1e-9) as f32
+}
+
+//the sRGB of a spectrum, which 'Wavelengths::to_rgb' estimates
+pub fn spectrum_to_rgb(spectrum: impl Fn(f32) -> f32) -> RGB {
+    let mut xyz = [0.0; 3];
+    let mut lambda = LAMBDA_MIN + 0.5;
+    while lambda < LAMBDA_MAX {
+        let bar = xyz_bar(lambda);
+        let v = spectrum(lambda);
+        for i in 0..3 {
+            xyz[i] += v * bar[i] / CIE_Y_INTEGRAL;
+        }
+        lambda += 1.0;
+    }
+    let rgb = xyz_to_srgb(&xyz);
+    RGB::new(rgb.r / WHITE[0], rgb.g / WHITE[1], rgb.b / WHITE[2])
+}
+
+//spectrum of a blackbody scaled to the luminance of the constant spectrum 1
+#[derive(Clone, Debug)]
+pub struct Blackbody {
+    pub temperature: f32,
+    scale: f32,
+    rgb: RGB,
+}
+
+impl Blackbody {
+    pub fn new(temperature: f32) -> Self {
+        let mut y = 0.0;
+        let mut lambda = LAMBDA_MIN + 0.5;
+        while lambda < LAMBDA_MAX {
+            y += planck(lambda, temperature) * xyz_bar(lambda)[1];
+            lambda += 1.0;
+        }
+        let scale = CIE_Y_INTEGRAL / y;
+        let rgb = spectrum_to_rgb(|lambda| planck(lambda, temperature) * scale);
+        Blackbody {
+            temperature,
+            scale,
+            rgb,
+        }
+    }
+
+    pub fn at(&self, lambda: f32) -> f32 {
+        planck(lambda, self.temperature) * self.scale
+    }
+
+    pub fn rgb(&self) -> RGB {
+        self.rgb
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Wavelengths {
+    //the first one is the hero wavelength
+    pub lambda: [f32; N_WAVELENGTHS],
+    //true after the secondary wavelengths are terminated
+    pub single: bool,
+}
+
+impl Wavelengths {
+    //the secondary wavelengths are the hero rotated by equal steps in the range
+    pub fn sample<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        use rand::distributions::Uniform;
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = Uniform::new(LAMBDA_MIN, LAMBDA_MAX).sample(rng);
+        let mut lambda = [hero; N_WAVELENGTHS];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            *l = LAMBDA_MIN + (hero - LAMBDA_MIN + range * i as f32 / N_WAVELENGTHS as f32) % range;
+        }
+        Wavelengths {
+            lambda,
+            single: false,
+        }
+    }
+
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn blackbody(&self, blackbody: &Blackbody) -> RGB {
+        RGB::new(
+            blackbody.at(self.lambda[0]),
+            blackbody.at(self.lambda[1]),
+            blackbody.at(self.lambda[2]),
+        )
+    }
+
+    pub fn uplift(&self, rgb: &RGB) -> RGB {
+        RGB::new(
+            uplift(rgb, self.lambda[0]),
+            uplift(rgb, self.lambda[1]),
+            uplift(rgb, self.lambda[2]),
+        )
+    }
+
+    //keeps the hero wavelength only. it takes over the weight of the terminated ones.
+    pub fn hero_only(&self, values: &RGB) -> RGB {
+        if self.single {
+            RGB::new(values.r, 0.0, 0.0)
+        } else {
+            RGB::new(values.r * N_WAVELENGTHS as f32, 0.0, 0.0)
+        }
+    }
+
+    pub fn terminate_secondary(mut self) -> Self {
+        self.single = true;
+        self
+    }
+
+    //the sRGB estimate of radiance whose values at the wavelengths are in the slots
+    pub fn to_rgb(&self, values: &RGB) -> RGB {
+        let values = [values.r, values.g, values.b];
+        let mut xyz = [0.0; 3];
+        for (v, lambda) in values.iter().zip(self.lambda.iter()) {
+            let bar = xyz_bar(*lambda);
+            for i in 0..3 {
+                xyz[i] += v * bar[i];
+            }
+        }
+        //each wavelength is sampled with pdf 1 / (LAMBDA_MAX - LAMBDA_MIN)
+        let scale = (LAMBDA_MAX - LAMBDA_MIN) / (N_WAVELENGTHS as f32 * CIE_Y_INTEGRAL);
+        for c in xyz.iter_mut() {
+            *c *= scale;
+        }
+        let rgb = xyz_to_srgb(&xyz);
+        RGB::new(rgb.r / WHITE[0], rgb.g / WHITE[1], rgb.b / WHITE[2])
+    }
+}
+
+//converts spectral samples into RGB before passing them to the accumulator
+pub struct SpectralAccumulator<'a, T> {
+    pub accum: &'a mut T,
+    pub wavelengths: Wavelengths,
+}
+
+impl<T: Accumulator> Accumulator for SpectralAccumulator<'_, T> {
+    fn accum(&mut self, (color, len): &(RGB, usize)) {
+        self.accum.accum(&(self.wavelengths.to_rgb(color), *len))
+    }
+
+    fn accum_path(&mut self, color: &RGB, events: &[lpe::Event]) {
+        self.accum
+            .accum_path(&self.wavelengths.to_rgb(color), events)
+    }
+
+    fn merge(&mut self, another: &Self) {
+        self.accum.merge(another.accum)
+    }
+
+    fn is_finite(&self) -> bool {
+        self.accum.is_finite()
+    }
+
+    fn reset(&mut self) {
+        self.accum.reset()
+    }
+
+    fn scale(&mut self, a: f32) {
+        self.accum.scale(a)
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    for rgb in [RGB::all(1.0), RGB::new(0.2, 0.5, 0.8)].iter() {
+        let mut sum = RGB::all(0.0);
+        let n = 100000;
+        for _ in 0..n {
+            let w = Wavelengths::sample(&mut rng);
+            sum += w.to_rgb(&w.uplift(rgb));
+        }
+        let avg = sum / n as f32;
+        assert!((avg.r - rgb.r).abs() < 0.05);
+        assert!((avg.g - rgb.g).abs() < 0.05);
+        assert!((avg.b - rgb.b).abs() < 0.05);
+    }
+}
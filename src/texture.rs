@@ -0,0 +1,208 @@
+//colors on surfaces looked up by the texture coordinates of shapes
+use crate::*;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub enum Texture {
+    Constant(RGB),
+    //image repeated over the texture coordinates and multiplied by the color
+    Image(Arc<image::Image>, RGB),
+}
+
+impl From<RGB> for Texture {
+    fn from(color: RGB) -> Self {
+        Texture::Constant(color)
+    }
+}
+
+impl std::fmt::Debug for Texture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Texture::Constant(color) => write!(f, "Constant({:?})", color),
+            Texture::Image(image, color) => {
+                write!(f, "Image({}x{}, {:?})", image.w(), image.h(), color)
+            }
+        }
+    }
+}
+
+impl Texture {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let image = image::Image::read_exr16(file).ok_or(format!("{}: failed to read", file))?;
+        Ok(Texture::Image(Arc::new(image), RGB::all(1.0)))
+    }
+
+    pub fn set_scale(self, scale: RGB) -> Self {
+        match self {
+            Texture::Constant(color) => Texture::Constant(color * scale),
+            Texture::Image(image, color) => Texture::Image(image, color * scale),
+        }
+    }
+
+    //bilinearly interpolated. v = 0 is the bottom row of the image.
+    pub fn at(&self, uv: &P2) -> RGB {
+        let (image, color) = match self {
+            Texture::Constant(color) => return *color,
+            Texture::Image(image, color) => (image, color),
+        };
+        let (w, h) = (image.w() as i64, image.h() as i64);
+        let x = uv[0] * w as f32 - 0.5;
+        let y = (1.0 - uv[1]) * h as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let at = |dx: i64, dy: i64| {
+            let ix = (x0 as i64 + dx).rem_euclid(w) as usize;
+            let iy = (y0 as i64 + dy).rem_euclid(h) as usize;
+            *image.at(ix, iy)
+        };
+        let top = at(0, 0) * (1.0 - tx) + at(1, 0) * tx;
+        let bottom = at(0, 1) * (1.0 - tx) + at(1, 1) * tx;
+        (top * (1.0 - ty) + bottom * ty) * *color
+    }
+}
+
+//perturbation of shading normals in the frame of the tangent, the bitangent and the normal
+#[derive(Clone, Debug)]
+pub enum NormalMap {
+    //normals encoded as (n + 1) / 2 in the colors
+    Normal(Texture),
+    //heights in the red channel, scaled into the slopes per texture coordinate
+    Bump(Texture, f32),
+}
+
+impl NormalMap {
+    //the normal in the frame
+    fn local_normal(&self, uv: &P2) -> V3 {
+        match self {
+            NormalMap::Normal(texture) => {
+                let c = texture.at(uv);
+                V3::new(c.r * 2.0 - 1.0, c.g * 2.0 - 1.0, c.b * 2.0 - 1.0)
+            }
+            NormalMap::Bump(texture, scale) => {
+                //forward differences over a texel
+                let (du, dv) = match texture {
+                    Texture::Constant(_) => return V3::z(),
+                    Texture::Image(image, _) => (1.0 / image.w() as f32, 1.0 / image.h() as f32),
+                };
+                let h = texture.at(uv).r;
+                let dhdu = (texture.at(&P2::new(uv[0] + du, uv[1])).r - h) / du;
+                let dhdv = (texture.at(&P2::new(uv[0], uv[1] + dv)).r - h) / dv;
+                V3::new(-scale * dhdu, -scale * dhdv, 1.0)
+            }
+        }
+    }
+
+    //the perturbed normal. 'tangent' gives the direction of u.
+    pub fn apply(&self, uv: &P2, normal: &V3, tangent: &V3) -> V3 {
+        let b = normal.cross(tangent).normalize();
+        let t = b.cross(normal);
+        let n = self.local_normal(uv);
+        let perturbed = t * n[0] + b * n[1] + normal * n[2];
+        if perturbed.norm_squared() > 0.0 {
+            perturbed.normalize()
+        } else {
+            *normal
+        }
+    }
+}
+
+//rotation of the tangents about the shading normals, orienting anisotropic materials
+#[derive(Clone, Debug)]
+pub enum TangentMap {
+    //directions encoded as (t + 1) / 2 in the red and green channels, in the frame of the tangent
+    //and the bitangent (flow maps)
+    Flow(Texture),
+    //angles from the tangent in the red channel, in turns
+    Rotation(Texture),
+}
+
+impl TangentMap {
+    //the rotated tangent, perpendicular to the normal
+    pub fn apply(&self, uv: &P2, normal: &V3, tangent: &V3) -> V3 {
+        let b = normal.cross(tangent).normalize();
+        let t = b.cross(normal);
+        let (x, y) = match self {
+            TangentMap::Flow(texture) => {
+                let c = texture.at(uv);
+                (c.r * 2.0 - 1.0, c.g * 2.0 - 1.0)
+            }
+            TangentMap::Rotation(texture) => {
+                let angle = 2.0 * std::f32::consts::PI * texture.at(uv).r;
+                (angle.cos(), angle.sin())
+            }
+        };
+        let rotated = t * x + b * y;
+        if rotated.norm_squared() > 0.0 {
+            rotated.normalize()
+        } else {
+            t
+        }
+    }
+}
+
+//opacity of surfaces in the red channel, to cut out shapes
+#[derive(Clone, Debug)]
+pub enum AlphaMask {
+    //opaque where the opacity is at least the threshold
+    Threshold(Texture, f32),
+    //opaque with the probability of the opacity
+    Stochastic(Texture),
+}
+
+impl AlphaMask {
+    //the opacity expected over the decisions
+    pub fn opacity(&self, uv: &P2) -> f32 {
+        match self {
+            AlphaMask::Threshold(texture, threshold) => {
+                if texture.at(uv).r >= *threshold {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            AlphaMask::Stochastic(texture) => texture.at(uv).r.clamp(0.0, 1.0),
+        }
+    }
+
+    //'u' in [0, 1) decides stochastic masks
+    pub fn is_opaque(&self, uv: &P2, u: f32) -> bool {
+        match self {
+            AlphaMask::Threshold(..) => self.opacity(uv) > 0.0,
+            AlphaMask::Stochastic(..) => u < self.opacity(uv),
+        }
+    }
+}
+
+#[test]
+fn test_texture_at() {
+    let mut image = image::Image::new(2, 1);
+    *image.at_mut(1, 0) = RGB::all(1.0);
+    let texture = Texture::Image(Arc::new(image), RGB::all(2.0));
+    //centers of the pixels, the middle between them and the wrapped edge
+    assert_eq!(texture.at(&P2::new(0.25, 0.5)).r, 0.0);
+    assert_eq!(texture.at(&P2::new(0.75, 0.5)).r, 2.0);
+    assert_eq!(texture.at(&P2::new(0.5, 0.5)).r, 1.0);
+    assert_eq!(texture.at(&P2::new(1.0, 0.5)).r, 1.0);
+}
+
+#[test]
+fn test_bump_map() {
+    //the height increases along u, so the normal tilts toward -u
+    let mut image = image::Image::new(4, 1);
+    for i in 0..4 {
+        *image.at_mut(i, 0) = RGB::all(i as f32);
+    }
+    let bump = NormalMap::Bump(Texture::Image(Arc::new(image), RGB::all(1.0)), 0.01);
+    let n = bump.apply(&P2::new(0.3, 0.5), &V3::z(), &V3::x());
+    assert!((n - V3::new(-0.04, 0.0, 1.0).normalize()).norm() < 1e-4);
+}
+
+#[test]
+fn test_tangent_map() {
+    let flow = TangentMap::Flow(Texture::from(RGB::new(0.5, 1.0, 0.5)));
+    let t = flow.apply(&P2::origin(), &V3::z(), &V3::new(1.0, 0.0, 0.3));
+    assert!((t - V3::y()).norm() < 1e-5);
+    let rotation = TangentMap::Rotation(Texture::from(RGB::all(0.5)));
+    let t = rotation.apply(&P2::origin(), &V3::z(), &V3::x());
+    assert!((t + V3::x()).norm() < 1e-5);
+}